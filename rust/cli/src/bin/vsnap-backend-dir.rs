@@ -0,0 +1,223 @@
+//! Reference remote plugin that keeps snapshots in a plain directory, e.g.
+//! `vsnap push snapshot-a dir:///mnt/backup/snapshot-a`.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+    process::ExitCode,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::Value;
+use vsnap::library::{
+    error::Error,
+    metadata::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST, SnapshotMetadata},
+    remote::{
+        RemoteSnapshot,
+        plugin::{PROTOCOL_VERSION, PluginOperation, PluginRequest, PluginResponse},
+    },
+};
+
+fn main() -> ExitCode {
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = io::stdout().lock();
+
+    let (response, archive) = match handle(&mut input) {
+        Ok(result) => result,
+        Err(e) => (
+            PluginResponse {
+                error: Some(format!("{:#}", e)),
+                not_found: matches!(e.downcast_ref(), Some(Error::SnapshotNotFound(_))),
+                ..Default::default()
+            },
+            None,
+        ),
+    };
+
+    let failed = response.error.is_some();
+
+    match send(&mut output, &response, archive) {
+        Ok(()) if !failed => ExitCode::SUCCESS,
+        Ok(()) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn handle(input: &mut impl BufRead) -> Result<(PluginResponse, Option<File>)> {
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+
+    let request: PluginRequest = serde_json::from_str(&line)?;
+
+    if request.version != PROTOCOL_VERSION {
+        bail!("Unsupported protocol version {}", request.version);
+    }
+
+    match request.operation {
+        PluginOperation::Put {
+            location,
+            archive_name,
+            archive_size,
+            metadata,
+        } => {
+            put(
+                Path::new(&location),
+                &archive_name,
+                archive_size,
+                &metadata,
+                input,
+            )?;
+
+            Ok((PluginResponse::default(), None))
+        }
+        PluginOperation::Get { location } => {
+            let (metadata, archive) = get(Path::new(&location))?;
+
+            Ok((
+                PluginResponse {
+                    metadata: Some(metadata),
+                    ..Default::default()
+                },
+                Some(archive),
+            ))
+        }
+        PluginOperation::List { location } => {
+            let mut snapshots = vec![];
+
+            if Path::new(&location).is_dir() {
+                list(Path::new(&location), &mut snapshots)?;
+            }
+
+            snapshots.sort_by(|a, b| a.remote.cmp(&b.remote));
+
+            Ok((
+                PluginResponse {
+                    snapshots: Some(snapshots),
+                    ..Default::default()
+                },
+                None,
+            ))
+        }
+        PluginOperation::Delete { location } => {
+            let dir = Path::new(&location);
+
+            if !dir.join(SNAPSHOT_METADATA).is_file() {
+                return Err(Error::SnapshotNotFound(location).into());
+            }
+
+            remove_snapshot(dir)?;
+
+            // Only succeeds when nothing else is kept in the directory.
+            let _ = fs::remove_dir(dir);
+
+            Ok((PluginResponse::default(), None))
+        }
+    }
+}
+
+fn send(output: &mut impl Write, response: &PluginResponse, archive: Option<File>) -> Result<()> {
+    serde_json::to_writer(&mut *output, response)?;
+    output.write_all(b"\n")?;
+
+    if let Some(mut archive) = archive {
+        io::copy(&mut archive, output)?;
+    }
+
+    Ok(output.flush()?)
+}
+
+fn put(
+    dir: &Path,
+    archive_name: &str,
+    archive_size: u64,
+    metadata: &Value,
+    input: &mut impl Read,
+) -> Result<()> {
+    if ![SNAPSHOT_TAR, SNAPSHOT_TAR_ZST].contains(&archive_name) {
+        bail!("Unexpected archive name {}", archive_name);
+    }
+
+    fs::create_dir_all(dir)?;
+    remove_snapshot(dir)?;
+
+    let path = dir.join(archive_name);
+    let copied = io::copy(&mut input.take(archive_size), &mut File::create(&path)?)?;
+
+    // vsnap ends stdin once the archive made it, or kills the plugin when it didn't.
+    let complete = copied == archive_size && input.read(&mut [0])? == 0;
+
+    if !complete {
+        fs::remove_file(&path)?;
+        bail!("Expected an archive of {} bytes", archive_size);
+    }
+
+    // Written last, a snapshot without it is incomplete.
+    fs::write(dir.join(SNAPSHOT_METADATA), serde_json::to_vec(metadata)?)?;
+
+    Ok(())
+}
+
+fn get(dir: &Path) -> Result<(Value, File)> {
+    let content = match fs::read(dir.join(SNAPSHOT_METADATA)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::SnapshotNotFound(dir.display().to_string()).into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let archive_name = serde_json::from_slice::<SnapshotMetadata>(&content)?.archive_name();
+
+    Ok((
+        serde_json::from_slice(&content)?,
+        File::open(dir.join(archive_name))?,
+    ))
+}
+
+/// Snapshots in `dir` and below it.
+fn list(dir: &Path, snapshots: &mut Vec<RemoteSnapshot>) -> Result<()> {
+    let metadata_path = dir.join(SNAPSHOT_METADATA);
+
+    if metadata_path.is_file() {
+        let metadata = SnapshotMetadata::read(&metadata_path)?;
+        let modified_at = fs::metadata(&metadata_path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)?
+            .as_secs() as i64;
+
+        snapshots.push(RemoteSnapshot {
+            remote: dir.display().to_string(),
+            size: fs::metadata(dir.join(metadata.archive_name()))
+                .ok()
+                .map(|archive| archive.len()),
+            modified_at: Some(modified_at),
+        });
+    }
+
+    for entry in fs::read_dir(dir).map_err(|e| anyhow!("{}: {}", dir.display(), e))? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            list(&path, snapshots)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a snapshot, its metadata first so that it stops looking complete.
+fn remove_snapshot(dir: &Path) -> Result<()> {
+    for name in [SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST] {
+        match fs::remove_file(dir.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
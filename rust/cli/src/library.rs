@@ -1,13 +1,66 @@
+use indicatif::HumanBytes;
 use serde::{Deserialize, Serialize};
 
+pub mod backend;
+pub mod batch;
+pub mod binary;
+pub mod cancel;
+pub mod capabilities;
+pub mod channel;
 pub mod cli;
+pub mod client;
+pub mod config;
+pub mod connection;
 pub mod constant;
+pub mod directory;
 pub mod docker;
+pub mod doctor;
+pub mod error;
+pub mod gc;
+pub mod journal;
+pub mod listing;
+pub mod lock;
+pub mod metadata;
+pub mod output;
+pub mod pattern;
 pub mod progress;
+pub mod registry;
+pub mod remote;
+pub mod state;
+pub mod storage;
 pub mod table;
+pub mod volume;
 
 #[derive(Serialize, Deserialize)]
 pub struct Progress {
     pub progress: u64,
     pub total: u64,
 }
+
+/// A warning the runner prints between progress lines.
+#[derive(Serialize, Deserialize)]
+pub struct RunnerWarning {
+    pub warning: String,
+}
+
+/// Space on the filesystem backing a volume, as reported by `vsnap-runner free-space`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct DiskSpace {
+    pub available: u64,
+    pub total: u64,
+}
+
+impl DiskSpace {
+    /// Explains why `required` bytes don't fit, if they don't.
+    pub fn check_fits(&self, required: u64) -> Result<(), String> {
+        match required > self.available {
+            true => Err(format!(
+                "needs {} but only {} of {} are free",
+                HumanBytes(required),
+                HumanBytes(self.available),
+                HumanBytes(self.total)
+            )),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use crate::library::cancel::Cancellation;
+
+pub mod docker;
+pub mod fake;
+
+#[derive(Clone, Debug, Default)]
+pub struct VolumeInfo {
+    pub name: String,
+    pub driver: String,
+    pub driver_opts: HashMap<String, String>,
+    pub labels: HashMap<String, String>,
+}
+
+/// How to create a volume. Without a driver, the engine's default driver is used.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VolumeSpec {
+    pub driver: Option<String>,
+    pub driver_opts: HashMap<String, String>,
+    pub labels: HashMap<String, String>,
+}
+
+impl From<HashMap<String, String>> for VolumeSpec {
+    fn from(labels: HashMap<String, String>) -> Self {
+        VolumeSpec {
+            labels,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub image: String,
+    pub state: Option<String>,
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum VolumeSize {
+    Bytes(i64),
+    Unavailable,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MountSource {
+    Volume(String),
+    /// A directory on the engine's host, bind mounted.
+    Directory(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct VolumeMount {
+    pub source: MountSource,
+    pub target: String,
+    pub read_only: bool,
+}
+
+/// A file copied into the container before it starts.
+#[derive(Clone)]
+pub struct ContainerFile {
+    pub path: String,
+    pub content: Vec<u8>,
+    pub mode: u32,
+}
+
+/// What the container engine reports about itself.
+#[derive(Clone, Debug, Default)]
+pub struct EngineInfo {
+    pub version: Option<String>,
+    pub api_version: Option<String>,
+    /// The API version vsnap's client speaks.
+    pub client_api_version: String,
+    pub os: Option<String>,
+    pub arch: Option<String>,
+    pub volume_drivers: Vec<String>,
+    pub podman: bool,
+}
+
+/// A one-off container running the vsnap runner.
+pub struct ContainerSpec {
+    pub image: String,
+    pub entrypoint: Option<Vec<String>>,
+    pub cmd: Vec<String>,
+    pub mounts: Vec<VolumeMount>,
+    pub files: Vec<ContainerFile>,
+    pub labels: HashMap<String, String>,
+    /// Data for the container's stdin, which is closed once the sender is dropped.
+    pub stdin: Option<mpsc::Receiver<Vec<u8>>>,
+    /// Receives stdout instead of the `run_container` callback, so that a slow receiver slows
+    /// down the container instead of buffering its output.
+    pub stdout: Option<mpsc::Sender<Vec<u8>>>,
+}
+
+/// The container engine operations vsnap relies on.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    async fn engine_info(&self) -> anyhow::Result<EngineInfo>;
+
+    /// Whether the engine runs on this machine, so that paths here are paths on its host.
+    fn is_local(&self) -> bool;
+
+    async fn list_volumes(&self) -> anyhow::Result<Vec<VolumeInfo>>;
+
+    async fn inspect_volume(&self, volume_name: &str) -> anyhow::Result<Option<VolumeInfo>>;
+
+    async fn create_volume(&self, volume_name: &str, spec: VolumeSpec) -> anyhow::Result<()>;
+
+    async fn remove_volume(&self, volume_name: &str) -> anyhow::Result<()>;
+
+    /// Disk usage per volume, as far as the engine reports it.
+    async fn volume_sizes(&self) -> anyhow::Result<HashMap<String, VolumeSize>>;
+
+    /// Containers, running or not, that mount the volume.
+    async fn find_containers_using_volume(
+        &self,
+        volume_name: &str,
+    ) -> anyhow::Result<Vec<ContainerInfo>>;
+
+    /// Containers, running or not, that carry the label.
+    async fn find_containers_by_label(&self, label: &str) -> anyhow::Result<Vec<ContainerInfo>>;
+
+    /// Containers, running or not, whose name contains `name`.
+    async fn find_containers_by_name(&self, name: &str) -> anyhow::Result<Vec<ContainerInfo>>;
+
+    /// Removes the container, stopping it first if it is running.
+    async fn remove_container(&self, container_name: &str) -> anyhow::Result<()>;
+
+    async fn image_exists(&self, image: &str) -> bool;
+
+    /// Pulls the image, authenticating with the registry if credentials are configured.
+    async fn pull_image(&self, image: &str) -> anyhow::Result<()>;
+
+    /// Loads images from a `docker save` archive.
+    async fn load_image(&self, archive: &Path) -> anyhow::Result<()>;
+
+    /// Builds and tags an image from a tarred build context.
+    async fn build_image(
+        &self,
+        context: Vec<u8>,
+        dockerfile: &str,
+        tag: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Runs the container to completion and removes it, streaming its stdout to `on_stdout`.
+    /// A non-zero exit must surface as `Error::RunnerFailed` carrying the container's stderr.
+    /// When cancelled, the container is stopped and removed before the cancellation is returned.
+    async fn run_container(
+        &self,
+        spec: ContainerSpec,
+        cancellation: &Cancellation,
+        on_stdout: &mut (dyn for<'a> FnMut(&'a [u8]) + Send),
+    ) -> anyhow::Result<()>;
+}
@@ -0,0 +1,577 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use bollard::{
+    Docker,
+    container::{
+        AttachContainerOptions, AttachContainerResults, Config, CreateContainerOptions,
+        ListContainersOptions, LogOutput, RemoveContainerOptions, StartContainerOptions,
+        StopContainerOptions, UploadToContainerOptions, WaitContainerOptions,
+    },
+    image::{BuildImageOptions, CreateImageOptions, ImportImageOptions},
+    secret::{ContainerSummary, HostConfig, HostConfigLogConfig, Mount, MountTypeEnum},
+    volume::{CreateVolumeOptions, ListVolumesOptions},
+};
+use futures::{StreamExt, TryStreamExt};
+use tokio::io::AsyncWriteExt;
+
+use crate::library::{
+    backend::{
+        Backend, ContainerFile, ContainerInfo, ContainerSpec, EngineInfo, MountSource, VolumeInfo,
+        VolumeSize, VolumeSpec,
+    },
+    cancel::Cancellation,
+    connection::{ConnectionOptions, SshTunnel, connect, resolve_endpoint},
+    error::Error,
+    registry::registry_credentials,
+};
+
+fn container_info(container: ContainerSummary) -> Option<ContainerInfo> {
+    let name = container
+        .names
+        .iter()
+        .flatten()
+        .next()
+        .map(|name| name.trim_start_matches('/').to_string())?;
+
+    Some(ContainerInfo {
+        name,
+        image: container.image.unwrap_or_default(),
+        state: container.state,
+        labels: container.labels.unwrap_or_default(),
+    })
+}
+
+/// Seconds the runner gets to clean up after SIGTERM before it is killed.
+const STOP_TIMEOUT: i64 = 10;
+
+/// Tells apart the runner containers one process starts at the same time.
+static CONTAINER_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+pub struct DockerBackend {
+    docker: Docker,
+    /// Podman's compat API lacks some list filters and may not report volume usage.
+    podman: bool,
+    /// Where registry credentials are looked up.
+    docker_config: Option<PathBuf>,
+    local: bool,
+    _tunnel: Option<SshTunnel>,
+}
+
+impl DockerBackend {
+    pub fn new(docker: Docker) -> Self {
+        DockerBackend {
+            docker,
+            podman: false,
+            docker_config: ConnectionOptions::default()
+                .with_env_defaults()
+                .docker_config_dir()
+                .ok(),
+            // A client from elsewhere doesn't tell where it connects to.
+            local: true,
+            _tunnel: None,
+        }
+    }
+
+    pub fn connect(options: &ConnectionOptions) -> anyhow::Result<Self> {
+        let endpoint = resolve_endpoint(options)?;
+        let (docker, tunnel) = connect(&endpoint)?;
+
+        Ok(DockerBackend {
+            docker,
+            podman: endpoint.podman,
+            docker_config: Some(options.docker_config_dir()?),
+            local: endpoint.is_local(),
+            _tunnel: tunnel,
+        })
+    }
+
+    async fn df_volume_sizes(&self) -> anyhow::Result<HashMap<String, VolumeSize>> {
+        let df = self.docker.df().await?;
+
+        Ok(df
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|volume| {
+                let size = volume
+                    .usage_data
+                    .map(|usage_data| VolumeSize::Bytes(usage_data.size))
+                    .unwrap_or(VolumeSize::Unavailable);
+
+                (volume.name, size)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Backend for DockerBackend {
+    async fn engine_info(&self) -> anyhow::Result<EngineInfo> {
+        let version = self.docker.version().await?;
+        let info = self.docker.info().await?;
+
+        Ok(EngineInfo {
+            version: version.version,
+            api_version: version.api_version,
+            client_api_version: self.docker.client_version().to_string(),
+            os: version.os,
+            arch: version.arch,
+            volume_drivers: info
+                .plugins
+                .and_then(|plugins| plugins.volume)
+                .unwrap_or_default(),
+            podman: self.podman,
+        })
+    }
+
+    fn is_local(&self) -> bool {
+        self.local
+    }
+
+    async fn list_volumes(&self) -> anyhow::Result<Vec<VolumeInfo>> {
+        let volumes = self
+            .docker
+            .list_volumes(None::<ListVolumesOptions<String>>)
+            .await?;
+
+        Ok(volumes
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|volume| VolumeInfo {
+                name: volume.name,
+                driver: volume.driver,
+                driver_opts: volume.options,
+                labels: volume.labels,
+            })
+            .collect())
+    }
+
+    async fn inspect_volume(&self, volume_name: &str) -> anyhow::Result<Option<VolumeInfo>> {
+        match self.docker.inspect_volume(volume_name).await {
+            Ok(volume) => Ok(Some(VolumeInfo {
+                name: volume.name,
+                driver: volume.driver,
+                driver_opts: volume.options,
+                labels: volume.labels,
+            })),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn create_volume(&self, volume_name: &str, spec: VolumeSpec) -> anyhow::Result<()> {
+        self.docker
+            .create_volume(CreateVolumeOptions {
+                name: volume_name.to_string(),
+                driver: spec.driver.unwrap_or_default(),
+                driver_opts: spec.driver_opts,
+                labels: spec.labels,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_volume(&self, volume_name: &str) -> anyhow::Result<()> {
+        self.docker.remove_volume(volume_name, None).await?;
+
+        Ok(())
+    }
+
+    async fn volume_sizes(&self) -> anyhow::Result<HashMap<String, VolumeSize>> {
+        let result = self.df_volume_sizes().await;
+
+        if !self.podman {
+            return result;
+        }
+
+        // Podman's df may fail or omit volumes, sizes are reported as unavailable instead.
+        let mut volume_sizes = result.unwrap_or_default();
+
+        for volume in self.list_volumes().await? {
+            volume_sizes
+                .entry(volume.name)
+                .or_insert(VolumeSize::Unavailable);
+        }
+
+        Ok(volume_sizes)
+    }
+
+    async fn find_containers_using_volume(
+        &self,
+        volume_name: &str,
+    ) -> anyhow::Result<Vec<ContainerInfo>> {
+        let filters = match self.podman {
+            true => HashMap::new(),
+            false => HashMap::<&str, Vec<&str>>::from([("volume", vec![volume_name])]),
+        };
+
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions::<&str> {
+                all: true,
+                filters,
+                ..Default::default()
+            }))
+            .await?;
+
+        Ok(containers
+            .into_iter()
+            .filter(|container| {
+                !self.podman
+                    || container.mounts.iter().flatten().any(|mount| {
+                        mount.name.as_deref() == Some(volume_name)
+                            || mount.source.as_deref() == Some(volume_name)
+                    })
+            })
+            .filter_map(container_info)
+            .collect())
+    }
+
+    async fn find_containers_by_label(&self, label: &str) -> anyhow::Result<Vec<ContainerInfo>> {
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions::<&str> {
+                all: true,
+                filters: HashMap::from([("label", vec![label])]),
+                ..Default::default()
+            }))
+            .await?;
+
+        Ok(containers.into_iter().filter_map(container_info).collect())
+    }
+
+    async fn find_containers_by_name(&self, name: &str) -> anyhow::Result<Vec<ContainerInfo>> {
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions::<&str> {
+                all: true,
+                filters: HashMap::from([("name", vec![name])]),
+                ..Default::default()
+            }))
+            .await?;
+
+        Ok(containers
+            .into_iter()
+            // The engine matches the filter as a pattern.
+            .filter(|container| {
+                container
+                    .names
+                    .iter()
+                    .flatten()
+                    .any(|container_name| container_name.contains(name))
+            })
+            .filter_map(container_info)
+            .collect())
+    }
+
+    async fn remove_container(&self, container_name: &str) -> anyhow::Result<()> {
+        self.docker
+            .remove_container(
+                container_name,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn image_exists(&self, image: &str) -> bool {
+        self.docker.inspect_image(image).await.ok().is_some()
+    }
+
+    async fn pull_image(&self, image: &str) -> anyhow::Result<()> {
+        let credentials = match &self.docker_config {
+            Some(docker_config) => registry_credentials(docker_config, image)?,
+            None => None,
+        };
+
+        self.docker
+            .create_image(
+                Some(CreateImageOptions {
+                    from_image: image,
+                    ..Default::default()
+                }),
+                None,
+                credentials,
+            )
+            .try_for_each(async |_| Ok(()))
+            .await
+            .map_err(|e| anyhow!("Failed to pull docker image {}: {}", image, e))?;
+
+        Ok(())
+    }
+
+    async fn load_image(&self, archive: &Path) -> anyhow::Result<()> {
+        let content = tokio::fs::read(archive)
+            .await
+            .map_err(|e| anyhow!("Failed to read image archive {}: {}", archive.display(), e))?;
+
+        self.docker
+            .import_image(ImportImageOptions { quiet: true }, content.into(), None)
+            .try_for_each(async |info| match info.error {
+                Some(error) => Err(bollard::errors::Error::DockerStreamError { error }),
+                None => Ok(()),
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to load image archive {}: {}", archive.display(), e))?;
+
+        Ok(())
+    }
+
+    async fn build_image(
+        &self,
+        context: Vec<u8>,
+        dockerfile: &str,
+        tag: &str,
+    ) -> anyhow::Result<()> {
+        self.docker
+            .build_image(
+                BuildImageOptions {
+                    dockerfile,
+                    t: tag,
+                    rm: true,
+                    ..Default::default()
+                },
+                None,
+                Some(context.into()),
+            )
+            .try_for_each(async |info| match info.error {
+                Some(error) => Err(bollard::errors::Error::DockerStreamError { error }),
+                None => Ok(()),
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to build image {}: {}", tag, e))?;
+
+        Ok(())
+    }
+
+    async fn run_container(
+        &self,
+        spec: ContainerSpec,
+        cancellation: &Cancellation,
+        on_stdout: &mut (dyn for<'a> FnMut(&'a [u8]) + Send),
+    ) -> anyhow::Result<()> {
+        let ContainerSpec {
+            image,
+            entrypoint,
+            cmd,
+            mounts,
+            files,
+            labels,
+            stdin,
+            stdout,
+        } = spec;
+        let docker = &self.docker;
+        let container_name = format!(
+            "vsnap-runner-{}-{}-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+            std::process::id(),
+            CONTAINER_SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let options = Some(CreateContainerOptions {
+            name: container_name.to_string(),
+            platform: None,
+        });
+
+        let host_config = HostConfig {
+            mounts: Some(
+                mounts
+                    .into_iter()
+                    .map(|mount| {
+                        let (source, typ) = match mount.source {
+                            MountSource::Volume(volume) => (volume, MountTypeEnum::VOLUME),
+                            MountSource::Directory(path) => {
+                                (path.display().to_string(), MountTypeEnum::BIND)
+                            }
+                        };
+
+                        Mount {
+                            source: Some(source),
+                            target: Some(mount.target),
+                            typ: Some(typ),
+                            read_only: mount.read_only.then_some(true),
+                            ..Default::default()
+                        }
+                    })
+                    .collect(),
+            ),
+            // Archives stream through stdin and stdout, which a log driver would keep a copy of.
+            log_config: Some(HostConfigLogConfig {
+                typ: Some("none".to_string()),
+                config: None,
+            }),
+            ..Default::default()
+        };
+
+        let has_stdin = stdin.is_some();
+
+        let config = Config {
+            image: Some(image),
+            entrypoint,
+            cmd: Some(cmd),
+            labels: Some(labels),
+            host_config: Some(host_config),
+            attach_stdin: has_stdin.then_some(true),
+            open_stdin: has_stdin.then_some(true),
+            stdin_once: has_stdin.then_some(true),
+            ..Default::default()
+        };
+
+        let result = async {
+            docker.create_container(options, config).await?;
+
+            if !files.is_empty() {
+                docker
+                    .upload_to_container(
+                        &container_name,
+                        Some(UploadToContainerOptions {
+                            path: "/",
+                            ..Default::default()
+                        }),
+                        files_archive(&files)?.into(),
+                    )
+                    .await?;
+            }
+
+            // Attached before the start, so that no output is missed. Without a log driver
+            // there are no logs to replay.
+            let AttachContainerResults {
+                mut output,
+                mut input,
+            } = docker
+                .attach_container(
+                    &container_name,
+                    Some(AttachContainerOptions::<String> {
+                        stdin: Some(has_stdin),
+                        stdout: Some(true),
+                        stderr: Some(true),
+                        stream: Some(true),
+                        logs: Some(false),
+                        detach_keys: None,
+                    }),
+                )
+                .await?;
+
+            docker
+                .start_container(&container_name, None::<StartContainerOptions<String>>)
+                .await?;
+
+            let mut stderr = vec![];
+
+            let write_stdin = async {
+                let Some(mut stdin) = stdin else {
+                    return anyhow::Ok(());
+                };
+
+                while let Some(chunk) = stdin.recv().await {
+                    input.write_all(&chunk).await?;
+                }
+
+                // Closing our end closes the container's stdin.
+                input.shutdown().await?;
+
+                anyhow::Ok(())
+            };
+
+            let read_output = async {
+                while let Some(log) = output.next().await {
+                    match log? {
+                        LogOutput::StdOut { message } => match &stdout {
+                            Some(stdout) => stdout
+                                .send(message.to_vec())
+                                .await
+                                .map_err(|_| anyhow!("Runner output was no longer read"))?,
+                            None => on_stdout(&message),
+                        },
+                        LogOutput::StdErr { message } => stderr.extend_from_slice(&message),
+                        _ => {}
+                    }
+                }
+
+                anyhow::Ok(())
+            };
+
+            let follow_logs = async {
+                let (written, read) = tokio::join!(write_stdin, read_output);
+
+                written.and(read)
+            };
+
+            tokio::select! {
+                result = follow_logs => result?,
+                reason = cancellation.cancelled() => {
+                    // The runner cleans up after itself on SIGTERM.
+                    docker
+                        .stop_container(
+                            &container_name,
+                            Some(StopContainerOptions { t: STOP_TIMEOUT }),
+                        )
+                        .await
+                        .ok();
+
+                    return Err(Error::from(reason).into());
+                }
+            }
+
+            match docker
+                .wait_container(&container_name, None::<WaitContainerOptions<String>>)
+                .try_collect::<Vec<_>>()
+                .await
+            {
+                Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => {
+                    Err(Error::RunnerFailed {
+                        code,
+                        message: String::from_utf8_lossy(&stderr).trim().to_string(),
+                    }
+                    .into())
+                }
+                Err(e) => Err(e.into()),
+                Ok(_) => Ok(()),
+            }
+        }
+        .await;
+
+        docker
+            .remove_container(
+                &container_name,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+            .ok();
+
+        result
+    }
+}
+
+fn files_archive(files: &[ContainerFile]) -> anyhow::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(vec![]);
+
+    for file in files {
+        let mut header = tar::Header::new_gnu();
+
+        header.set_size(file.content.len() as u64);
+        header.set_mode(file.mode);
+        header.set_cksum();
+
+        builder.append_data(&mut header, &file.path, file.content.as_slice())?;
+    }
+
+    Ok(builder.into_inner()?)
+}
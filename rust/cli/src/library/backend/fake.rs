@@ -0,0 +1,352 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use crate::library::{
+    backend::{
+        Backend, ContainerInfo, ContainerSpec, EngineInfo, MountSource, VolumeInfo, VolumeSize,
+        VolumeSpec,
+    },
+    cancel::Cancellation,
+    channel::{ChannelReader, ChannelWriter},
+    directory::dir_size,
+    error::Error,
+};
+
+/// Executes runner commands in-process, reading what the runner would read from stdin and
+/// writing what it would print to stdout.
+pub type FakeRunner =
+    Arc<dyn Fn(Vec<String>, &mut dyn Read, &mut dyn Write) -> anyhow::Result<()> + Send + Sync>;
+
+#[derive(Default)]
+struct FakeState {
+    volumes: HashMap<String, VolumeSpec>,
+    containers: Vec<(ContainerInfo, Vec<String>)>,
+}
+
+/// A backend without a container engine. Volumes are directories below `root` and runner
+/// containers are executed by `runner`, with mount targets in their arguments replaced by the
+/// volume directories.
+pub struct FakeBackend {
+    root: PathBuf,
+    runner: FakeRunner,
+    state: Mutex<FakeState>,
+    local: bool,
+    arch: Option<String>,
+}
+
+impl FakeBackend {
+    pub fn new(root: &Path, runner: FakeRunner) -> Self {
+        FakeBackend {
+            root: root.to_path_buf(),
+            runner,
+            state: Mutex::new(FakeState::default()),
+            local: true,
+            arch: None,
+        }
+    }
+
+    /// Pretends that the engine runs on another machine.
+    pub fn with_remote_engine(mut self) -> Self {
+        self.local = false;
+        self
+    }
+
+    /// Pretends that the engine runs on `arch`, e.g. to reject runner binaries built for another.
+    pub fn with_arch(mut self, arch: &str) -> Self {
+        self.arch = Some(arch.to_string());
+        self
+    }
+
+    pub fn volume_path(&self, volume_name: &str) -> PathBuf {
+        self.root.join(volume_name)
+    }
+
+    /// Pretends that a container is using the volumes, e.g. to test in-use checks.
+    pub fn add_container(&self, container_name: &str, state: &str, volume_names: &[&str]) {
+        self.add_labeled_container(container_name, state, volume_names, &[]);
+    }
+
+    pub fn add_labeled_container(
+        &self,
+        container_name: &str,
+        state: &str,
+        volume_names: &[&str],
+        labels: &[(&str, &str)],
+    ) {
+        self.add_image_container(container_name, "", state, volume_names, labels);
+    }
+
+    pub fn add_image_container(
+        &self,
+        container_name: &str,
+        image: &str,
+        state: &str,
+        volume_names: &[&str],
+        labels: &[(&str, &str)],
+    ) {
+        self.state.lock().unwrap().containers.push((
+            ContainerInfo {
+                name: container_name.to_string(),
+                image: image.to_string(),
+                state: Some(state.to_string()),
+                labels: labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            },
+            volume_names.iter().map(|name| name.to_string()).collect(),
+        ));
+    }
+}
+
+#[async_trait]
+impl Backend for FakeBackend {
+    async fn engine_info(&self) -> anyhow::Result<EngineInfo> {
+        Ok(EngineInfo {
+            version: Some("fake".to_string()),
+            arch: self.arch.clone(),
+            volume_drivers: vec!["local".to_string()],
+            ..Default::default()
+        })
+    }
+
+    fn is_local(&self) -> bool {
+        self.local
+    }
+
+    async fn list_volumes(&self) -> anyhow::Result<Vec<VolumeInfo>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .volumes
+            .iter()
+            .map(|(name, spec)| volume_info(name, spec))
+            .collect())
+    }
+
+    async fn inspect_volume(&self, volume_name: &str) -> anyhow::Result<Option<VolumeInfo>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .volumes
+            .get(volume_name)
+            .map(|spec| volume_info(volume_name, spec)))
+    }
+
+    async fn create_volume(&self, volume_name: &str, spec: VolumeSpec) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        // Like docker, creating an existing volume is a no-op.
+        if state.volumes.contains_key(volume_name) {
+            return Ok(());
+        }
+
+        fs::create_dir_all(self.volume_path(volume_name))?;
+        state.volumes.insert(volume_name.to_string(), spec);
+
+        Ok(())
+    }
+
+    async fn remove_volume(&self, volume_name: &str) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.volumes.remove(volume_name).is_none() {
+            return Err(anyhow!("No such volume: {}", volume_name));
+        }
+
+        fs::remove_dir_all(self.volume_path(volume_name))?;
+
+        Ok(())
+    }
+
+    async fn volume_sizes(&self) -> anyhow::Result<HashMap<String, VolumeSize>> {
+        let volume_names = self
+            .state
+            .lock()
+            .unwrap()
+            .volumes
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+
+        volume_names
+            .into_iter()
+            .map(|name| {
+                let size = dir_size(&self.volume_path(&name))?;
+
+                Ok((name, VolumeSize::Bytes(size as i64)))
+            })
+            .collect()
+    }
+
+    async fn find_containers_using_volume(
+        &self,
+        volume_name: &str,
+    ) -> anyhow::Result<Vec<ContainerInfo>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .containers
+            .iter()
+            .filter(|(_, volumes)| volumes.iter().any(|name| name == volume_name))
+            .map(|(container, _)| container.clone())
+            .collect())
+    }
+
+    async fn find_containers_by_label(&self, label: &str) -> anyhow::Result<Vec<ContainerInfo>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .containers
+            .iter()
+            .filter(|(container, _)| container.labels.contains_key(label))
+            .map(|(container, _)| container.clone())
+            .collect())
+    }
+
+    async fn find_containers_by_name(&self, name: &str) -> anyhow::Result<Vec<ContainerInfo>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .containers
+            .iter()
+            .filter(|(container, _)| container.name.contains(name))
+            .map(|(container, _)| container.clone())
+            .collect())
+    }
+
+    async fn remove_container(&self, container_name: &str) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let len = state.containers.len();
+
+        state
+            .containers
+            .retain(|(container, _)| container.name != container_name);
+
+        if len == state.containers.len() {
+            return Err(anyhow!("No such container: {}", container_name));
+        }
+
+        Ok(())
+    }
+
+    async fn image_exists(&self, _image: &str) -> bool {
+        true
+    }
+
+    async fn pull_image(&self, _image: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn load_image(&self, _archive: &Path) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn build_image(
+        &self,
+        _context: Vec<u8>,
+        _dockerfile: &str,
+        _tag: &str,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn run_container(
+        &self,
+        spec: ContainerSpec,
+        cancellation: &Cancellation,
+        on_stdout: &mut (dyn for<'a> FnMut(&'a [u8]) + Send),
+    ) -> anyhow::Result<()> {
+        let mut mounts = vec![];
+
+        for mount in &spec.mounts {
+            let path = match &mount.source {
+                MountSource::Volume(volume) => {
+                    if self.inspect_volume(volume).await?.is_none() {
+                        return Err(anyhow!("No such volume: {}", volume));
+                    }
+
+                    self.volume_path(volume)
+                }
+                MountSource::Directory(path) => {
+                    if !path.is_dir() {
+                        return Err(anyhow!("No such directory: {}", path.display()));
+                    }
+
+                    path.clone()
+                }
+            };
+
+            mounts.push((mount.target.clone(), path));
+        }
+
+        let args = spec
+            .cmd
+            .into_iter()
+            .map(|arg| {
+                mounts
+                    .iter()
+                    .find_map(|(target, path)| {
+                        arg.strip_prefix(target.as_str())
+                            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+                            .map(|rest| format!("{}{}", path.display(), rest))
+                    })
+                    .unwrap_or(arg)
+            })
+            .collect::<Vec<String>>();
+
+        let runner = self.runner.clone();
+        let ContainerSpec { stdin, stdout, .. } = spec;
+
+        let run = tokio::task::spawn_blocking(move || {
+            // Empty without a receiver.
+            let mut input = ChannelReader::new(stdin);
+            let mut output = vec![];
+
+            let result = match stdout {
+                Some(sender) => runner(args, &mut input, &mut ChannelWriter::new(sender)),
+                None => runner(args, &mut input, &mut output),
+            };
+
+            (result, output)
+        });
+
+        // The in-process runner can't be stopped, it finishes in the background.
+        let (result, stdout) = tokio::select! {
+            output = run => output?,
+            reason = cancellation.cancelled() => return Err(Error::from(reason).into()),
+        };
+
+        on_stdout(&stdout);
+
+        result.map_err(|e| {
+            Error::RunnerFailed {
+                code: 1,
+                message: format!("{:#}", e),
+            }
+            .into()
+        })
+    }
+}
+
+fn volume_info(volume_name: &str, spec: &VolumeSpec) -> VolumeInfo {
+    VolumeInfo {
+        name: volume_name.to_string(),
+        driver: spec.driver.clone().unwrap_or_else(|| "local".to_string()),
+        driver_opts: spec.driver_opts.clone(),
+        labels: spec.labels.clone(),
+    }
+}
@@ -0,0 +1,87 @@
+use std::future::Future;
+
+use futures::{StreamExt, stream};
+use serde::Serialize;
+
+use crate::library::error::Error;
+
+/// Default number of targets a batch works on at the same time.
+pub static DEFAULT_PARALLELISM: usize = 4;
+
+/// Why one target of a batch failed, in the shape `--output json` reports errors.
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchError {
+    pub kind: &'static str,
+    pub message: String,
+    pub exit_code: u8,
+}
+
+impl From<&Error> for BatchError {
+    fn from(error: &Error) -> Self {
+        BatchError {
+            kind: error.kind(),
+            message: error.to_string(),
+            exit_code: error.exit_code(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchItem<T> {
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<BatchError>,
+}
+
+/// The outcome of one operation run for several targets, in the order of the targets.
+#[derive(Serialize, Clone, Debug)]
+pub struct BatchResult<T> {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub items: Vec<BatchItem<T>>,
+}
+
+/// Runs `run` for every target, at most `parallelism` at a time. A failing target doesn't stop
+/// the others.
+pub async fn run_batch<I, T, F, Fut>(
+    targets: Vec<(String, I)>,
+    parallelism: usize,
+    run: F,
+) -> BatchResult<T>
+where
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let items = stream::iter(targets)
+        .map(|(target, input)| {
+            let future = run(input);
+
+            async move {
+                match future.await {
+                    Ok(result) => BatchItem {
+                        target,
+                        result: Some(result),
+                        error: None,
+                    },
+                    Err(error) => BatchItem {
+                        target,
+                        result: None,
+                        error: Some(BatchError::from(&error)),
+                    },
+                }
+            }
+        })
+        .buffered(parallelism.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let failed = items.iter().filter(|item| item.error.is_some()).count();
+
+    BatchResult {
+        succeeded: items.len() - failed,
+        failed,
+        items,
+    }
+}
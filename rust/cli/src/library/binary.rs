@@ -0,0 +1,113 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+
+use crate::library::constant::RUNNER_BINARY_NAME;
+
+const PT_INTERP: u32 = 3;
+
+/// ELF machines of 64-bit little-endian Linux, named like the engine names its architecture.
+const MACHINES: &[(u16, &str)] = &[(0x3e, "amd64"), (0xb7, "arm64"), (0xf3, "riscv64")];
+
+/// The architecture of a 64-bit little-endian ELF executable without a dynamic loader, i.e. one
+/// that runs in any Linux container of that architecture regardless of its libc.
+pub fn static_elf_arch(content: &[u8]) -> Option<&'static str> {
+    let read = |offset: usize, length: usize| content.get(offset..offset.checked_add(length)?);
+
+    let read_u16 = |offset: usize| {
+        read(offset, 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    };
+
+    let read_u32 = |offset: usize| {
+        read(offset, 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let read_u64 = |offset: usize| {
+        read(offset, 8).and_then(|bytes| {
+            let mut buffer = [0; 8];
+            buffer.copy_from_slice(bytes);
+            usize::try_from(u64::from_le_bytes(buffer)).ok()
+        })
+    };
+
+    // ELF magic, 64-bit class and little-endian data.
+    if !content.starts_with(b"\x7fELF\x02\x01") {
+        return None;
+    }
+
+    let machine = read_u16(0x12)?;
+    let (_, arch) = MACHINES
+        .iter()
+        .find(|(candidate, _)| *candidate as usize == machine)?;
+
+    let program_headers = read_u64(0x20)?;
+    let entry_size = read_u16(0x36)?;
+    let entry_count = read_u16(0x38)?;
+
+    (0..entry_count)
+        .all(|index| {
+            index
+                .checked_mul(entry_size)
+                .and_then(|offset| offset.checked_add(program_headers))
+                .and_then(&read_u32)
+                .is_some_and(|typ| typ != PT_INTERP)
+        })
+        .then_some(*arch)
+}
+
+/// Whether the bytes are a static Linux executable of an architecture vsnap knows.
+pub fn is_static_elf(content: &[u8]) -> bool {
+    static_elf_arch(content).is_some()
+}
+
+/// A static runner binary to inject into stock images, read once and copied into every runner
+/// container.
+#[derive(Clone)]
+pub struct RunnerBinary {
+    pub path: PathBuf,
+    pub arch: &'static str,
+    pub content: Arc<[u8]>,
+}
+
+impl RunnerBinary {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read(path)
+            .map_err(|e| anyhow!("Failed to read runner binary {}: {}", path.display(), e))?;
+
+        let Some(arch) = static_elf_arch(&content) else {
+            return Err(anyhow!(
+                "{} is not a static Linux binary, build it with `cargo build --release --target x86_64-unknown-linux-musl -p vsnap-runner`",
+                path.display()
+            ));
+        };
+
+        Ok(RunnerBinary {
+            path: path.to_path_buf(),
+            arch,
+            content: content.into(),
+        })
+    }
+}
+
+impl fmt::Debug for RunnerBinary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunnerBinary")
+            .field("path", &self.path)
+            .field("arch", &self.arch)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A static runner binary installed next to the vsnap executable, if any.
+pub fn find_bundled_runner_binary() -> Option<RunnerBinary> {
+    let path = std::env::current_exe()
+        .ok()?
+        .parent()?
+        .join(RUNNER_BINARY_NAME);
+
+    RunnerBinary::read(&path).ok()
+}
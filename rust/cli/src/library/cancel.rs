@@ -0,0 +1,74 @@
+use std::{future::pending, sync::Arc};
+
+use tokio::sync::watch;
+
+use crate::library::error::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    Interrupted,
+    TimedOut,
+}
+
+impl From<CancelReason> for Error {
+    fn from(reason: CancelReason) -> Self {
+        match reason {
+            CancelReason::Interrupted => Error::Interrupted,
+            CancelReason::TimedOut => Error::TimedOut,
+        }
+    }
+}
+
+/// Cancels an operation from another task. The running runner container is stopped and the
+/// operation rolls back the volumes it created before returning.
+#[derive(Clone, Debug)]
+pub struct Cancellation {
+    sender: Arc<watch::Sender<Option<CancelReason>>>,
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Cancellation {
+            sender: Arc::new(watch::channel(None).0),
+        }
+    }
+}
+
+impl Cancellation {
+    /// Cancels with `reason` unless already cancelled.
+    pub fn cancel(&self, reason: CancelReason) {
+        self.sender.send_if_modified(|current| match current {
+            Some(_) => false,
+            None => {
+                *current = Some(reason);
+                true
+            }
+        });
+    }
+
+    pub fn reason(&self) -> Option<CancelReason> {
+        *self.sender.borrow()
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        match self.reason() {
+            Some(reason) => Err(reason.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Resolves once cancelled.
+    pub async fn cancelled(&self) -> CancelReason {
+        let mut receiver = self.sender.subscribe();
+
+        let reason = receiver
+            .wait_for(|reason| reason.is_some())
+            .await
+            .map(|reason| *reason);
+
+        match reason {
+            Ok(Some(reason)) => reason,
+            _ => pending().await,
+        }
+    }
+}
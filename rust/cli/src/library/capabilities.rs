@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use crate::library::{constant::VERSION, metadata::METADATA_VERSION};
+
+/// Version of the command line and progress protocol between the CLI and the runner. Bump it
+/// whenever a runner command changes incompatibly.
+pub static PROTOCOL_VERSION: u32 = 1;
+
+/// The runner accepts `snapshot --source-name`.
+pub static FEATURE_SOURCE_NAME: &str = "source_name";
+
+/// Snapshots carry a checksum that `inspect --verify` checks.
+pub static FEATURE_CHECKSUM: &str = "checksum";
+
+/// The runner reports free disk space with `free-space`.
+pub static FEATURE_FREE_SPACE: &str = "free_space";
+
+/// The runner checks free space before writing and accepts `--force` to only warn.
+pub static FEATURE_SPACE_CHECK: &str = "space_check";
+
+/// The runner's `restore` takes several restore paths.
+pub static FEATURE_MULTI_RESTORE: &str = "multi_restore";
+
+/// What a runner reports from `vsnap-runner capabilities --json`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub protocol_version: u32,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub compression: Vec<String>,
+    #[serde(default)]
+    pub metadata_versions: Vec<u32>,
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+/// A runner command the CLI is about to start.
+#[derive(Clone, Copy, Debug)]
+pub enum RunnerCommand {
+    Snapshot { compress: bool },
+    Restore,
+    Clone,
+    Copy,
+    Inspect { verify: bool },
+    Export,
+    Import,
+    FreeSpace,
+}
+
+impl RunnerCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RunnerCommand::Snapshot { .. } => "snapshot",
+            RunnerCommand::Restore => "restore",
+            RunnerCommand::Clone => "clone",
+            RunnerCommand::Copy => "copy",
+            RunnerCommand::Inspect { .. } => "inspect",
+            RunnerCommand::Export => "export",
+            RunnerCommand::Import => "import",
+            RunnerCommand::FreeSpace => "free-space",
+        }
+    }
+}
+
+impl Capabilities {
+    /// Runners from before the handshake only snapshot and restore.
+    pub fn legacy() -> Self {
+        Capabilities {
+            protocol_version: 0,
+            version: None,
+            commands: vec!["snapshot".to_string(), "restore".to_string()],
+            compression: vec!["zstd".to_string()],
+            metadata_versions: vec![1],
+            features: vec![],
+        }
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Explains why the runner cannot run the command, if it cannot.
+    pub fn check(&self, command: RunnerCommand) -> Result<(), String> {
+        let runner = match &self.version {
+            Some(version) => format!("Runner {}", version),
+            None => "Runner".to_string(),
+        };
+
+        if self.protocol_version > PROTOCOL_VERSION {
+            return Err(format!(
+                "{} speaks protocol {}, but vsnap {} only supports up to {}, upgrade vsnap or use an older runner image",
+                runner,
+                self.protocol_version,
+                VERSION.as_str(),
+                PROTOCOL_VERSION
+            ));
+        }
+
+        if !self.commands.iter().any(|c| c == command.name()) {
+            return Err(format!(
+                "{} does not support {}, use the runner image matching vsnap {}",
+                runner,
+                command.name(),
+                VERSION.as_str()
+            ));
+        }
+
+        match command {
+            RunnerCommand::Snapshot { compress: true }
+                if !self.compression.iter().any(|c| c == "zstd") =>
+            {
+                Err(format!("{} does not support zstd compression", runner))
+            }
+            RunnerCommand::Inspect { verify: true } if !self.has_feature(FEATURE_CHECKSUM) => {
+                Err(format!("{} cannot verify checksums", runner))
+            }
+            _ => Ok(()),
+        }?;
+
+        let reads_metadata = matches!(
+            command,
+            RunnerCommand::Snapshot { .. } | RunnerCommand::Restore | RunnerCommand::Inspect { .. }
+        );
+
+        if reads_metadata
+            && !self
+                .metadata_versions
+                .iter()
+                .any(|version| *version <= METADATA_VERSION)
+        {
+            return Err(format!(
+                "{} only writes snapshot metadata newer than version {}, upgrade vsnap",
+                runner, METADATA_VERSION
+            ));
+        }
+
+        Ok(())
+    }
+}
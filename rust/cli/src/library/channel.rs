@@ -0,0 +1,125 @@
+use std::{
+    io::{self, Read, Write},
+    sync::mpsc,
+};
+
+use tokio::sync::mpsc as tokio_mpsc;
+
+/// The receiving end of a channel that chunks of a byte stream are sent through.
+pub trait ChunkReceiver {
+    type Chunk: AsRef<[u8]> + Default;
+
+    /// Blocks until the next chunk arrives, `None` once the sender hung up.
+    fn receive(&mut self) -> Option<Self::Chunk>;
+}
+
+impl<T: AsRef<[u8]> + Default> ChunkReceiver for mpsc::Receiver<T> {
+    type Chunk = T;
+
+    fn receive(&mut self) -> Option<T> {
+        self.recv().ok()
+    }
+}
+
+/// Must not be used from within an async context.
+impl<T: AsRef<[u8]> + Default> ChunkReceiver for tokio_mpsc::Receiver<T> {
+    type Chunk = T;
+
+    fn receive(&mut self) -> Option<T> {
+        self.blocking_recv()
+    }
+}
+
+/// No channel, an empty stream.
+impl<R: ChunkReceiver> ChunkReceiver for Option<R> {
+    type Chunk = R::Chunk;
+
+    fn receive(&mut self) -> Option<R::Chunk> {
+        self.as_mut()?.receive()
+    }
+}
+
+/// The sending end of a channel that chunks of a byte stream are sent through.
+pub trait ChunkSender {
+    /// Blocks until there is room for the chunk, fails once the receiver hung up.
+    fn send(&self, chunk: &[u8]) -> io::Result<()>;
+}
+
+impl<T: for<'a> From<&'a [u8]>> ChunkSender for mpsc::SyncSender<T> {
+    fn send(&self, chunk: &[u8]) -> io::Result<()> {
+        mpsc::SyncSender::send(self, T::from(chunk)).map_err(|_| hung_up())
+    }
+}
+
+/// Must not be used from within an async context.
+impl<T: for<'a> From<&'a [u8]>> ChunkSender for tokio_mpsc::Sender<T> {
+    fn send(&self, chunk: &[u8]) -> io::Result<()> {
+        self.blocking_send(T::from(chunk)).map_err(|_| hung_up())
+    }
+}
+
+/// Reads the chunks another thread sends, until it hangs up.
+pub struct ChannelReader<R: ChunkReceiver> {
+    receiver: R,
+    chunk: R::Chunk,
+    position: usize,
+}
+
+impl<R: ChunkReceiver> ChannelReader<R> {
+    pub fn new(receiver: R) -> Self {
+        ChannelReader {
+            receiver,
+            chunk: R::Chunk::default(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: ChunkReceiver> Read for ChannelReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.as_ref().len() {
+            match self.receiver.receive() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let chunk = &self.chunk.as_ref()[self.position..];
+        let bytes_read = buf.len().min(chunk.len());
+
+        buf[..bytes_read].copy_from_slice(&chunk[..bytes_read]);
+        self.position += bytes_read;
+
+        Ok(bytes_read)
+    }
+}
+
+/// Sends everything written to it as chunks to a `ChannelReader`.
+pub struct ChannelWriter<S: ChunkSender> {
+    sender: S,
+}
+
+impl<S: ChunkSender> ChannelWriter<S> {
+    pub fn new(sender: S) -> Self {
+        ChannelWriter { sender }
+    }
+}
+
+impl<S: ChunkSender> Write for ChannelWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender.send(buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn hung_up() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "The reader hung up")
+}
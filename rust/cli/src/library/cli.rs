@@ -1,17 +1,36 @@
-use anyhow::anyhow;
-use bollard::Docker;
-use clap::{ArgGroup, Parser, Subcommand};
+use std::{
+    future::{Future, pending},
+    io::IsTerminal,
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use indicatif::{HumanBytes, MultiProgress};
 use inquire::Confirm;
 
 use crate::library::{
+    batch::{BatchResult, DEFAULT_PARALLELISM, run_batch},
+    cancel::{CancelReason, Cancellation},
+    client::{
+        CloneOptions, CreateOptions, DropOptions, ExistingVolume, InspectOptions, ListOptions,
+        PullOptions, PushOptions, RestoreCopiesOptions, RestoreOptions, VsnapClient,
+    },
+    config::Config,
+    connection::ConnectionOptions,
     constant::VERSION,
-    docker::{
-        create_volume, drop_volume, find_snapshot_volume_name_by_snapshot_name,
-        find_snapshot_volume_names, get_snapshot_volume_name, get_volume_sizes_for_volume_names,
-        restore_snapshot, snapshot, verify_snapshot_does_not_exist, verify_volume_exists,
-        verify_volume_not_in_use, volume_exists,
+    doctor::{CheckStatus, DoctorReport, engine_unreachable},
+    error::Error,
+    gc::GcResult,
+    listing::{
+        ListFilter, ListFormat, SortKey, format_age, parse_duration, parse_since, render_entries,
     },
-    table::print_snapshot_table,
+    output::{CommandResult, OutputFormat, print_error, print_json_result},
+    pattern::expand_template,
+    progress::{multi_terminal_progress, terminal_progress},
+    table::print_snapshot_details,
+    volume::{VolumeOverrides, parse_key_value},
 };
 
 #[derive(Parser, Debug)]
@@ -26,23 +45,155 @@ use crate::library::{
     }
 )]
 pub struct Cli {
+    /// Output format. With json, every command prints a structured result or error.
+    #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(Args, Debug)]
+pub struct ClientArgs {
+    #[command(flatten)]
+    pub connection: ConnectionOptions,
+
+    /// Runner image to use instead of fominv/vsnap:<version> or the configured image.
+    #[arg(long, global = true)]
+    pub image: Option<String>,
+
+    /// Static vsnap-runner binary to inject into a stock image (busybox unless --image is set),
+    /// so no vsnap image is needed.
+    #[arg(long, global = true)]
+    pub runner_binary: Option<PathBuf>,
+
+    /// Give up after this long, e.g. 90s or 30m, stopping the runner and removing partially
+    /// created volumes.
+    #[arg(long, global = true, value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Fail instead of waiting when another vsnap operation holds a volume or snapshot.
+    #[arg(long, global = true, default_value_t = false)]
+    pub no_wait: bool,
+
+    /// Keep snapshots under this volume name prefix instead of vsnap- or the configured one, so
+    /// that projects sharing a host don't see each other's snapshots.
+    #[arg(long, global = true)]
+    pub snapshot_prefix: Option<String>,
+
+    /// Create snapshot volumes with this driver instead of the engine's default.
+    #[arg(long, global = true)]
+    pub snapshot_driver: Option<String>,
+
+    /// Driver option for new snapshot volumes, e.g. --snapshot-driver-opt device=/mnt/big.
+    /// Repeatable.
+    #[arg(long, global = true, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub snapshot_driver_opt: Vec<(String, String)>,
+
+    /// Keep snapshots as files in this directory instead of in volumes, e.g.
+    /// ~/.local/share/vsnap. Needs an engine on this machine.
+    #[arg(long, global = true)]
+    pub snapshot_dir: Option<PathBuf>,
+
+    #[arg(skip)]
+    pub cancellation: Cancellation,
+}
+
+impl ClientArgs {
+    fn client(&self) -> Result<VsnapClient, Error> {
+        let client = VsnapClient::connect(&self.connection.clone().with_env_defaults())?
+            .with_progress(terminal_progress())
+            .with_cancellation(self.cancellation.clone())
+            .with_lock_wait(!self.no_wait)
+            .with_snapshot_driver(
+                self.snapshot_driver.clone(),
+                self.snapshot_driver_opt.iter().cloned().collect(),
+            );
+
+        let client = match &self.snapshot_prefix {
+            Some(prefix) => client.with_snapshot_prefix(prefix.clone()),
+            None => client,
+        };
+
+        let client = match &self.snapshot_dir {
+            Some(snapshot_dir) => client.with_snapshot_dir(snapshot_dir.clone()),
+            None => client,
+        };
+
+        let client = match &self.image {
+            Some(image) => client.with_image(image.clone()),
+            None => client,
+        };
+
+        Ok(match &self.runner_binary {
+            Some(runner_binary) => client.with_runner_binary(runner_binary.clone()),
+            None => client,
+        })
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(group(
     ArgGroup::new("target")
         .required(true)
-        .args(["all", "snapshot_name"])
+        .args(["all", "snapshot_names"])
 ))]
 pub struct Drop {
     /// Drop all snapshots.
     #[arg(long, short, default_value_t = false)]
     all: bool,
 
-    /// Name of the snapshot to delete.
-    snapshot_name: Option<String>,
+    /// Also drop pinned snapshots.
+    #[arg(long, short, default_value_t = false)]
+    force: bool,
+
+    /// How many snapshots to drop at the same time.
+    #[arg(long, short, default_value_t = DEFAULT_PARALLELISM)]
+    jobs: usize,
+
+    /// Names of the snapshots to delete.
+    snapshot_names: Vec<String>,
+}
+
+/// Overrides for the driver, driver options and labels a restored volume inherits from the
+/// snapshotted volume.
+#[derive(Args, Debug)]
+pub struct VolumeArgs {
+    /// Create restored volumes with this driver.
+    #[arg(long)]
+    driver: Option<String>,
+
+    /// Set a driver option, e.g. --driver-opt type=tmpfs. Repeatable.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    driver_opt: Vec<(String, String)>,
+
+    /// Leave out an inherited driver option, by name or glob pattern. Repeatable.
+    #[arg(long, value_name = "KEY")]
+    strip_driver_opt: Vec<String>,
+
+    /// Set a label. Repeatable.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    label: Vec<(String, String)>,
+
+    /// Leave out an inherited label, by name or glob pattern, e.g. 'com.docker.compose.*'.
+    /// Repeatable.
+    #[arg(long, value_name = "KEY")]
+    strip_label: Vec<String>,
+}
+
+impl From<VolumeArgs> for VolumeOverrides {
+    fn from(args: VolumeArgs) -> Self {
+        VolumeOverrides {
+            driver: args.driver,
+            driver_opts: args.driver_opt.into_iter().collect(),
+            strip_driver_opts: args.strip_driver_opt,
+            labels: args.label.into_iter().collect(),
+            strip_labels: args.strip_label,
+        }
+    }
 }
 
 /// Subcommands for the vs tool.
@@ -54,11 +205,22 @@ pub enum Commands {
         #[arg(long, short, default_value_t = false)]
         compress: bool,
 
-        /// Name of the volume to snapshot.
-        source_volume_name: String,
+        /// Replace an existing snapshot with the same name.
+        #[arg(long, short, default_value_t = false)]
+        force: bool,
 
-        /// Name of the snapshot.
-        snapshot_name: String,
+        /// Only warn when the disk looks too small for the snapshot.
+        #[arg(long, default_value_t = false)]
+        ignore_space: bool,
+
+        /// How many snapshots to create at the same time.
+        #[arg(long, short, default_value_t = DEFAULT_PARALLELISM)]
+        jobs: usize,
+
+        /// The volume to snapshot and the name of the snapshot, or several volume:snapshot
+        /// pairs.
+        #[arg(required = true, value_name = "VOLUME SNAPSHOT | VOLUME:SNAPSHOT")]
+        targets: Vec<String>,
     },
     /// List all snapshots.
     List {
@@ -66,6 +228,27 @@ pub enum Commands {
         /// Might be slow for many / large volumes.
         #[arg(long, short, default_value_t = false)]
         size: bool,
+
+        /// Output format: table, json, csv, names or a template like "{{.Name}}\t{{.Size}}".
+        /// Template fields: Name, Volume, Created, Datetime, Age, Source, Size, Pinned.
+        #[arg(long, short, default_value = "table")]
+        format: ListFormat,
+
+        /// Only list snapshots of this source volume.
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Only list snapshots newer than a duration (e.g. 2h, 7d), a date or a datetime.
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only list snapshots whose name matches a glob pattern.
+        #[arg(long = "match", short = 'm')]
+        pattern: Option<String>,
+
+        /// Sort order.
+        #[arg(long, value_enum, default_value_t = SortKey::Date)]
+        sort: SortKey,
     },
     /// Restore a volume from a snapshot.
     Restore {
@@ -73,147 +256,893 @@ pub enum Commands {
         #[arg(long, short, default_value_t = false)]
         drop: bool,
 
-        /// Name of the snapshot volume to restore.
-        snapshot_name: String,
+        /// Only warn when the disk looks too small for the snapshot.
+        #[arg(long, default_value_t = false)]
+        ignore_space: bool,
 
-        /// Name of the volume to restore to.
-        restore_volume_name: String,
+        /// Replace existing volumes without asking for confirmation.
+        #[arg(long, short, default_value_t = false)]
+        yes: bool,
+
+        /// How many volumes to restore at the same time.
+        #[arg(long, short, default_value_t = DEFAULT_PARALLELISM)]
+        jobs: usize,
+
+        /// Restore into this many volumes named after --name-template, reading the snapshot
+        /// only once.
+        #[arg(long, requires = "name_template")]
+        count: Option<usize>,
+
+        /// Names of the --count volumes, with {i} replaced by the index, e.g. test-db-{i}.
+        #[arg(long, requires = "count")]
+        name_template: Option<String>,
+
+        /// Index of the first of the --count volumes.
+        #[arg(long, default_value_t = 1)]
+        start: usize,
+
+        #[command(flatten)]
+        volume: VolumeArgs,
+
+        /// The snapshot to restore and the volume to restore to, or several snapshot:volume
+        /// pairs. Only the snapshot with --count.
+        #[arg(required = true, value_name = "SNAPSHOT VOLUME | SNAPSHOT:VOLUME")]
+        targets: Vec<String>,
+    },
+
+    /// Copy a volume into a new volume directly, without taking a snapshot.
+    Clone {
+        /// Only warn when the disk looks too small for the copy.
+        #[arg(long, default_value_t = false)]
+        ignore_space: bool,
+
+        /// Name of the volume to copy.
+        source_volume_name: String,
+
+        /// Name of the new volume.
+        volume_name: String,
     },
 
     /// Drop a snapshot.
     Drop(Drop),
+
+    /// Rename a snapshot, keeping its creation time.
+    Rename {
+        /// Name of the snapshot to rename.
+        snapshot_name: String,
+
+        /// New name of the snapshot.
+        new_snapshot_name: String,
+    },
+
+    /// Copy a snapshot, keeping its creation time.
+    Copy {
+        /// Name of the snapshot to copy.
+        snapshot_name: String,
+
+        /// Name of the copy.
+        new_snapshot_name: String,
+    },
+
+    /// Upload a snapshot to a remote, e.g. s3://bucket/path or oci://registry/repository:tag.
+    /// Credentials come from the AWS settings for S3 and from the docker config for registries.
+    /// Other schemes are handed to a vsnap-backend-<scheme> plugin on PATH.
+    Push {
+        /// Name of the snapshot to upload.
+        snapshot_name: String,
+
+        /// Where to upload it. A remote ending in / stores the snapshot under its own name.
+        remote: String,
+    },
+
+    /// Download a snapshot from a remote.
+    Pull {
+        /// Where to download it from, e.g. s3://bucket/path or oci://registry/repository:tag.
+        remote: String,
+
+        /// Name of the new snapshot.
+        snapshot_name: String,
+    },
+
+    /// Manage snapshots on a remote.
+    #[command(subcommand)]
+    Remote(RemoteCommands),
+
+    /// Show detailed information about a snapshot.
+    Inspect {
+        /// Verify the archive checksum. Reads the whole archive.
+        #[arg(long, default_value_t = false)]
+        verify: bool,
+
+        /// Name of the snapshot to inspect.
+        snapshot_name: String,
+    },
+
+    /// Pin a snapshot so it cannot be dropped without --force.
+    Pin {
+        /// Name of the snapshot to pin.
+        snapshot_name: String,
+    },
+
+    /// Unpin a previously pinned snapshot.
+    Unpin {
+        /// Name of the snapshot to unpin.
+        snapshot_name: String,
+    },
+
+    /// Protect volumes from being overwritten by restore.
+    Protect {
+        /// Volume name or glob pattern (e.g. "prod-*") to protect.
+        /// Lists protected volumes when omitted.
+        pattern: Option<String>,
+    },
+
+    /// Remove a volume name or pattern from the protected list.
+    Unprotect {
+        /// Volume name or glob pattern to unprotect.
+        pattern: String,
+    },
+
+    /// Remove runner containers, broken snapshots and half-restored volumes left behind by
+    /// interrupted operations.
+    Gc {
+        /// Remove without asking for confirmation.
+        #[arg(long, short, default_value_t = false)]
+        yes: bool,
+
+        /// Only list what would be removed.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// Drop the volumes a restore with --count created.
+    Cleanup {
+        /// The --name-template of the restore, e.g. test-db-{i}.
+        #[arg(long)]
+        name_template: String,
+
+        /// Drop without asking for confirmation.
+        #[arg(long, short, default_value_t = false)]
+        yes: bool,
+    },
+
+    /// Check the container engine, runner image, disk space and snapshots, e.g. for bug reports.
+    Doctor,
+
+    /// Manage the runner image.
+    #[command(subcommand)]
+    Image(ImageCommands),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RemoteCommands {
+    /// List the snapshots at or below a remote, e.g. s3://bucket/team/ or the tags of
+    /// oci://registry/repository.
+    List { remote: String },
+
+    /// Delete a snapshot from a remote.
+    Delete { remote: String },
 }
 
-pub async fn run() -> anyhow::Result<()> {
+#[derive(Subcommand, Debug)]
+pub enum ImageCommands {
+    /// Pull the runner image, using registry credentials from the docker config.
+    Pull,
+
+    /// Load the runner image from a `docker save` archive.
+    Load {
+        /// Path to the archive.
+        archive: PathBuf,
+    },
+
+    /// Build the runner image from a vsnap source checkout.
+    ///
+    /// The vsnap binary doesn't carry the sources of the runner, so this needs a clone of the
+    /// vsnap repository at the version of this vsnap.
+    Build {
+        /// Root of the checkout, containing the dockerfile.
+        #[arg(long, default_value = ".")]
+        source: PathBuf,
+    },
+}
+
+pub async fn run() -> ExitCode {
     let args = Cli::parse();
+    let output = args.output;
+
+    tokio::spawn(cancel_on_signal(
+        args.client.cancellation.clone(),
+        args.client.timeout,
+    ));
+
+    match execute(args.command, output, &args.client).await {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            print_error(&error, output);
+
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
+
+/// Cancels on Ctrl-C, SIGTERM or timeout so the operation can clean up. A second signal exits
+/// right away.
+async fn cancel_on_signal(cancellation: Cancellation, timeout: Option<Duration>) {
+    let timeout = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => pending().await,
+        }
+    };
+
+    tokio::select! {
+        _ = shutdown_signal() => cancellation.cancel(CancelReason::Interrupted),
+        _ = timeout => cancellation.cancel(CancelReason::TimedOut),
+    }
+
+    shutdown_signal().await;
+    std::process::exit(130);
+}
 
-    match args.command {
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            },
+            Err(_) => {
+                tokio::signal::ctrl_c().await.ok();
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+}
+
+async fn execute(
+    command: Commands,
+    output: OutputFormat,
+    client_args: &ClientArgs,
+) -> Result<ExitCode, Error> {
+    let result = match command {
         Commands::Create {
             compress,
-            source_volume_name,
-            snapshot_name,
-        } => create(source_volume_name, snapshot_name, compress).await?,
-        Commands::List { size } => {
-            list(size).await?;
+            force,
+            ignore_space,
+            jobs,
+            targets,
+        } => {
+            let mut targets = parse_targets(targets, "VOLUME:SNAPSHOT")?;
+            let client = client_args.client()?;
+
+            match targets.len() {
+                1 => {
+                    let (source_volume, snapshot) = targets.remove(0);
+
+                    CommandResult::Create(
+                        client
+                            .create(CreateOptions {
+                                source_volume,
+                                snapshot,
+                                compress,
+                                force,
+                                ignore_space,
+                            })
+                            .await?,
+                    )
+                }
+                _ => CommandResult::CreateBatch(
+                    run_with_progress(
+                        &client,
+                        &client_args.cancellation,
+                        labelled(targets),
+                        jobs,
+                        |client, (source_volume, snapshot)| async move {
+                            client
+                                .create(CreateOptions {
+                                    source_volume,
+                                    snapshot,
+                                    compress,
+                                    force,
+                                    ignore_space,
+                                })
+                                .await
+                        },
+                    )
+                    .await?,
+                ),
+            }
+        }
+        Commands::List {
+            size,
+            format,
+            source,
+            since,
+            pattern,
+            sort,
+        } => {
+            let filter = ListFilter {
+                source,
+                since: since
+                    .as_deref()
+                    .map(parse_since)
+                    .transpose()
+                    .map_err(|e| Error::InvalidArgument(e.to_string()))?,
+                pattern,
+            };
+
+            let snapshots = client_args
+                .client()?
+                .list(ListOptions {
+                    include_size: size,
+                    filter,
+                    sort,
+                })
+                .await?;
+
+            if output == OutputFormat::Text {
+                let include_size = size || matches!(sort, SortKey::Size);
+
+                let rendered = render_entries(&snapshots, &format, include_size)?;
+
+                if !rendered.is_empty() {
+                    println!("{}", rendered);
+                }
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            CommandResult::List { snapshots }
         }
         Commands::Restore {
             drop,
+            ignore_space,
+            yes,
+            count: Some(count),
+            name_template: Some(name_template),
+            start,
+            volume,
+            targets,
+            ..
+        } => {
+            let [snapshot] = <[String; 1]>::try_from(targets).map_err(|_| {
+                Error::InvalidArgument("--count restores a single snapshot".to_string())
+            })?;
+            let client = client_args.client()?;
+            let volume_names = expand_template(&name_template, start, count)?;
+            let mut existing = 0;
+
+            for volume_name in &volume_names {
+                if client.volume_exists(volume_name).await {
+                    existing += 1;
+                }
+            }
+
+            let existing_volume = match existing {
+                0 => ExistingVolume::Fail,
+                existing => confirm_replace_volume(
+                    &format!(
+                        "{} of the volumes already exist, do you wish to drop them?",
+                        existing
+                    ),
+                    yes,
+                    output,
+                )?,
+            };
+
+            CommandResult::RestoreCopies(
+                client
+                    .restore_copies(RestoreCopiesOptions {
+                        snapshot,
+                        name_template,
+                        count,
+                        start,
+                        existing_volume,
+                        drop_snapshot: drop,
+                        ignore_space,
+                        overrides: volume.into(),
+                    })
+                    .await?,
+            )
+        }
+        Commands::Restore {
+            drop,
+            ignore_space,
+            yes,
+            jobs,
+            volume: volume_args,
+            targets,
+            ..
+        } => {
+            let targets = parse_targets(targets, "SNAPSHOT:VOLUME")?;
+            let overrides = VolumeOverrides::from(volume_args);
+            let client = client_args.client()?;
+            let config = Config::load()?;
+            let mut restores = vec![];
+
+            // Asked up front, so that prompts don't interleave with progress bars.
+            for (snapshot, volume) in targets {
+                // Protected volumes are rejected by the client, there is no point in asking first.
+                let existing_volume =
+                    match client.volume_exists(&volume).await && !config.is_protected(&volume) {
+                        true => confirm_replace_volume(
+                            &format!("Volume {} already exists, do you wish to drop it?", volume),
+                            yes,
+                            output,
+                        )?,
+                        false => ExistingVolume::Fail,
+                    };
+
+                restores.push(RestoreOptions {
+                    snapshot,
+                    volume,
+                    existing_volume,
+                    drop_snapshot: drop,
+                    ignore_space,
+                    overrides: overrides.clone(),
+                });
+            }
+
+            match restores.len() {
+                1 => CommandResult::Restore(client.restore(restores.remove(0)).await?),
+                _ => CommandResult::RestoreBatch(
+                    run_with_progress(
+                        &client,
+                        &client_args.cancellation,
+                        restores
+                            .into_iter()
+                            .map(|options| {
+                                (format!("{}:{}", options.snapshot, options.volume), options)
+                            })
+                            .collect(),
+                        jobs,
+                        |client, options| async move { client.restore(options).await },
+                    )
+                    .await?,
+                ),
+            }
+        }
+        Commands::Clone {
+            ignore_space,
+            source_volume_name,
+            volume_name,
+        } => CommandResult::Clone(
+            client_args
+                .client()?
+                .clone_volume(CloneOptions {
+                    source_volume: source_volume_name,
+                    volume: volume_name,
+                    ignore_space,
+                })
+                .await?,
+        ),
+        Commands::Drop(Drop {
+            all,
+            force,
+            jobs,
+            snapshot_names,
+        }) => CommandResult::Drop(
+            client_args
+                .client()?
+                .drop(DropOptions {
+                    snapshots: snapshot_names,
+                    all,
+                    force,
+                    parallelism: jobs,
+                })
+                .await?,
+        ),
+        Commands::Rename {
+            snapshot_name,
+            new_snapshot_name,
+        } => CommandResult::Rename(
+            client_args
+                .client()?
+                .rename(&snapshot_name, &new_snapshot_name)
+                .await?,
+        ),
+        Commands::Copy {
+            snapshot_name,
+            new_snapshot_name,
+        } => CommandResult::Copy(
+            client_args
+                .client()?
+                .copy(&snapshot_name, &new_snapshot_name)
+                .await?,
+        ),
+        Commands::Push {
+            snapshot_name,
+            remote,
+        } => CommandResult::Push(
+            client_args
+                .client()?
+                .push(PushOptions {
+                    snapshot: snapshot_name,
+                    remote,
+                })
+                .await?,
+        ),
+        Commands::Pull {
+            remote,
+            snapshot_name,
+        } => CommandResult::Pull(
+            client_args
+                .client()?
+                .pull(PullOptions {
+                    remote,
+                    snapshot: snapshot_name,
+                })
+                .await?,
+        ),
+        Commands::Remote(RemoteCommands::List { remote }) => CommandResult::RemoteList {
+            snapshots: client_args.client()?.list_remote(&remote).await?,
+        },
+        Commands::Remote(RemoteCommands::Delete { remote }) => {
+            client_args.client()?.delete_remote(&remote).await?;
+
+            CommandResult::RemoteDelete { remote }
+        }
+        Commands::Inspect {
+            verify,
             snapshot_name,
-            restore_volume_name,
         } => {
-            restore(snapshot_name, restore_volume_name, drop).await?;
+            let details = client_args
+                .client()?
+                .inspect(&snapshot_name, InspectOptions { verify })
+                .await?;
+
+            if output == OutputFormat::Text {
+                print_snapshot_details(&details)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            CommandResult::Inspect {
+                snapshot: Box::new(details),
+            }
+        }
+        Commands::Pin { snapshot_name } => {
+            client_args.client()?.pin(&snapshot_name, true).await?;
+
+            CommandResult::Pin {
+                snapshot: snapshot_name,
+                pinned: true,
+            }
+        }
+        Commands::Unpin { snapshot_name } => {
+            client_args.client()?.pin(&snapshot_name, false).await?;
+
+            CommandResult::Pin {
+                snapshot: snapshot_name,
+                pinned: false,
+            }
+        }
+        Commands::Protect { pattern } => {
+            let print_patterns = pattern.is_none() || output == OutputFormat::Json;
+            let result = protect(pattern)?;
+
+            if !print_patterns {
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            result
         }
-        Commands::Drop(Drop { all, snapshot_name }) => match all {
-            true => {
-                let docker = Docker::connect_with_local_defaults()?;
-                let snapshot_volume_names = find_snapshot_volume_names(&docker).await?;
+        Commands::Unprotect { pattern } => {
+            let result = unprotect(pattern)?;
 
-                for snapshot_volume_name in snapshot_volume_names {
-                    drop_volume(&docker, &snapshot_volume_name).await?;
+            if output == OutputFormat::Text {
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            result
+        }
+        Commands::Cleanup { name_template, yes } => {
+            let client = client_args.client()?;
+            let volume_names = client.find_copies(&name_template).await?;
+
+            if output == OutputFormat::Text && !yes {
+                for volume_name in &volume_names {
+                    println!("{}", volume_name);
                 }
             }
-            false => {
-                drop(snapshot_name.ok_or(anyhow!("Snapshot name is required"))?).await?;
+
+            let dropped_volumes = match !volume_names.is_empty()
+                && (yes || confirm_cleanup(volume_names.len(), output)?)
+            {
+                true => client.drop_copies(&name_template).await?,
+                false => vec![],
+            };
+
+            CommandResult::Cleanup { dropped_volumes }
+        }
+        Commands::Gc { yes, dry_run } => {
+            let client = client_args.client()?;
+            let garbage = client.find_garbage().await?;
+
+            if output == OutputFormat::Text {
+                for item in &garbage {
+                    println!("{}: {}", item.name, item.reason);
+                }
             }
-        },
+
+            let remove =
+                !garbage.is_empty() && !dry_run && (yes || confirm_gc(garbage.len(), output)?);
+
+            CommandResult::Gc(match remove {
+                true => client.collect_garbage(garbage).await?,
+                false => GcResult {
+                    removed: vec![],
+                    kept: garbage,
+                },
+            })
+        }
+        Commands::Doctor => CommandResult::Doctor(match client_args.client() {
+            Ok(client) => client.doctor().await,
+            Err(error) => DoctorReport::new(vec![engine_unreachable(error.to_string())]),
+        }),
+        Commands::Image(ImageCommands::Pull) => {
+            CommandResult::Image(client_args.client()?.pull_image().await?)
+        }
+        Commands::Image(ImageCommands::Load { archive }) => {
+            CommandResult::Image(client_args.client()?.load_image(&archive).await?)
+        }
+        Commands::Image(ImageCommands::Build { source }) => {
+            CommandResult::Image(client_args.client()?.build_image(&source).await?)
+        }
+    };
+
+    match output {
+        OutputFormat::Json => print_json_result(&result)?,
+        OutputFormat::Text => print_text_result(&result),
     }
 
-    Ok(())
+    // The report is the output, failed checks only show in the exit code.
+    Ok(match result.failed() {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    })
 }
 
-async fn create(
-    source_volume_name: String,
-    snapshot_name: String,
-    compress: bool,
-) -> anyhow::Result<()> {
-    let docker = Docker::connect_with_local_defaults()?;
-
-    verify_snapshot_does_not_exist(&docker, &snapshot_name).await?;
+/// Splits `first:second` pairs. A single pair may also be given as two arguments.
+fn parse_targets(targets: Vec<String>, format: &str) -> Result<Vec<(String, String)>, Error> {
+    match targets.as_slice() {
+        [first, second] if !first.contains(':') && !second.contains(':') => {
+            return Ok(vec![(first.clone(), second.clone())]);
+        }
+        _ => {}
+    }
 
-    let snapshot_volume_name =
-        get_snapshot_volume_name(chrono::Utc::now().timestamp(), &snapshot_name);
+    targets
+        .iter()
+        .map(|target| match target.split_once(':') {
+            Some((first, second)) if !first.is_empty() && !second.is_empty() => {
+                Ok((first.to_string(), second.to_string()))
+            }
+            _ => Err(Error::InvalidArgument(format!(
+                "Expected {}, got {}",
+                format, target
+            ))),
+        })
+        .collect()
+}
 
-    verify_volume_not_in_use(&docker, &source_volume_name).await?;
-    verify_volume_exists(&docker, &source_volume_name).await?;
+fn labelled(targets: Vec<(String, String)>) -> Vec<(String, (String, String))> {
+    targets
+        .into_iter()
+        .map(|(first, second)| (format!("{}:{}", first, second), (first, second)))
+        .collect()
+}
 
-    create_volume(&docker, &snapshot_volume_name).await?;
+/// Runs a batch with a progress line per target. An interrupted batch fails as a whole.
+async fn run_with_progress<I, T, F, Fut>(
+    client: &VsnapClient,
+    cancellation: &Cancellation,
+    targets: Vec<(String, I)>,
+    jobs: usize,
+    run: F,
+) -> Result<BatchResult<T>, Error>
+where
+    F: Fn(VsnapClient, I) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let multi = MultiProgress::new();
 
-    if let Err(e) = snapshot(
-        &docker,
-        &source_volume_name,
-        &snapshot_volume_name,
-        compress,
+    let result = run_batch(
+        targets
+            .into_iter()
+            .map(|(target, input)| (target.clone(), (target, input)))
+            .collect(),
+        jobs,
+        |(target, input)| {
+            run(
+                client
+                    .clone()
+                    .with_progress(multi_terminal_progress(&multi, &target)),
+                input,
+            )
+        },
     )
-    .await
-    {
-        drop_volume(&docker, &snapshot_volume_name).await.ok();
-        return Err(e);
-    };
+    .await;
 
-    Ok(())
+    match cancellation.reason() {
+        Some(reason) => Err(reason.into()),
+        None => Ok(result),
+    }
 }
 
-async fn list(include_size: bool) -> anyhow::Result<()> {
-    let docker = Docker::connect_with_local_defaults()?;
+/// Prompts need someone reading text output on a terminal, scripts get an error instead of
+/// hanging on a question nobody answers.
+fn is_interactive(output: OutputFormat) -> bool {
+    output == OutputFormat::Text && std::io::stdin().is_terminal()
+}
 
-    let mut volume_names = find_snapshot_volume_names(&docker).await?;
-    volume_names.sort();
+/// Without `--yes` and a terminal to ask on, the existing volume is kept and the restore fails
+/// with `VolumeExists`.
+fn confirm_replace_volume(
+    question: &str,
+    yes: bool,
+    output: OutputFormat,
+) -> Result<ExistingVolume, Error> {
+    match (yes, is_interactive(output)) {
+        (true, _) => return Ok(ExistingVolume::Replace),
+        (false, false) => return Ok(ExistingVolume::Fail),
+        (false, true) => {}
+    }
 
-    let volume_sizes = match include_size {
-        true => Some(get_volume_sizes_for_volume_names(&docker, &volume_names).await?),
-        false => None,
-    };
+    let ans = Confirm::new(question)
+        .with_default(false)
+        .with_help_message("This will delete the volume and all its data.")
+        .prompt()
+        .map_err(anyhow::Error::from)?;
 
-    if volume_names.is_empty() {
-        println!("No snapshots found.");
-        return Ok(());
-    }
+    Ok(match ans {
+        true => ExistingVolume::Replace,
+        false => ExistingVolume::Merge,
+    })
+}
+
+fn confirm_cleanup(count: usize, output: OutputFormat) -> Result<bool, Error> {
+    verify_interactive(output)?;
+
+    Ok(Confirm::new(&format!("Drop these {} volumes?", count))
+        .with_default(false)
+        .with_help_message("Dropped volumes and their data cannot be recovered.")
+        .prompt()
+        .map_err(anyhow::Error::from)?)
+}
 
-    print_snapshot_table(volume_names, volume_sizes)?;
+fn confirm_gc(count: usize, output: OutputFormat) -> Result<bool, Error> {
+    verify_interactive(output)?;
 
-    Ok(())
+    Ok(Confirm::new(&format!("Remove these {} leftovers?", count))
+        .with_default(false)
+        .with_help_message("Removed volumes and their data cannot be recovered.")
+        .prompt()
+        .map_err(anyhow::Error::from)?)
 }
 
-async fn restore(
-    snapshot_name: String,
-    restore_volume_name: String,
-    drop: bool,
-) -> anyhow::Result<()> {
-    let docker = Docker::connect_with_local_defaults()?;
+fn verify_interactive(output: OutputFormat) -> Result<(), Error> {
+    match is_interactive(output) {
+        true => Ok(()),
+        false => Err(Error::InvalidArgument(
+            "Not asking for confirmation without a terminal, pass --yes".to_string(),
+        )),
+    }
+}
 
-    let snapshot_volume_name = find_snapshot_volume_name_by_snapshot_name(&docker, &snapshot_name)
-        .await?
-        .ok_or(anyhow!("Snapshot {} not found", snapshot_name))?;
+fn print_text_result(result: &CommandResult) {
+    match result {
+        CommandResult::Doctor(report) => {
+            for check in &report.checks {
+                let status = match check.status {
+                    CheckStatus::Pass => "pass",
+                    CheckStatus::Warn => "warn",
+                    CheckStatus::Fail => "fail",
+                };
 
-    if volume_exists(&docker, &restore_volume_name).await {
-        let ans = Confirm::new("The volume to restore to already exists, do you wish to drop it?")
-            .with_default(false)
-            .with_help_message("This will delete the volume and all its data.")
-            .prompt();
+                println!("[{}] {}: {}", status, check.name, check.message);
 
-        if ans? {
-            drop_volume(&docker, &restore_volume_name).await?;
+                if let Some(hint) = &check.hint {
+                    println!("       {}", hint);
+                }
+            }
         }
-    }
+        CommandResult::Gc(result) => match (result.removed.len(), result.kept.len()) {
+            (0, 0) => println!("Nothing to clean up"),
+            (0, _) => {}
+            (removed, _) => println!("Removed {} leftovers", removed),
+        },
+        CommandResult::Drop(result) => {
+            for snapshot_name in &result.skipped {
+                println!("Skipping pinned snapshot {}", snapshot_name);
+            }
 
-    create_volume(&docker, &restore_volume_name).await?;
-    restore_snapshot(&docker, &snapshot_volume_name, &restore_volume_name).await?;
+            for failed in &result.failed {
+                println!(
+                    "Failed to drop {}: {}",
+                    failed.snapshot, failed.error.message
+                );
+            }
 
-    if drop {
-        drop_volume(&docker, &snapshot_volume_name).await?;
+            if !result.failed.is_empty() {
+                println!(
+                    "{} dropped, {} failed",
+                    result.dropped.len(),
+                    result.failed.len()
+                );
+            }
+        }
+        CommandResult::RestoreCopies(result) => {
+            for volume_name in &result.volume_names {
+                println!("{}", volume_name);
+            }
+        }
+        CommandResult::Cleanup { dropped_volumes } if dropped_volumes.is_empty() => {
+            println!("Nothing to clean up")
+        }
+        CommandResult::CreateBatch(result) => print_batch_summary(result),
+        CommandResult::RestoreBatch(result) => print_batch_summary(result),
+        CommandResult::Protect { protected_volumes } => {
+            for pattern in protected_volumes {
+                println!("{}", pattern);
+            }
+        }
+        CommandResult::RemoteList { snapshots } => {
+            let now = chrono::Utc::now().timestamp();
+
+            for snapshot in snapshots {
+                let size = snapshot
+                    .size
+                    .map(|size| HumanBytes(size).to_string())
+                    .unwrap_or("-".to_string());
+                let age = snapshot
+                    .modified_at
+                    .map(|modified_at| format_age(now - modified_at))
+                    .unwrap_or("-".to_string());
+
+                println!("{}\t{}\t{}", snapshot.remote, size, age);
+            }
+        }
+        _ => {}
     }
+}
 
-    Ok(())
+fn print_batch_summary<T>(result: &BatchResult<T>) {
+    for item in &result.items {
+        if let Some(error) = &item.error {
+            println!("Failed {}: {}", item.target, error.message);
+        }
+    }
+
+    println!("{} succeeded, {} failed", result.succeeded, result.failed);
 }
 
-async fn drop(snapshot_name: String) -> anyhow::Result<()> {
-    let docker = Docker::connect_with_local_defaults()?;
+fn protect(pattern: Option<String>) -> anyhow::Result<CommandResult> {
+    let protected_volumes = match pattern {
+        Some(pattern) => Config::update(|config| {
+            config.protect(&pattern);
+            config.protected_volumes.clone()
+        })?,
+        None => Config::load()?.protected_volumes,
+    };
 
-    let snapshot_volume_name = find_snapshot_volume_name_by_snapshot_name(&docker, &snapshot_name)
-        .await?
-        .ok_or(anyhow!("Snapshot {} not found", snapshot_name))?;
+    Ok(CommandResult::Protect { protected_volumes })
+}
+
+fn unprotect(pattern: String) -> anyhow::Result<CommandResult> {
+    let protected_volumes = Config::update(|config| {
+        config
+            .unprotect(&pattern)
+            .then(|| config.protected_volumes.clone())
+    })?;
 
-    drop_volume(&docker, &snapshot_volume_name).await?;
+    let Some(protected_volumes) = protected_volumes else {
+        return Err(Error::InvalidArgument(format!("Pattern {} is not protected", pattern)).into());
+    };
 
-    Ok(())
+    Ok(CommandResult::Protect { protected_volumes })
 }
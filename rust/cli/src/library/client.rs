@@ -0,0 +1,940 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use bollard::Docker;
+use futures::{StreamExt, stream};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::library::{
+    backend::{Backend, VolumeSize, docker::DockerBackend},
+    batch::BatchError,
+    binary::{RunnerBinary, find_bundled_runner_binary},
+    cancel::Cancellation,
+    capabilities::Capabilities,
+    config::Config,
+    connection::ConnectionOptions,
+    constant::{
+        BASE_IMAGE, LABEL_COMPRESSED, LABEL_PARENT, LABEL_SOURCE, LABEL_VERSION, RUNNER_IMAGE,
+        SNAPSHOT_PREFIX, VERSION,
+    },
+    directory::SnapshotDirectory,
+    docker::{
+        CapabilitiesCache, RunnerOptions, copy_snapshot, create_snapshot_volume,
+        drop_snapshot_volume, find_dependent_snapshot_volume_names,
+        find_snapshot_volume_name_by_snapshot_name, find_snapshot_volume_names,
+        find_snapshot_volumes, get_snapshot_volume_labels,
+        get_snapshot_volume_name_by_snapshot_name, get_snapshot_volume_sizes, get_volume_info,
+        inspect_snapshot, runner_capabilities, snapshot, verify_snapshot_does_not_exist,
+        verify_volume_not_in_use, volume_exists,
+    },
+    error::Error,
+    journal::Journal,
+    listing::{ListFilter, SnapshotEntry, SortKey, sort_entries},
+    lock::{LockGuard, LockMode, LockTarget, acquire_locks},
+    metadata::SnapshotDetails,
+    progress::{ProgressHandler, no_progress},
+    remote::RemoteStore,
+    storage::SnapshotStorage,
+    volume::{VolumeOverrides, source_spec_labels},
+};
+
+mod doctor;
+mod gc;
+mod image;
+mod restore;
+mod transfer;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Debug, Default)]
+pub struct CreateOptions {
+    pub source_volume: String,
+    pub snapshot: String,
+    pub compress: bool,
+    /// Replace an existing snapshot with the same name.
+    pub force: bool,
+    /// Only warn when the disk looks too small.
+    pub ignore_space: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ListOptions {
+    pub include_size: bool,
+    pub filter: ListFilter,
+    pub sort: SortKey,
+}
+
+/// What to do when the volume to restore to already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExistingVolume {
+    #[default]
+    Fail,
+    /// Drop the volume and restore into a fresh one.
+    Replace,
+    /// Restore on top of the existing contents.
+    Merge,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RestoreOptions {
+    pub snapshot: String,
+    pub volume: String,
+    pub existing_volume: ExistingVolume,
+    /// Drop the snapshot after a successful restore.
+    pub drop_snapshot: bool,
+    /// Only warn when the disk looks too small.
+    pub ignore_space: bool,
+    /// Changes to the driver, options and labels recorded from the snapshotted volume.
+    pub overrides: VolumeOverrides,
+}
+
+/// Restores one snapshot into several volumes named after a template like `test-db-{i}`.
+#[derive(Clone, Debug, Default)]
+pub struct RestoreCopiesOptions {
+    pub snapshot: String,
+    pub name_template: String,
+    pub count: usize,
+    /// Index of the first volume.
+    pub start: usize,
+    pub existing_volume: ExistingVolume,
+    /// Drop the snapshot after a successful restore.
+    pub drop_snapshot: bool,
+    /// Only warn when the disk looks too small.
+    pub ignore_space: bool,
+    /// Changes to the driver, options and labels recorded from the snapshotted volume.
+    pub overrides: VolumeOverrides,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CloneOptions {
+    pub source_volume: String,
+    pub volume: String,
+    /// Only warn when the disk looks too small.
+    pub ignore_space: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DropOptions {
+    /// Snapshots to drop. Ignored when `all` is set.
+    pub snapshots: Vec<String>,
+    pub all: bool,
+    /// Also drop pinned snapshots.
+    pub force: bool,
+    /// How many snapshots to drop at the same time, at least one.
+    pub parallelism: usize,
+}
+
+/// Uploads a snapshot to a remote such as `s3://bucket/path`. A remote ending in `/` is a
+/// folder the snapshot is stored in under its own name.
+#[derive(Clone, Debug, Default)]
+pub struct PushOptions {
+    pub snapshot: String,
+    pub remote: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PullOptions {
+    pub remote: String,
+    pub snapshot: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InspectOptions {
+    /// Verify the archive checksum, reading the whole archive.
+    pub verify: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CreateResult {
+    pub snapshot: String,
+    pub volume_name: String,
+    pub source_volume: String,
+    pub compressed: bool,
+    pub replaced: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RestoreResult {
+    pub snapshot: String,
+    pub volume_name: String,
+    pub replaced_volume: bool,
+    pub dropped_snapshot: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RestoreCopiesResult {
+    pub snapshot: String,
+    pub volume_names: Vec<String>,
+    pub replaced_volumes: Vec<String>,
+    pub dropped_snapshot: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CloneResult {
+    pub source_volume: String,
+    pub volume_name: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DropResult {
+    pub dropped: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<FailedDrop>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FailedDrop {
+    pub snapshot: String,
+    pub error: BatchError,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ImageResult {
+    pub image: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PushResult {
+    pub snapshot: String,
+    pub remote: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PullResult {
+    pub remote: String,
+    pub snapshot: String,
+    pub volume_name: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CopyResult {
+    pub snapshot: String,
+    pub new_snapshot: String,
+    pub volume_name: String,
+}
+
+/// Async API for snapshotting and restoring Docker volumes.
+#[derive(Clone)]
+pub struct VsnapClient {
+    backend: Arc<dyn Backend>,
+    progress: ProgressHandler,
+    config_path: PathBuf,
+    image: Option<String>,
+    image_archive: Option<PathBuf>,
+    runner_binary: Option<PathBuf>,
+    /// The runner binary once it's been read, shared between clones.
+    resolved_runner_binary: Arc<OnceLock<Option<RunnerBinary>>>,
+    capabilities: CapabilitiesCache,
+    cancellation: Cancellation,
+    wait_for_locks: bool,
+    snapshot_prefix: Option<String>,
+    snapshot_driver: Option<String>,
+    snapshot_driver_opts: HashMap<String, String>,
+    snapshot_dir: Option<PathBuf>,
+    remote_stores: HashMap<String, Arc<dyn RemoteStore>>,
+}
+
+impl VsnapClient {
+    /// Connects like the docker CLI would, honouring DOCKER_HOST and the current context.
+    pub fn new() -> Result<Self> {
+        Self::connect(&ConnectionOptions::default().with_env_defaults())
+    }
+
+    pub fn connect(options: &ConnectionOptions) -> Result<Self> {
+        Self::with_backend(Arc::new(DockerBackend::connect(options)?))
+    }
+
+    pub fn with_docker(docker: Docker) -> Result<Self> {
+        Self::with_backend(Arc::new(DockerBackend::new(docker)))
+    }
+
+    pub fn with_backend(backend: Arc<dyn Backend>) -> Result<Self> {
+        Ok(VsnapClient {
+            backend,
+            progress: no_progress(),
+            config_path: Config::path()?,
+            image: None,
+            image_archive: None,
+            runner_binary: None,
+            resolved_runner_binary: Default::default(),
+            capabilities: Default::default(),
+            cancellation: Cancellation::default(),
+            wait_for_locks: true,
+            snapshot_prefix: None,
+            snapshot_driver: None,
+            snapshot_driver_opts: HashMap::new(),
+            snapshot_dir: None,
+            remote_stores: HashMap::new(),
+        })
+    }
+
+    /// Receive progress events instead of discarding them.
+    pub fn with_progress(mut self, progress: ProgressHandler) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Read and write pins and protected volumes in another config file.
+    pub fn with_config_path(mut self, config_path: PathBuf) -> Self {
+        self.config_path = config_path;
+        self.resolved_runner_binary = Default::default();
+        self
+    }
+
+    /// Use another runner image than the configured or default one.
+    pub fn with_image(mut self, image: String) -> Self {
+        self.image = Some(image);
+        self
+    }
+
+    /// Load the runner image from a `docker save` archive when it is missing instead of pulling.
+    pub fn with_image_archive(mut self, image_archive: PathBuf) -> Self {
+        self.image_archive = Some(image_archive);
+        self
+    }
+
+    /// Inject a static runner binary into a stock image instead of using a runner image.
+    pub fn with_runner_binary(mut self, runner_binary: PathBuf) -> Self {
+        self.runner_binary = Some(runner_binary);
+        self.resolved_runner_binary = Default::default();
+        self
+    }
+
+    /// Cancel operations through `cancellation`, e.g. on Ctrl-C or after a timeout.
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Fail right away instead of waiting when another operation holds a volume or snapshot.
+    pub fn with_lock_wait(mut self, wait: bool) -> Self {
+        self.wait_for_locks = wait;
+        self
+    }
+
+    /// Keep snapshots under another prefix than the configured or default `vsnap-`, e.g. one
+    /// per project. Snapshots under other prefixes are invisible to this client.
+    pub fn with_snapshot_prefix(mut self, prefix: String) -> Self {
+        self.snapshot_prefix = Some(prefix);
+        self
+    }
+
+    /// Create snapshot volumes with this driver and these options, on top of the configured
+    /// ones.
+    pub fn with_snapshot_driver(
+        mut self,
+        driver: Option<String>,
+        driver_opts: HashMap<String, String>,
+    ) -> Self {
+        self.snapshot_driver = driver;
+        self.snapshot_driver_opts = driver_opts;
+        self
+    }
+
+    /// Keep snapshots as files below `snapshot_dir` on the engine's host instead of in volumes.
+    pub fn with_snapshot_dir(mut self, snapshot_dir: PathBuf) -> Self {
+        self.snapshot_dir = Some(snapshot_dir);
+        self
+    }
+
+    /// Push to and pull from remotes with this scheme through `store`, in place of a built-in
+    /// store.
+    pub fn with_remote_store(mut self, scheme: &str, store: Arc<dyn RemoteStore>) -> Self {
+        self.remote_stores.insert(scheme.to_string(), store);
+        self
+    }
+
+    pub async fn volume_exists(&self, volume_name: &str) -> bool {
+        volume_exists(self.backend.as_ref(), volume_name).await
+    }
+
+    pub async fn create(&self, options: CreateOptions) -> Result<CreateResult> {
+        let targets = vec![
+            (
+                LockTarget::Volume(options.source_volume.clone()),
+                LockMode::Shared,
+            ),
+            (
+                self.storage()?.snapshot_lock(&options.snapshot),
+                LockMode::Exclusive,
+            ),
+        ];
+
+        self.locked("create", targets, self.create_locked(options))
+            .await
+    }
+
+    async fn create_locked(&self, options: CreateOptions) -> Result<CreateResult> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+        let CreateOptions {
+            source_volume,
+            snapshot: snapshot_name,
+            compress,
+            force,
+            ignore_space,
+        } = options;
+
+        let journal = self.load_journal()?;
+
+        let existing_volume_name = match force {
+            true => {
+                find_snapshot_volume_name_by_snapshot_name(
+                    backend,
+                    &storage,
+                    &snapshot_name,
+                    &journal,
+                )
+                .await?
+            }
+            false => {
+                verify_snapshot_does_not_exist(backend, &storage, &snapshot_name, &journal).await?;
+                None
+            }
+        };
+
+        // The replacement is written next to the existing snapshot, which stays untouched until
+        // the replacement is complete, so it needs another name even within the same second.
+        let timestamp = match &existing_volume_name {
+            Some(existing_volume_name) => chrono::Utc::now()
+                .timestamp()
+                .max(storage.snapshot_timestamp(existing_volume_name)? + 1),
+            None => chrono::Utc::now().timestamp(),
+        };
+        let snapshot_volume_name = storage.snapshot_volume_name(timestamp, &snapshot_name);
+
+        verify_volume_not_in_use(backend, &source_volume).await?;
+
+        // The source's driver, options and labels are recreated when the snapshot is restored.
+        let mut labels = source_spec_labels(&get_volume_info(backend, &source_volume).await?)?;
+
+        labels.extend([
+            (LABEL_SOURCE.to_string(), source_volume.clone()),
+            (LABEL_COMPRESSED.to_string(), compress.to_string()),
+            (LABEL_VERSION.to_string(), VERSION.to_string()),
+        ]);
+
+        // Until the replacement is complete, names resolve to the existing snapshot, afterwards
+        // to the replacement, and gc drops whichever an interruption left behind.
+        self.update_journal(|journal| {
+            journal.start_create(&snapshot_volume_name, &snapshot_name);
+
+            if let Some(existing_volume_name) = &existing_volume_name {
+                journal.start_replace(existing_volume_name, &snapshot_volume_name);
+            }
+        })?;
+        create_snapshot_volume(backend, &storage, &snapshot_volume_name, labels).await?;
+
+        if let Err(e) = snapshot(
+            backend,
+            &self.runner_options()?,
+            &source_volume,
+            storage.mount_source(&snapshot_volume_name),
+            compress,
+            ignore_space,
+            &self.progress,
+        )
+        .await
+        {
+            if drop_snapshot_volume(backend, &storage, &snapshot_volume_name)
+                .await
+                .is_ok()
+            {
+                self.update_journal(|journal| {
+                    journal.finish_create(&snapshot_volume_name);
+
+                    if let Some(existing_volume_name) = &existing_volume_name {
+                        journal.finish_replace(existing_volume_name);
+                    }
+                })?;
+            }
+
+            return Err(e.into());
+        };
+
+        self.update_journal(|journal| journal.finish_create(&snapshot_volume_name))?;
+
+        if let Some(existing_volume_name) = &existing_volume_name {
+            self.transfer_pin(existing_volume_name, &snapshot_volume_name)?;
+            drop_snapshot_volume(backend, &storage, existing_volume_name).await?;
+            self.update_journal(|journal| journal.finish_replace(existing_volume_name))?;
+        }
+
+        Ok(CreateResult {
+            snapshot: snapshot_name,
+            volume_name: snapshot_volume_name,
+            source_volume,
+            compressed: compress,
+            replaced: existing_volume_name.is_some(),
+        })
+    }
+
+    pub async fn list(&self, options: ListOptions) -> Result<Vec<SnapshotEntry>> {
+        let backend = self.backend.as_ref();
+        let config = self.load_config()?;
+        let storage = self.storage()?;
+
+        let include_size = options.include_size || matches!(options.sort, SortKey::Size);
+
+        let volumes = find_snapshot_volumes(backend, &storage).await?;
+        let volume_names = volumes
+            .iter()
+            .map(|volume| volume.name.clone())
+            .collect::<Vec<String>>();
+
+        let volume_sizes = match include_size {
+            true => get_snapshot_volume_sizes(backend, &storage, &volume_names).await?,
+            false => HashMap::new(),
+        };
+
+        let mut entries = volumes
+            .into_iter()
+            .map(|volume| {
+                Ok(SnapshotEntry {
+                    name: storage.snapshot_name(&volume.name),
+                    created_at: storage.snapshot_timestamp(&volume.name)?,
+                    source: volume.labels.get(LABEL_SOURCE).cloned(),
+                    size: match volume_sizes.get(&volume.name) {
+                        Some(VolumeSize::Bytes(size)) => u64::try_from(*size).ok(),
+                        _ => None,
+                    },
+                    pinned: config.is_pinned(&volume.name),
+                    volume_name: volume.name,
+                })
+            })
+            .filter_ok(|entry| options.filter.matches(entry))
+            .collect::<anyhow::Result<Vec<SnapshotEntry>>>()?;
+
+        sort_entries(&mut entries, options.sort);
+
+        Ok(entries)
+    }
+
+    pub async fn drop(&self, options: DropOptions) -> Result<DropResult> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+
+        let snapshot_volume_names = match options.all {
+            true => find_snapshot_volume_names(backend, &storage).await?,
+            false => {
+                let mut snapshot_volume_names = vec![];
+
+                for snapshot_name in &options.snapshots {
+                    snapshot_volume_names.push(
+                        get_snapshot_volume_name_by_snapshot_name(
+                            backend,
+                            &storage,
+                            snapshot_name,
+                            &self.load_journal()?,
+                        )
+                        .await?,
+                    );
+                }
+
+                snapshot_volume_names
+            }
+        };
+
+        let storage = &storage;
+        let options = &options;
+        let outcomes = stream::iter(snapshot_volume_names)
+            .map(|snapshot_volume_name| async move {
+                let snapshot_name = storage.snapshot_name(&snapshot_volume_name);
+                let result = self
+                    .locked(
+                        "drop",
+                        vec![(storage.snapshot_lock(&snapshot_name), LockMode::Exclusive)],
+                        async {
+                            // Pins change under the snapshot lock, so this one holds until the
+                            // snapshot is gone.
+                            if self.load_config()?.is_pinned(&snapshot_volume_name)
+                                && !options.force
+                            {
+                                return match options.all {
+                                    true => Ok(false),
+                                    false => Err(Error::SnapshotPinned {
+                                        snapshot: snapshot_name.clone(),
+                                        hint: "use --force to drop it anyway".to_string(),
+                                    }),
+                                };
+                            }
+
+                            drop_snapshot_volume(backend, storage, &snapshot_volume_name).await?;
+                            self.update_config(|config| config.unpin(&snapshot_volume_name))?;
+
+                            Ok(true)
+                        },
+                    )
+                    .await;
+
+                (snapshot_name, result)
+            })
+            .buffered(options.parallelism.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        let single = outcomes.len() == 1;
+        let mut dropped = vec![];
+        let mut skipped = vec![];
+        let mut failed = vec![];
+        let mut first_error = None;
+
+        for (snapshot_name, result) in outcomes {
+            match result {
+                Ok(true) => dropped.push(snapshot_name),
+                Ok(false) => skipped.push(snapshot_name),
+                Err(error) => {
+                    failed.push(FailedDrop {
+                        snapshot: snapshot_name,
+                        error: BatchError::from(&error),
+                    });
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+
+        // A single snapshot fails with its own error, like any other command.
+        if let Some(error) = first_error.filter(|_| single) {
+            return Err(error);
+        }
+
+        Ok(DropResult {
+            dropped,
+            skipped,
+            failed,
+        })
+    }
+
+    pub async fn inspect(
+        &self,
+        snapshot_name: &str,
+        options: InspectOptions,
+    ) -> Result<SnapshotDetails> {
+        let backend = self.backend.as_ref();
+        let config = self.load_config()?;
+        let storage = self.storage()?;
+
+        let snapshot_volume_name = get_snapshot_volume_name_by_snapshot_name(
+            backend,
+            &storage,
+            snapshot_name,
+            &self.load_journal()?,
+        )
+        .await?;
+
+        let labels = get_snapshot_volume_labels(backend, &storage, &snapshot_volume_name).await?;
+
+        Ok(SnapshotDetails {
+            name: snapshot_name.to_string(),
+            created_at: storage.snapshot_timestamp(&snapshot_volume_name)?,
+            pinned: config.is_pinned(&snapshot_volume_name),
+            parent: labels
+                .get(LABEL_PARENT)
+                .map(|parent| storage.snapshot_name(parent)),
+            dependents: find_dependent_snapshot_volume_names(
+                backend,
+                &storage,
+                &snapshot_volume_name,
+            )
+            .await?
+            .iter()
+            .map(|name| storage.snapshot_name(name))
+            .collect(),
+            inspection: inspect_snapshot(
+                backend,
+                &self.runner_options()?,
+                storage.mount_source(&snapshot_volume_name),
+                options.verify,
+                &self.progress,
+            )
+            .await?,
+            volume_name: snapshot_volume_name,
+            labels: labels.into_iter().collect(),
+        })
+    }
+
+    /// Copies a snapshot under a new name, keeping its creation time.
+    pub async fn copy(&self, snapshot_name: &str, new_snapshot_name: &str) -> Result<CopyResult> {
+        let storage = self.storage()?;
+
+        self.locked(
+            "copy",
+            vec![
+                (storage.snapshot_lock(snapshot_name), LockMode::Shared),
+                (
+                    storage.snapshot_lock(new_snapshot_name),
+                    LockMode::Exclusive,
+                ),
+            ],
+            self.copy_or_rename(snapshot_name, new_snapshot_name, false),
+        )
+        .await
+    }
+
+    /// Renames a snapshot, keeping its creation time.
+    pub async fn rename(&self, snapshot_name: &str, new_snapshot_name: &str) -> Result<CopyResult> {
+        let storage = self.storage()?;
+
+        self.locked(
+            "rename",
+            vec![
+                (storage.snapshot_lock(snapshot_name), LockMode::Exclusive),
+                (
+                    storage.snapshot_lock(new_snapshot_name),
+                    LockMode::Exclusive,
+                ),
+            ],
+            self.copy_or_rename(snapshot_name, new_snapshot_name, true),
+        )
+        .await
+    }
+
+    pub async fn pin(&self, snapshot_name: &str, pinned: bool) -> Result<()> {
+        let storage = self.storage()?;
+
+        // Under the snapshot lock, so that a drop running at the same time either sees the pin
+        // or is done before it's set.
+        let targets = vec![(storage.snapshot_lock(snapshot_name), LockMode::Shared)];
+
+        self.locked("pin", targets, async {
+            let snapshot_volume_name = get_snapshot_volume_name_by_snapshot_name(
+                self.backend.as_ref(),
+                &storage,
+                snapshot_name,
+                &self.load_journal()?,
+            )
+            .await?;
+
+            self.update_config(|config| match pinned {
+                true => config.pin(&snapshot_volume_name),
+                false => config.unpin(&snapshot_volume_name),
+            })?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Asks the runner image what it supports.
+    pub async fn runner_capabilities(&self) -> Result<Capabilities> {
+        Ok(runner_capabilities(
+            self.backend.as_ref(),
+            &self.runner_options()?,
+            &self.progress,
+        )
+        .await?)
+    }
+
+    async fn copy_or_rename(
+        &self,
+        snapshot_name: &str,
+        new_snapshot_name: &str,
+        drop_original: bool,
+    ) -> Result<CopyResult> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+
+        let snapshot_volume_name = get_snapshot_volume_name_by_snapshot_name(
+            backend,
+            &storage,
+            snapshot_name,
+            &self.load_journal()?,
+        )
+        .await?;
+
+        verify_snapshot_does_not_exist(backend, &storage, new_snapshot_name, &self.load_journal()?)
+            .await?;
+
+        let new_snapshot_volume_name = storage.snapshot_volume_name(
+            storage.snapshot_timestamp(&snapshot_volume_name)?,
+            new_snapshot_name,
+        );
+
+        let parent = (!drop_original).then_some(snapshot_volume_name.as_str());
+
+        self.copy_snapshot_volume(&snapshot_volume_name, &new_snapshot_volume_name, parent)
+            .await?;
+
+        if drop_original {
+            drop_snapshot_volume(backend, &storage, &snapshot_volume_name).await?;
+            self.transfer_pin(&snapshot_volume_name, &new_snapshot_volume_name)?;
+        }
+
+        Ok(CopyResult {
+            snapshot: snapshot_name.to_string(),
+            new_snapshot: new_snapshot_name.to_string(),
+            volume_name: new_snapshot_volume_name,
+        })
+    }
+
+    async fn copy_snapshot_volume(
+        &self,
+        snapshot_volume_name: &str,
+        destination_volume_name: &str,
+        parent: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+        let mut labels =
+            get_snapshot_volume_labels(backend, &storage, snapshot_volume_name).await?;
+
+        if let Some(parent) = parent {
+            labels.insert(LABEL_PARENT.to_string(), parent.to_string());
+        }
+
+        create_snapshot_volume(backend, &storage, destination_volume_name, labels).await?;
+
+        if let Err(e) = copy_snapshot(
+            backend,
+            &self.runner_options()?,
+            storage.mount_source(snapshot_volume_name),
+            storage.mount_source(destination_volume_name),
+            &self.progress,
+        )
+        .await
+        {
+            drop_snapshot_volume(backend, &storage, destination_volume_name)
+                .await
+                .ok();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn lock(
+        &self,
+        operation: &str,
+        targets: Vec<(LockTarget, LockMode)>,
+    ) -> Result<LockGuard> {
+        acquire_locks(
+            self.backend.as_ref(),
+            operation,
+            targets,
+            self.wait_for_locks,
+            &self.cancellation,
+            &self.progress,
+        )
+        .await
+    }
+
+    /// Runs `operation` while holding the locks, releasing them whether it succeeds or not.
+    async fn locked<T>(
+        &self,
+        operation: &str,
+        targets: Vec<(LockTarget, LockMode)>,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let lock = self.lock(operation, targets).await?;
+        let result = future.await;
+
+        lock.release(self.backend.as_ref()).await;
+
+        result
+    }
+
+    fn runner_options(&self) -> anyhow::Result<RunnerOptions> {
+        let config = self.load_config()?;
+        let binary = self.runner_binary(&config)?;
+
+        let default_image = match binary {
+            Some(_) => BASE_IMAGE.to_string(),
+            None => RUNNER_IMAGE.to_string(),
+        };
+
+        Ok(RunnerOptions {
+            image: self.image.clone().or(config.image).unwrap_or(default_image),
+            image_archive: self.image_archive.clone().or(config.image_archive),
+            binary,
+            capabilities: self.capabilities.clone(),
+            cancellation: self.cancellation.clone(),
+        })
+    }
+
+    /// Reads the given, configured or bundled runner binary once instead of for every runner.
+    fn runner_binary(&self, config: &Config) -> anyhow::Result<Option<RunnerBinary>> {
+        if let Some(binary) = self.resolved_runner_binary.get() {
+            return Ok(binary.clone());
+        }
+
+        let binary = match self
+            .runner_binary
+            .as_ref()
+            .or(config.runner_binary.as_ref())
+        {
+            Some(path) => Some(RunnerBinary::read(path)?),
+            None => find_bundled_runner_binary(),
+        };
+
+        Ok(self.resolved_runner_binary.get_or_init(|| binary).clone())
+    }
+
+    fn storage(&self) -> Result<SnapshotStorage> {
+        let config = self.load_config()?;
+        let prefix = self
+            .snapshot_prefix
+            .clone()
+            .or(config.snapshot_prefix)
+            .unwrap_or(SNAPSHOT_PREFIX.to_string());
+
+        let mut storage = SnapshotStorage::new(&prefix)?;
+
+        storage.driver = self.snapshot_driver.clone().or(config.snapshot_driver);
+        storage.driver_opts = config.snapshot_driver_opts;
+        storage
+            .driver_opts
+            .extend(self.snapshot_driver_opts.clone());
+
+        if let Some(snapshot_dir) = self.snapshot_dir.clone().or(config.snapshot_dir) {
+            if storage.driver.is_some() || !storage.driver_opts.is_empty() {
+                return Err(Error::InvalidArgument(
+                    "A snapshot driver can't be used with a snapshot directory".to_string(),
+                ));
+            }
+
+            // The runner bind mounts the directory on the engine's host, while listing and
+            // labels are read here.
+            if !self.backend.is_local() {
+                return Err(Error::InvalidArgument(format!(
+                    "The snapshot directory {} needs a container engine on this machine",
+                    snapshot_dir.display()
+                )));
+            }
+
+            storage.directory = Some(SnapshotDirectory::new(&snapshot_dir)?);
+        }
+
+        Ok(storage)
+    }
+
+    fn load_config(&self) -> anyhow::Result<Config> {
+        Config::load_from(&self.config_path)
+    }
+
+    fn update_config<T>(&self, change: impl FnOnce(&mut Config) -> T) -> anyhow::Result<T> {
+        Config::update_at(&self.config_path, change)
+    }
+
+    /// Creates and replacements on this host that are still running, or that were interrupted.
+    fn load_journal(&self) -> anyhow::Result<Journal> {
+        Journal::load_from(&Journal::path(&self.config_path))
+    }
+
+    fn update_journal<T>(&self, change: impl FnOnce(&mut Journal) -> T) -> anyhow::Result<T> {
+        Journal::update_at(&Journal::path(&self.config_path), change)
+    }
+
+    fn transfer_pin(&self, from_volume_name: &str, to_volume_name: &str) -> anyhow::Result<()> {
+        self.update_config(|config| {
+            if config.unpin(from_volume_name) {
+                config.pin(to_volume_name);
+            }
+        })
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use crate::library::{
+    DiskSpace,
+    backend::MountSource,
+    capabilities::{FEATURE_FREE_SPACE, RunnerCommand},
+    client::VsnapClient,
+    constant::VERSION,
+    docker::{
+        RunnerOptions, find_leftover_runners, find_snapshot_volume_names, runner_capabilities,
+        volume_free_space,
+    },
+    doctor::{Check, DoctorReport, check_disk_space, engine_unreachable, is_older_api_version},
+};
+
+impl VsnapClient {
+    /// Checks the engine, the runner and the snapshots, reporting problems instead of failing.
+    pub async fn doctor(&self) -> DoctorReport {
+        let backend = self.backend.as_ref();
+        let mut checks = vec![];
+
+        let engine = match backend.engine_info().await {
+            Ok(engine) => engine,
+            Err(e) => {
+                checks.push(engine_unreachable(format!("{:#}", e)));
+
+                return DoctorReport::new(checks);
+            }
+        };
+
+        let engine_name = if engine.podman { "Podman" } else { "Docker" };
+        let api_version = engine.api_version.clone().unwrap_or_default();
+
+        checks.push(Check::pass(
+            "engine",
+            format!(
+                "{} {}, API {} on {}/{}",
+                engine_name,
+                engine.version.as_deref().unwrap_or("unknown"),
+                api_version,
+                engine.os.as_deref().unwrap_or("unknown"),
+                engine.arch.as_deref().unwrap_or("unknown"),
+            ),
+        ));
+
+        checks.push(
+            match is_older_api_version(&api_version, &engine.client_api_version) {
+                true => Check::warn(
+                    "api_version",
+                    format!(
+                        "The engine speaks API {}, vsnap uses {}",
+                        api_version, engine.client_api_version
+                    ),
+                    "Upgrade the container engine if requests fail",
+                ),
+                false => Check::pass(
+                    "api_version",
+                    format!("API {} is supported", engine.client_api_version),
+                ),
+            },
+        );
+
+        checks.push(
+            match engine.volume_drivers.iter().any(|driver| driver == "local") {
+                true => Check::pass(
+                    "volume_driver",
+                    format!("Volume drivers: {}", engine.volume_drivers.join(", ")),
+                ),
+                false => Check::warn(
+                    "volume_driver",
+                    format!(
+                        "No local volume driver, only: {}",
+                        engine.volume_drivers.join(", ")
+                    ),
+                    "Snapshots are created with the engine's default driver, make sure it persists data",
+                ),
+            },
+        );
+
+        match self.runner_options() {
+            Err(e) => checks.push(Check::fail(
+                "runner_image",
+                format!("{:#}", e),
+                "Fix the runner settings in the config file or on the command line",
+            )),
+            Ok(runner) => match backend.image_exists(&runner.image).await {
+                true => {
+                    checks.push(Check::pass(
+                        "runner_image",
+                        format!("{} is present", runner.image),
+                    ));
+                    checks.extend(self.doctor_runner_checks(&runner).await);
+                }
+                false => checks.push(Check::warn(
+                    "runner_image",
+                    format!("{} is missing, runner checks were skipped", runner.image),
+                    "Run `vsnap image pull`, or `vsnap image load` on offline machines",
+                )),
+            },
+        }
+
+        match find_leftover_runners(backend).await {
+            Ok(containers) => {
+                let orphaned = containers
+                    .into_iter()
+                    .map(|container| container.name)
+                    .collect::<Vec<_>>();
+
+                checks.push(match orphaned.is_empty() {
+                    true => Check::pass("orphaned_containers", "No leftover runner containers"),
+                    false => Check::warn(
+                        "orphaned_containers",
+                        format!("Leftover runner containers: {}", orphaned.join(", ")),
+                        "Run `vsnap gc` to remove them",
+                    ),
+                });
+            }
+            Err(e) => checks.push(Check::fail(
+                "orphaned_containers",
+                format!("{:#}", e),
+                "Check that the engine allows listing containers",
+            )),
+        }
+
+        DoctorReport::new(checks)
+    }
+
+    async fn doctor_runner_checks(&self, runner: &RunnerOptions) -> Vec<Check> {
+        let backend = self.backend.as_ref();
+        let mut checks = vec![];
+
+        let capabilities = match runner_capabilities(backend, runner, &self.progress).await {
+            Ok(capabilities) => capabilities,
+            Err(e) => {
+                checks.push(Check::fail(
+                    "runner_version",
+                    format!("{:#}", e),
+                    "Run `vsnap image pull` or use a working runner image with --image",
+                ));
+
+                return checks;
+            }
+        };
+
+        let runner_version = capabilities
+            .version
+            .clone()
+            .unwrap_or("unknown".to_string());
+
+        checks.push(
+            match capabilities.check(RunnerCommand::Snapshot { compress: true }) {
+                Err(message) => Check::fail(
+                    "runner_version",
+                    message,
+                    format!(
+                        "Use the runner image matching vsnap {} with --image or in the config",
+                        VERSION.as_str()
+                    ),
+                ),
+                Ok(()) if runner_version != VERSION.as_str() => Check::warn(
+                    "runner_version",
+                    format!(
+                        "Runner {} differs from vsnap {}",
+                        runner_version,
+                        VERSION.as_str()
+                    ),
+                    "Run `vsnap image pull` to get the matching runner",
+                ),
+                Ok(()) => Check::pass(
+                    "runner_version",
+                    format!(
+                        "Runner {} speaks protocol {}",
+                        runner_version, capabilities.protocol_version
+                    ),
+                ),
+            },
+        );
+
+        checks.push(match capabilities.has_feature(FEATURE_FREE_SPACE) {
+            true => match self.probe_free_space(runner).await {
+                Ok(space) => check_disk_space(space),
+                Err(e) => Check::warn(
+                    "disk_space",
+                    format!("{:#}", e),
+                    "Check free space under the engine's data root",
+                ),
+            },
+            false => Check::warn(
+                "disk_space",
+                "The runner can't report free space",
+                "Run `vsnap image pull` to get a newer runner",
+            ),
+        });
+
+        let storage = match self.storage() {
+            Ok(storage) => storage,
+            Err(e) => {
+                checks.push(Check::fail(
+                    "snapshots",
+                    e.to_string(),
+                    "Fix the snapshot settings in the config file",
+                ));
+
+                return checks;
+            }
+        };
+
+        let volume_names = match find_snapshot_volume_names(backend, &storage).await {
+            Ok(volume_names) => volume_names,
+            Err(e) => {
+                checks.push(Check::fail(
+                    "snapshots",
+                    format!("{:#}", e),
+                    "Check that the engine allows listing volumes",
+                ));
+
+                return checks;
+            }
+        };
+
+        let mut unreadable = vec![];
+
+        for volume_name in volume_names {
+            let defect = match self.find_snapshot_defect(&volume_name).await {
+                Ok(defect) => defect,
+                Err(e) => Some(format!("{:#}", e)),
+            };
+
+            if let Some(defect) = defect {
+                unreadable.push(format!(
+                    "{} ({})",
+                    storage.snapshot_name(&volume_name),
+                    defect
+                ));
+            }
+        }
+
+        checks.push(match unreadable.is_empty() {
+            true => Check::pass("snapshots", "All snapshots are readable"),
+            false => Check::warn(
+                "snapshots",
+                format!("Unreadable snapshots: {}", unreadable.join(", ")),
+                "Run `vsnap gc` to remove broken snapshots",
+            ),
+        });
+
+        checks
+    }
+
+    /// Free space where snapshots are stored, measured on a throwaway volume or on the snapshot
+    /// directory.
+    async fn probe_free_space(&self, runner: &RunnerOptions) -> anyhow::Result<DiskSpace> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+
+        if let Some(directory) = &storage.directory {
+            std::fs::create_dir_all(directory.root())?;
+
+            return volume_free_space(
+                backend,
+                runner,
+                MountSource::Directory(directory.root().to_path_buf()),
+                &self.progress,
+            )
+            .await;
+        }
+
+        let volume_name = format!("vsnap-doctor-{}", chrono::Utc::now().timestamp());
+
+        backend
+            .create_volume(
+                &volume_name,
+                storage.volume_spec(&volume_name, HashMap::new()),
+            )
+            .await?;
+
+        let space = volume_free_space(
+            backend,
+            runner,
+            MountSource::Volume(volume_name.clone()),
+            &self.progress,
+        )
+        .await;
+
+        backend.remove_volume(&volume_name).await.ok();
+
+        space
+    }
+}
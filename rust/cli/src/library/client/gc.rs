@@ -0,0 +1,244 @@
+use crate::library::{
+    client::{Result, VsnapClient},
+    constant::LOCK_PREFIX,
+    docker::{
+        drop_snapshot_volume, drop_volume, find_leftover_runners, find_snapshot_volume_names,
+        inspect_snapshot, verify_volume_not_in_use, volume_exists,
+    },
+    gc::{Garbage, GarbageKind, GcResult},
+    listing::format_age,
+    lock::{LockOwner, LockTarget, find_live_lock},
+};
+
+impl VsnapClient {
+    /// Finds runner containers, snapshots, restored volumes and locks left behind by interrupted
+    /// operations. Anything still in use by a running container or locked is skipped.
+    pub async fn find_garbage(&self) -> Result<Vec<Garbage>> {
+        let backend = self.backend.as_ref();
+        let config = self.load_config()?;
+        let journal = self.load_journal()?;
+        let storage = self.storage()?;
+        let mut garbage = vec![];
+
+        for container in find_leftover_runners(backend).await? {
+            let state = container.state.unwrap_or_default();
+
+            garbage.push(Garbage {
+                kind: GarbageKind::Container,
+                name: container.name,
+                reason: format!("runner container was left behind ({})", state),
+            });
+        }
+
+        for volume in backend.list_volumes().await? {
+            if !volume.name.starts_with(LOCK_PREFIX) {
+                continue;
+            }
+
+            let owner = LockOwner::from_labels(&volume.labels);
+
+            if owner.is_stale(chrono::Utc::now().timestamp()) {
+                garbage.push(Garbage {
+                    kind: GarbageKind::Lock,
+                    name: volume.name,
+                    reason: format!("lock held by {} was never released", owner),
+                });
+            }
+        }
+
+        for restore in &journal.restores {
+            if !volume_exists(backend, &restore.volume).await
+                || find_live_lock(backend, &LockTarget::Volume(restore.volume.clone()))
+                    .await?
+                    .is_some()
+                || config.is_protected(&restore.volume)
+                || verify_volume_not_in_use(backend, &restore.volume)
+                    .await
+                    .is_err()
+            {
+                continue;
+            }
+
+            let operation = match &restore.source_volume {
+                Some(source_volume) => format!("clone of volume {}", source_volume),
+                None => format!("restore from snapshot {}", restore.snapshot),
+            };
+
+            garbage.push(Garbage {
+                kind: GarbageKind::RestoredVolume,
+                name: restore.volume.clone(),
+                reason: format!(
+                    "{} started {} and never finished",
+                    operation,
+                    format_age(chrono::Utc::now().timestamp() - restore.started_at)
+                ),
+            });
+        }
+
+        let snapshot_volume_names = find_snapshot_volume_names(backend, &storage).await?;
+        let incomplete = journal.incomplete_snapshots();
+
+        for volume_name in &snapshot_volume_names {
+            let lock = storage.snapshot_lock(&storage.snapshot_name(volume_name));
+
+            if find_live_lock(backend, &lock).await?.is_some() {
+                continue;
+            }
+
+            let reason = match journal
+                .creates
+                .iter()
+                .find(|create| create.volume == *volume_name)
+            {
+                Some(create) => Some(format!(
+                    "create started {} and never finished",
+                    format_age(chrono::Utc::now().timestamp() - create.started_at)
+                )),
+                None => self.find_snapshot_defect(volume_name).await?,
+            };
+
+            if let Some(reason) = reason {
+                garbage.push(Garbage {
+                    kind: GarbageKind::Snapshot,
+                    name: volume_name.clone(),
+                    reason,
+                });
+            }
+        }
+
+        // Snapshots an interrupted `create --force` completed the replacement of, but did not
+        // get to drop.
+        for replace in &journal.replaces {
+            let lock = storage.snapshot_lock(&storage.snapshot_name(&replace.volume));
+
+            if !snapshot_volume_names.contains(&replace.volume)
+                || !snapshot_volume_names.contains(&replace.replacement)
+                || incomplete.contains(&replace.replacement)
+                || find_live_lock(backend, &lock).await?.is_some()
+            {
+                continue;
+            }
+
+            garbage.push(Garbage {
+                kind: GarbageKind::ReplacedSnapshot,
+                name: replace.volume.clone(),
+                reason: format!(
+                    "replaced by {}, but the replacement was interrupted",
+                    replace.replacement
+                ),
+            });
+        }
+
+        Ok(garbage)
+    }
+
+    /// Removes what `find_garbage` found, runner containers first so that they no longer hold
+    /// on to the volumes.
+    pub async fn collect_garbage(&self, garbage: Vec<Garbage>) -> Result<GcResult> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+        let mut removed = vec![];
+
+        let (containers, volumes): (Vec<_>, Vec<_>) = garbage
+            .into_iter()
+            .partition(|item| item.kind == GarbageKind::Container);
+
+        for item in containers {
+            backend.remove_container(&item.name).await?;
+            removed.push(item);
+        }
+
+        for item in volumes {
+            match item.kind {
+                GarbageKind::Snapshot | GarbageKind::ReplacedSnapshot => {
+                    drop_snapshot_volume(backend, &storage, &item.name).await?
+                }
+                _ => drop_volume(backend, &item.name).await?,
+            }
+
+            match item.kind {
+                GarbageKind::Snapshot => {
+                    self.update_config(|config| config.unpin(&item.name))?;
+                    self.update_journal(|journal| journal.finish_create(&item.name))?;
+                }
+                GarbageKind::ReplacedSnapshot => {
+                    if let Some(replacement) = self.load_journal()?.replacement(&item.name) {
+                        self.transfer_pin(&item.name, replacement)?;
+                    }
+
+                    self.update_journal(|journal| journal.finish_replace(&item.name))?;
+                }
+                GarbageKind::RestoredVolume => {
+                    self.update_journal(|journal| journal.finish_restore(&item.name))?;
+                }
+                _ => {}
+            }
+
+            removed.push(item);
+        }
+
+        // Restores and creates whose volume is gone don't need to be tracked anymore.
+        let snapshot_volume_names = find_snapshot_volume_names(backend, &storage).await?;
+        let mut missing_volumes = vec![];
+
+        for restore in self.load_journal()?.restores {
+            if !volume_exists(backend, &restore.volume).await {
+                missing_volumes.push(restore.volume);
+            }
+        }
+
+        self.update_journal(|journal| {
+            journal
+                .restores
+                .retain(|restore| !missing_volumes.contains(&restore.volume));
+
+            journal
+                .creates
+                .retain(|create| snapshot_volume_names.contains(&create.volume));
+
+            // Either the replaced snapshot or its rolled back replacement is gone.
+            journal.replaces.retain(|replace| {
+                snapshot_volume_names.contains(&replace.volume)
+                    && snapshot_volume_names.contains(&replace.replacement)
+            });
+        })?;
+
+        Ok(GcResult {
+            removed,
+            kept: vec![],
+        })
+    }
+
+    /// Why a snapshot volume is unusable, if it is. In-use volumes may still be written to and
+    /// are never reported.
+    pub(super) async fn find_snapshot_defect(
+        &self,
+        volume_name: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let backend = self.backend.as_ref();
+
+        if verify_volume_not_in_use(backend, volume_name)
+            .await
+            .is_err()
+        {
+            return Ok(None);
+        }
+
+        let inspection = inspect_snapshot(
+            backend,
+            &self.runner_options()?,
+            self.storage()?.mount_source(volume_name),
+            false,
+            &self.progress,
+        )
+        .await?;
+
+        Ok(match (&inspection.archive, &inspection.metadata) {
+            (None, _) => Some("snapshot has no archive".to_string()),
+            (Some(_), None) => {
+                Some("snapshot has no metadata.json, it was not completely written".to_string())
+            }
+            _ => None,
+        })
+    }
+}
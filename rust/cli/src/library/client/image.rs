@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use crate::library::{
+    client::{ImageResult, Result, VsnapClient},
+    constant::{DOCKERFILE, VERSION},
+    docker::{load_image, pull_image},
+    error::Error,
+    progress::ProgressEvent,
+};
+
+impl VsnapClient {
+    /// Pulls the runner image.
+    pub async fn pull_image(&self) -> Result<ImageResult> {
+        let runner = self.runner_options()?;
+
+        pull_image(self.backend.as_ref(), &runner.image, &self.progress).await?;
+
+        Ok(ImageResult {
+            image: runner.image,
+        })
+    }
+
+    /// Loads the runner image from a `docker save` archive.
+    pub async fn load_image(&self, archive: &Path) -> Result<ImageResult> {
+        let runner = self.runner_options()?;
+
+        load_image(
+            self.backend.as_ref(),
+            &runner.image,
+            archive,
+            &self.progress,
+        )
+        .await?;
+
+        Ok(ImageResult {
+            image: runner.image,
+        })
+    }
+
+    /// Builds the runner image from a source checkout containing the dockerfile. The sources
+    /// are not part of the vsnap crate, so the checkout has to be made first.
+    pub async fn build_image(&self, source_dir: &Path) -> Result<ImageResult> {
+        let runner = self.runner_options()?;
+
+        if !source_dir.join(DOCKERFILE).is_file() {
+            return Err(Error::InvalidArgument(format!(
+                "{} does not contain a {}, expected a checkout of https://github.com/fominv/vsnap \
+                 at version {}",
+                source_dir.display(),
+                DOCKERFILE,
+                VERSION.as_str()
+            )));
+        }
+
+        let context = build_context(source_dir)?;
+
+        (self.progress)(ProgressEvent::BuildingImage {
+            image: runner.image.clone(),
+        });
+
+        let result = self
+            .backend
+            .build_image(context, DOCKERFILE, &runner.image)
+            .await;
+
+        (self.progress)(ProgressEvent::Finished);
+        result?;
+
+        Ok(ImageResult {
+            image: runner.image,
+        })
+    }
+}
+
+/// Tars the build context, leaving out build output and version control.
+fn build_context(source_dir: &Path) -> anyhow::Result<Vec<u8>> {
+    fn append_dir(
+        builder: &mut tar::Builder<Vec<u8>>,
+        root: &Path,
+        dir: &Path,
+    ) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path
+                .file_name()
+                .is_some_and(|name| name == "target" || name == ".git")
+            {
+                continue;
+            }
+
+            match path.is_dir() {
+                true => append_dir(builder, root, &path)?,
+                false => builder.append_path_with_name(&path, path.strip_prefix(root)?)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut builder = tar::Builder::new(vec![]);
+
+    append_dir(&mut builder, source_dir, source_dir)?;
+
+    Ok(builder.into_inner()?)
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::library::{
+    client::{
+        CloneOptions, CloneResult, ExistingVolume, RestoreCopiesOptions, RestoreCopiesResult,
+        RestoreOptions, RestoreResult, Result, VsnapClient,
+    },
+    docker::{
+        clone_volume, create_volume, drop_snapshot_volume, drop_volume, get_snapshot_volume_labels,
+        get_snapshot_volume_name_by_snapshot_name, restore_snapshot_many, verify_restore_fits,
+        verify_volume_exists, verify_volume_not_in_use, volume_exists,
+    },
+    error::Error,
+    lock::{LockMode, LockTarget},
+    pattern::{TEMPLATE_INDEX, expand_template, matches_template},
+    volume::{VolumeOverrides, recorded_source_spec},
+};
+
+impl VsnapClient {
+    pub async fn restore(&self, options: RestoreOptions) -> Result<RestoreResult> {
+        let targets = vec![
+            (
+                self.storage()?.snapshot_lock(&options.snapshot),
+                snapshot_lock_mode(options.drop_snapshot),
+            ),
+            (
+                LockTarget::Volume(options.volume.clone()),
+                LockMode::Exclusive,
+            ),
+        ];
+
+        self.locked("restore", targets, self.restore_locked(options))
+            .await
+    }
+
+    async fn restore_locked(&self, options: RestoreOptions) -> Result<RestoreResult> {
+        let replaced_volumes = self
+            .restore_volumes(
+                &options.snapshot,
+                std::slice::from_ref(&options.volume),
+                options.existing_volume,
+                options.drop_snapshot,
+                options.ignore_space,
+                &options.overrides,
+            )
+            .await?;
+
+        Ok(RestoreResult {
+            snapshot: options.snapshot,
+            volume_name: options.volume,
+            replaced_volume: !replaced_volumes.is_empty(),
+            dropped_snapshot: options.drop_snapshot,
+        })
+    }
+
+    /// Restores a snapshot into `count` volumes named after a template, e.g. one per test
+    /// worker. The runner reads the archive once for all of them.
+    pub async fn restore_copies(
+        &self,
+        options: RestoreCopiesOptions,
+    ) -> Result<RestoreCopiesResult> {
+        let volume_names = expand_template(&options.name_template, options.start, options.count)?;
+
+        let mut targets = vec![(
+            self.storage()?.snapshot_lock(&options.snapshot),
+            snapshot_lock_mode(options.drop_snapshot),
+        )];
+        targets.extend(
+            volume_names
+                .iter()
+                .map(|volume_name| (LockTarget::Volume(volume_name.clone()), LockMode::Exclusive)),
+        );
+
+        let replaced_volumes = self
+            .locked(
+                "restore",
+                targets,
+                self.restore_volumes(
+                    &options.snapshot,
+                    &volume_names,
+                    options.existing_volume,
+                    options.drop_snapshot,
+                    options.ignore_space,
+                    &options.overrides,
+                ),
+            )
+            .await?;
+
+        Ok(RestoreCopiesResult {
+            snapshot: options.snapshot,
+            volume_names,
+            replaced_volumes,
+            dropped_snapshot: options.drop_snapshot,
+        })
+    }
+
+    /// Restores into every volume with one runner and returns the volumes that were replaced.
+    /// Nothing is touched unless every volume can be restored to.
+    async fn restore_volumes(
+        &self,
+        snapshot_name: &str,
+        restore_volume_names: &[String],
+        existing_volume: ExistingVolume,
+        drop_snapshot: bool,
+        ignore_space: bool,
+        overrides: &VolumeOverrides,
+    ) -> Result<Vec<String>> {
+        let backend = self.backend.as_ref();
+        let config = self.load_config()?;
+        let storage = self.storage()?;
+
+        for restore_volume_name in restore_volume_names {
+            storage.verify_not_reserved(restore_volume_name)?;
+        }
+
+        let snapshot_volume_name = get_snapshot_volume_name_by_snapshot_name(
+            backend,
+            &storage,
+            snapshot_name,
+            &self.load_journal()?,
+        )
+        .await?;
+
+        if drop_snapshot && config.is_pinned(&snapshot_volume_name) {
+            return Err(Error::SnapshotPinned {
+                snapshot: snapshot_name.to_string(),
+                hint: format!(
+                    "unpin it with vsnap unpin {} first or restore without --drop",
+                    snapshot_name
+                ),
+            });
+        }
+
+        let recorded_spec = recorded_source_spec(
+            &get_snapshot_volume_labels(backend, &storage, &snapshot_volume_name).await?,
+        );
+
+        overrides.verify_device(
+            snapshot_name,
+            &recorded_spec,
+            restore_volume_names,
+            &backend.list_volumes().await?,
+        )?;
+
+        let volume_spec = overrides.apply(recorded_spec);
+
+        let mut existing_volume_names = vec![];
+
+        for restore_volume_name in restore_volume_names {
+            if volume_exists(backend, restore_volume_name).await {
+                if config.is_protected(restore_volume_name) {
+                    return Err(Error::VolumeProtected(restore_volume_name.clone()));
+                }
+
+                if existing_volume == ExistingVolume::Fail {
+                    return Err(Error::VolumeExists(restore_volume_name.clone()));
+                }
+
+                existing_volume_names.push(restore_volume_name.clone());
+            }
+        }
+
+        let (replaced_volumes, created_volumes) = match existing_volume {
+            ExistingVolume::Merge => (
+                vec![],
+                restore_volume_names
+                    .iter()
+                    .filter(|volume_name| !existing_volume_names.contains(volume_name))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            ),
+            _ => (existing_volume_names, restore_volume_names.to_vec()),
+        };
+
+        // Dropped volumes are gone for good, so the runner and the disk are checked first.
+        if !replaced_volumes.is_empty() {
+            verify_restore_fits(
+                backend,
+                &self.runner_options()?,
+                storage.mount_source(&snapshot_volume_name),
+                &replaced_volumes,
+                restore_volume_names.len(),
+                ignore_space,
+                &self.progress,
+            )
+            .await?;
+        }
+
+        for volume_name in &replaced_volumes {
+            drop_volume(backend, volume_name).await?;
+        }
+
+        for volume_name in &created_volumes {
+            self.update_journal(|journal| journal.start_restore(volume_name, snapshot_name))?;
+            backend
+                .create_volume(volume_name, volume_spec.clone())
+                .await?;
+        }
+
+        if let Err(e) = restore_snapshot_many(
+            backend,
+            &self.runner_options()?,
+            storage.mount_source(&snapshot_volume_name),
+            restore_volume_names,
+            ignore_space,
+            &self.progress,
+        )
+        .await
+        {
+            // Merge targets held data before, so they are left as is.
+            for volume_name in &created_volumes {
+                if drop_volume(backend, volume_name).await.is_ok() {
+                    self.update_journal(|journal| journal.finish_restore(volume_name))?;
+                }
+            }
+
+            return Err(e.into());
+        }
+
+        for volume_name in &created_volumes {
+            self.update_journal(|journal| journal.finish_restore(volume_name))?;
+        }
+
+        if drop_snapshot {
+            drop_snapshot_volume(backend, &storage, &snapshot_volume_name).await?;
+        }
+
+        Ok(replaced_volumes)
+    }
+
+    /// Copies a volume into a new volume directly, without taking a snapshot in between.
+    pub async fn clone_volume(&self, options: CloneOptions) -> Result<CloneResult> {
+        let targets = vec![
+            (
+                LockTarget::Volume(options.source_volume.clone()),
+                LockMode::Shared,
+            ),
+            (
+                LockTarget::Volume(options.volume.clone()),
+                LockMode::Exclusive,
+            ),
+        ];
+
+        self.locked("clone", targets, self.clone_locked(options))
+            .await
+    }
+
+    async fn clone_locked(&self, options: CloneOptions) -> Result<CloneResult> {
+        let backend = self.backend.as_ref();
+        let CloneOptions {
+            source_volume,
+            volume,
+            ignore_space,
+        } = options;
+
+        self.storage()?.verify_not_reserved(&volume)?;
+
+        verify_volume_not_in_use(backend, &source_volume).await?;
+        verify_volume_exists(backend, &source_volume).await?;
+
+        if volume_exists(backend, &volume).await {
+            return Err(match self.load_config()?.is_protected(&volume) {
+                true => Error::VolumeProtected(volume),
+                false => Error::VolumeExists(volume),
+            });
+        }
+
+        self.update_journal(|journal| journal.start_clone(&volume, &source_volume))?;
+        create_volume(backend, &volume, HashMap::new()).await?;
+
+        if let Err(e) = clone_volume(
+            backend,
+            &self.runner_options()?,
+            &source_volume,
+            &volume,
+            ignore_space,
+            &self.progress,
+        )
+        .await
+        {
+            if drop_volume(backend, &volume).await.is_ok() {
+                self.update_journal(|journal| journal.finish_restore(&volume))?;
+            }
+
+            return Err(e.into());
+        }
+
+        self.update_journal(|journal| journal.finish_restore(&volume))?;
+
+        Ok(CloneResult {
+            source_volume,
+            volume_name: volume,
+        })
+    }
+
+    /// Finds the volumes a template expands to, for any index.
+    pub async fn find_copies(&self, name_template: &str) -> Result<Vec<String>> {
+        if !name_template.contains(TEMPLATE_INDEX) {
+            return Err(Error::InvalidArgument(format!(
+                "Name template {} needs a {} placeholder",
+                name_template, TEMPLATE_INDEX
+            )));
+        }
+
+        let storage = self.storage()?;
+
+        Ok(self
+            .backend
+            .list_volumes()
+            .await?
+            .into_iter()
+            .map(|volume| volume.name)
+            .filter(|volume_name| {
+                matches_template(name_template, volume_name) && !storage.is_reserved(volume_name)
+            })
+            .sorted()
+            .collect())
+    }
+
+    /// Drops the volumes a template expands to, e.g. after `restore_copies`.
+    pub async fn drop_copies(&self, name_template: &str) -> Result<Vec<String>> {
+        let backend = self.backend.as_ref();
+        let config = self.load_config()?;
+        let volume_names = self.find_copies(name_template).await?;
+
+        if let Some(volume_name) = volume_names
+            .iter()
+            .find(|volume_name| config.is_protected(volume_name))
+        {
+            return Err(Error::VolumeProtected(volume_name.clone()));
+        }
+
+        for volume_name in &volume_names {
+            self.locked(
+                "drop",
+                vec![(LockTarget::Volume(volume_name.clone()), LockMode::Exclusive)],
+                async { Ok(drop_volume(backend, volume_name).await?) },
+            )
+            .await?;
+
+            self.update_journal(|journal| journal.finish_restore(volume_name))?;
+        }
+
+        Ok(volume_names)
+    }
+}
+
+/// Restores only read the snapshot, unless they drop it afterwards.
+fn snapshot_lock_mode(drop_snapshot: bool) -> LockMode {
+    match drop_snapshot {
+        true => LockMode::Exclusive,
+        false => LockMode::Shared,
+    }
+}
@@ -0,0 +1,334 @@
+use std::{collections::HashMap, future::Future, sync::Arc, time::Instant};
+
+use anyhow::anyhow;
+use futures::StreamExt;
+use tokio::sync::mpsc;
+
+use crate::library::{
+    backend::MountSource,
+    client::{PullOptions, PullResult, PushOptions, PushResult, Result, VsnapClient},
+    connection::ConnectionOptions,
+    constant::{LABEL_COMPRESSED, LABEL_REMOTE, LABEL_SOURCE, LABEL_VERSION, VERSION},
+    docker::{
+        RunnerOptions, create_snapshot_volume, drop_snapshot_volume, export_snapshot_file,
+        get_snapshot_volume_name_by_snapshot_name, import_snapshot_file, inspect_snapshot,
+        read_snapshot_file, verify_snapshot_does_not_exist,
+    },
+    lock::LockMode,
+    metadata::{SNAPSHOT_METADATA, SnapshotMetadata},
+    progress::ProgressEvent,
+    remote::{
+        RemoteDownload, RemoteSnapshot, RemoteStore, RemoteUpload, RemoteUrl, oci::OciStore,
+        plugin::PluginStore, receiver_stream, s3::S3Store, verify_archive,
+    },
+};
+
+impl VsnapClient {
+    /// Uploads a snapshot to a remote, streaming the archive out of the runner.
+    pub async fn push(&self, options: PushOptions) -> Result<PushResult> {
+        let targets = vec![(
+            self.storage()?.snapshot_lock(&options.snapshot),
+            LockMode::Shared,
+        )];
+
+        self.locked("push", targets, self.push_locked(options))
+            .await
+    }
+
+    async fn push_locked(&self, options: PushOptions) -> Result<PushResult> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+        let runner = self.runner_options()?;
+        let remote = options
+            .remote
+            .parse::<RemoteUrl>()?
+            .with_name(&options.snapshot);
+        let store = self.remote_store(&remote.scheme)?;
+
+        let snapshot_volume_name = get_snapshot_volume_name_by_snapshot_name(
+            backend,
+            &storage,
+            &options.snapshot,
+            &self.load_journal()?,
+        )
+        .await?;
+        let mount_source = storage.mount_source(&snapshot_volume_name);
+
+        let inspection = inspect_snapshot(
+            backend,
+            &runner,
+            mount_source.clone(),
+            false,
+            &self.progress,
+        )
+        .await?;
+
+        let (Some(metadata), Some(archive_name)) = (inspection.metadata, inspection.archive) else {
+            return Err(anyhow!(
+                "Snapshot {} is incomplete and can't be pushed",
+                options.snapshot
+            )
+            .into());
+        };
+
+        let archive_size = inspection.archive_size.unwrap_or_default();
+
+        let metadata_file = read_snapshot_file(
+            backend,
+            &runner,
+            mount_source.clone(),
+            SNAPSHOT_METADATA,
+            &self.progress,
+        )
+        .await?;
+
+        let (sender, receiver) = mpsc::channel(TRANSFER_CHUNKS);
+        let progress = self.progress.clone();
+
+        let archive = verify_archive(
+            receiver_stream(receiver),
+            Some(archive_size),
+            metadata.checksum,
+            move |transferred| {
+                progress(ProgressEvent::Progress {
+                    progress: transferred,
+                    total: archive_size,
+                })
+            },
+        );
+
+        let upload = RemoteUpload {
+            metadata: metadata_file,
+            archive_name: archive_name.clone(),
+            archive_size,
+            archive,
+        };
+
+        (self.progress)(ProgressEvent::Started);
+
+        let (exported, stored) = tokio::join!(
+            timed(export_snapshot_file(
+                backend,
+                &runner,
+                mount_source,
+                &archive_name,
+                sender,
+                &self.progress,
+            )),
+            timed(store.put(&remote.location, upload)),
+        );
+
+        (self.progress)(ProgressEvent::Finished);
+        first_error(exported, stored)?;
+
+        Ok(PushResult {
+            snapshot: options.snapshot,
+            remote: remote.to_string(),
+            size: archive_size,
+        })
+    }
+
+    /// Downloads a snapshot from a remote, streaming the archive into the runner.
+    pub async fn pull(&self, options: PullOptions) -> Result<PullResult> {
+        let targets = vec![(
+            self.storage()?.snapshot_lock(&options.snapshot),
+            LockMode::Exclusive,
+        )];
+
+        self.locked("pull", targets, self.pull_locked(options))
+            .await
+    }
+
+    async fn pull_locked(&self, options: PullOptions) -> Result<PullResult> {
+        let backend = self.backend.as_ref();
+        let storage = self.storage()?;
+        let runner = self.runner_options()?;
+        let remote = options.remote.parse::<RemoteUrl>()?;
+        let store = self.remote_store(&remote.scheme)?;
+
+        verify_snapshot_does_not_exist(backend, &storage, &options.snapshot, &self.load_journal()?)
+            .await?;
+
+        let download = store.get(&remote.location).await?;
+        let metadata: SnapshotMetadata = serde_json::from_slice(&download.metadata)
+            .map_err(|e| anyhow!("Invalid metadata in {}: {}", remote, e))?;
+
+        let timestamp = metadata
+            .created_at
+            .unwrap_or(chrono::Utc::now().timestamp());
+        let snapshot_volume_name = storage.snapshot_volume_name(timestamp, &options.snapshot);
+
+        let mut labels = HashMap::from([
+            (
+                LABEL_COMPRESSED.to_string(),
+                metadata.compression.is_some().to_string(),
+            ),
+            (LABEL_VERSION.to_string(), VERSION.to_string()),
+            (LABEL_REMOTE.to_string(), remote.to_string()),
+        ]);
+
+        if let Some(source_volume) = &metadata.source_volume {
+            labels.insert(LABEL_SOURCE.to_string(), source_volume.clone());
+        }
+
+        create_snapshot_volume(backend, &storage, &snapshot_volume_name, labels).await?;
+
+        if let Err(e) = self
+            .import_download(
+                &runner,
+                storage.mount_source(&snapshot_volume_name),
+                download,
+                &metadata,
+            )
+            .await
+        {
+            drop_snapshot_volume(backend, &storage, &snapshot_volume_name)
+                .await
+                .ok();
+            return Err(e.into());
+        }
+
+        Ok(PullResult {
+            remote: remote.to_string(),
+            snapshot: options.snapshot,
+            volume_name: snapshot_volume_name,
+        })
+    }
+
+    /// Writes the archive and then the metadata of a download into a snapshot, so that an
+    /// interrupted pull never looks complete.
+    async fn import_download(
+        &self,
+        runner: &RunnerOptions,
+        snapshot: MountSource,
+        download: RemoteDownload,
+        metadata: &SnapshotMetadata,
+    ) -> anyhow::Result<()> {
+        let backend = self.backend.as_ref();
+        let total = metadata.archive_size.unwrap_or_default();
+        let progress = self.progress.clone();
+
+        let mut archive = verify_archive(
+            download.archive,
+            metadata.archive_size,
+            metadata.checksum.clone(),
+            move |transferred| {
+                progress(ProgressEvent::Progress {
+                    progress: transferred,
+                    total,
+                })
+            },
+        );
+
+        let (sender, receiver) = mpsc::channel(TRANSFER_CHUNKS);
+
+        let forward = async move {
+            while let Some(chunk) = archive.next().await {
+                sender
+                    .send(chunk?)
+                    .await
+                    .map_err(|_| anyhow!("The runner stopped reading the archive"))?;
+            }
+
+            anyhow::Ok(())
+        };
+
+        (self.progress)(ProgressEvent::Started);
+
+        let (forwarded, imported) = tokio::join!(
+            timed(forward),
+            timed(import_snapshot_file(
+                backend,
+                runner,
+                snapshot.clone(),
+                &download.archive_name,
+                receiver,
+                &self.progress,
+            )),
+        );
+
+        (self.progress)(ProgressEvent::Finished);
+        first_error(forwarded, imported)?;
+
+        let (sender, receiver) = mpsc::channel(1);
+
+        sender.send(download.metadata).await?;
+        drop(sender);
+
+        import_snapshot_file(
+            backend,
+            runner,
+            snapshot,
+            SNAPSHOT_METADATA,
+            receiver,
+            &self.progress,
+        )
+        .await
+    }
+
+    /// Snapshots stored at or below a remote.
+    pub async fn list_remote(&self, remote: &str) -> Result<Vec<RemoteSnapshot>> {
+        let remote = remote.parse::<RemoteUrl>()?;
+        let store = self.remote_store(&remote.scheme)?;
+
+        Ok(store
+            .list(&remote.location)
+            .await?
+            .into_iter()
+            .map(|snapshot| RemoteSnapshot {
+                remote: format!("{}://{}", remote.scheme, snapshot.remote),
+                ..snapshot
+            })
+            .collect())
+    }
+
+    pub async fn delete_remote(&self, remote: &str) -> Result<()> {
+        let remote = remote.parse::<RemoteUrl>()?;
+
+        Ok(self
+            .remote_store(&remote.scheme)?
+            .delete(&remote.location)
+            .await?)
+    }
+
+    fn remote_store(&self, scheme: &str) -> Result<Arc<dyn RemoteStore>> {
+        if let Some(store) = self.remote_stores.get(scheme) {
+            return Ok(store.clone());
+        }
+
+        match scheme {
+            "s3" => Ok(Arc::new(S3Store::from_env()?)),
+            "oci" => Ok(Arc::new(OciStore::from_env(
+                ConnectionOptions::default()
+                    .with_env_defaults()
+                    .docker_config_dir()
+                    .ok(),
+            ))),
+            scheme => Ok(Arc::new(PluginStore::discover(scheme)?)),
+        }
+    }
+}
+
+/// How many chunks of an archive are buffered between the runner and a remote.
+const TRANSFER_CHUNKS: usize = 16;
+
+async fn timed<T>(future: impl Future<Output = anyhow::Result<T>>) -> (anyhow::Result<T>, Instant) {
+    let result = future.await;
+
+    (result, Instant::now())
+}
+
+/// When both ends of a transfer fail, the one failing first has the cause, the other only
+/// noticed the transfer stopping.
+fn first_error(
+    (a, a_finished): (anyhow::Result<()>, Instant),
+    (b, b_finished): (anyhow::Result<()>, Instant),
+) -> anyhow::Result<()> {
+    match (a, b) {
+        (Err(a), Err(b)) => Err(match a_finished <= b_finished {
+            true => a,
+            false => b,
+        }),
+        (a, b) => a.and(b),
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::library::{constant::CONFIG_PATH_ENV, pattern::matches_pattern, state};
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// Volume names or glob patterns that `restore` must never overwrite.
+    pub protected_volumes: Vec<String>,
+
+    /// Snapshot volume names that cannot be dropped without `--force`.
+    pub pinned_snapshots: Vec<String>,
+
+    /// Runner image to use instead of `fominv/vsnap:<version>`, e.g. from a registry mirror.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+
+    /// A `docker save` archive to load the runner image from instead of pulling it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_archive: Option<PathBuf>,
+
+    /// A static runner binary to inject into a stock image, so no vsnap image is needed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runner_binary: Option<PathBuf>,
+
+    /// Prefix of snapshot volume names instead of `vsnap-`, so that projects sharing a host
+    /// don't see each other's snapshots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_prefix: Option<String>,
+
+    /// Volume driver for snapshot volumes instead of the engine's default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_driver: Option<String>,
+
+    /// Driver options for snapshot volumes, e.g. to bind them to a directory on a larger disk.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub snapshot_driver_opts: HashMap<String, String>,
+
+    /// Directory on the engine's host to keep snapshots in as files instead of in volumes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn path() -> anyhow::Result<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_PATH_ENV) {
+            return Ok(PathBuf::from(path));
+        }
+
+        Ok(dirs::config_dir()
+            .ok_or(anyhow!("Failed to determine the config directory"))?
+            .join("vsnap")
+            .join("config.json"))
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        state::load(path, "config")
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        state::save(self, path)
+    }
+
+    /// Changes the config without losing changes other vsnap processes make at the same time.
+    pub fn update<T>(change: impl FnOnce(&mut Config) -> T) -> anyhow::Result<T> {
+        Self::update_at(&Self::path()?, change)
+    }
+
+    pub fn update_at<T>(path: &Path, change: impl FnOnce(&mut Config) -> T) -> anyhow::Result<T> {
+        state::update(path, "config", change)
+    }
+
+    pub fn is_pinned(&self, snapshot_volume_name: &str) -> bool {
+        self.pinned_snapshots
+            .iter()
+            .any(|name| name == snapshot_volume_name)
+    }
+
+    pub fn pin(&mut self, snapshot_volume_name: &str) -> bool {
+        if self.is_pinned(snapshot_volume_name) {
+            return false;
+        }
+
+        self.pinned_snapshots.push(snapshot_volume_name.to_string());
+
+        true
+    }
+
+    pub fn unpin(&mut self, snapshot_volume_name: &str) -> bool {
+        let len = self.pinned_snapshots.len();
+
+        self.pinned_snapshots
+            .retain(|name| name != snapshot_volume_name);
+
+        len != self.pinned_snapshots.len()
+    }
+
+    pub fn is_protected(&self, volume_name: &str) -> bool {
+        self.protected_volumes
+            .iter()
+            .any(|pattern| matches_pattern(pattern, volume_name))
+    }
+
+    pub fn protect(&mut self, pattern: &str) -> bool {
+        if self.protected_volumes.iter().any(|p| p == pattern) {
+            return false;
+        }
+
+        self.protected_volumes.push(pattern.to_string());
+
+        true
+    }
+
+    pub fn unprotect(&mut self, pattern: &str) -> bool {
+        let len = self.protected_volumes.len();
+
+        self.protected_volumes.retain(|p| p != pattern);
+
+        len != self.protected_volumes.len()
+    }
+}
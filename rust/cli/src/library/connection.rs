@@ -0,0 +1,360 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use bollard::{API_DEFAULT_VERSION, Docker};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::library::error::Error;
+
+const TIMEOUT: u64 = 120;
+const DEFAULT_CONTEXT: &str = "default";
+const DEFAULT_REMOTE_SOCKET: &str = "/var/run/docker.sock";
+
+/// How to reach the container engine. Unset options fall back to the docker environment
+/// variables, the current docker context and finally the local socket.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct ConnectionOptions {
+    /// Daemon socket to connect to, e.g. unix:///run/user/1000/podman/podman.sock,
+    /// tcp://build-box:2376 or ssh://user@build-box. Defaults to DOCKER_HOST.
+    #[arg(long, short = 'H', global = true)]
+    pub host: Option<String>,
+
+    /// Docker context to use. Defaults to DOCKER_CONTEXT or the current context.
+    #[arg(long, global = true, conflicts_with = "host")]
+    pub context: Option<String>,
+
+    /// Use TLS and verify the daemon's certificate. Defaults to DOCKER_TLS_VERIFY.
+    #[arg(long, global = true, default_value_t = false)]
+    pub tlsverify: bool,
+
+    /// CA certificate, defaults to ca.pem in DOCKER_CERT_PATH or ~/.docker.
+    #[arg(long, global = true)]
+    pub tlscacert: Option<PathBuf>,
+
+    /// Client certificate, defaults to cert.pem in DOCKER_CERT_PATH or ~/.docker.
+    #[arg(long, global = true)]
+    pub tlscert: Option<PathBuf>,
+
+    /// Client key, defaults to key.pem in DOCKER_CERT_PATH or ~/.docker.
+    #[arg(long, global = true)]
+    pub tlskey: Option<PathBuf>,
+
+    /// Talk to Podman's docker-compatible API. Implied for hosts with podman in their path.
+    #[arg(long, global = true, default_value_t = false)]
+    pub podman: bool,
+
+    /// Directory holding config.json and contexts, DOCKER_CONFIG or ~/.docker by default.
+    #[arg(skip)]
+    pub docker_config: Option<PathBuf>,
+
+    #[arg(skip)]
+    pub cert_path: Option<PathBuf>,
+}
+
+impl ConnectionOptions {
+    /// Fills unset options from DOCKER_HOST, DOCKER_CONTEXT, DOCKER_TLS_VERIFY, DOCKER_CERT_PATH
+    /// and DOCKER_CONFIG.
+    pub fn with_env_defaults(mut self) -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        if self.host.is_none() && self.context.is_none() {
+            self.host = env("DOCKER_HOST");
+            self.context = env("DOCKER_CONTEXT");
+        }
+
+        self.tlsverify |= env("DOCKER_TLS_VERIFY").is_some_and(|value| value != "0");
+        self.cert_path = self
+            .cert_path
+            .or(env("DOCKER_CERT_PATH").map(PathBuf::from));
+        self.docker_config = self
+            .docker_config
+            .or(env("DOCKER_CONFIG").map(PathBuf::from));
+
+        self
+    }
+
+    pub fn docker_config_dir(&self) -> anyhow::Result<PathBuf> {
+        match &self.docker_config {
+            Some(path) => Ok(path.clone()),
+            None => Ok(dirs::home_dir()
+                .ok_or(anyhow!("Failed to determine the home directory"))?
+                .join(".docker")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsFiles {
+    pub ca: PathBuf,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// A resolved daemon address. `host` is `None` for the platform's local socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    pub host: Option<String>,
+    pub tls: Option<TlsFiles>,
+    pub podman: bool,
+}
+
+impl Endpoint {
+    /// Whether the engine runs on this machine, so that paths here are paths on its host.
+    pub fn is_local(&self) -> bool {
+        let Some(host) = &self.host else {
+            return true;
+        };
+
+        match host.split_once("://") {
+            Some(("unix" | "npipe", _)) => true,
+            Some(("tcp" | "http" | "https", address)) => {
+                let host = match address.rsplit_once(':') {
+                    Some((host, _)) => host,
+                    None => address,
+                };
+
+                matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct DockerConfigFile {
+    current_context: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContextMeta {
+    endpoints: ContextEndpoints,
+}
+
+#[derive(Deserialize)]
+struct ContextEndpoints {
+    docker: ContextEndpoint,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContextEndpoint {
+    host: String,
+}
+
+pub fn resolve_endpoint(options: &ConnectionOptions) -> anyhow::Result<Endpoint> {
+    let docker_config_dir = options.docker_config_dir()?;
+
+    let context = match (&options.host, &options.context) {
+        (Some(_), _) => None,
+        (None, Some(context)) => Some(context.clone()),
+        (None, None) => read_current_context(&docker_config_dir)?,
+    };
+
+    let (host, context_tls) = match context.filter(|context| context != DEFAULT_CONTEXT) {
+        Some(context) => resolve_context(&docker_config_dir, &context)?,
+        None => (options.host.clone(), None),
+    };
+
+    let host = match (host, options.podman) {
+        (None, true) => Some(default_podman_host()),
+        (host, _) => host,
+    };
+
+    let tls = match options.tlsverify {
+        true => {
+            let cert_dir = options.cert_path.clone().unwrap_or(docker_config_dir);
+
+            Some(TlsFiles {
+                ca: options.tlscacert.clone().unwrap_or(cert_dir.join("ca.pem")),
+                cert: options.tlscert.clone().unwrap_or(cert_dir.join("cert.pem")),
+                key: options.tlskey.clone().unwrap_or(cert_dir.join("key.pem")),
+            })
+        }
+        false => context_tls,
+    };
+
+    Ok(Endpoint {
+        podman: options.podman || host.as_ref().is_some_and(|host| host.contains("podman")),
+        host,
+        tls,
+    })
+}
+
+fn read_current_context(docker_config_dir: &Path) -> anyhow::Result<Option<String>> {
+    let path = docker_config_dir.join("config.json");
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let config: DockerConfigFile = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+    Ok(config.current_context)
+}
+
+/// Contexts are stored by the docker CLI under the sha256 digest of their name.
+fn resolve_context(
+    docker_config_dir: &Path,
+    context: &str,
+) -> anyhow::Result<(Option<String>, Option<TlsFiles>)> {
+    let digest = format!("{:x}", Sha256::digest(context.as_bytes()));
+    let contexts_dir = docker_config_dir.join("contexts");
+    let meta_path = contexts_dir.join("meta").join(&digest).join("meta.json");
+
+    let meta: ContextMeta = serde_json::from_str(
+        &fs::read_to_string(&meta_path)
+            .map_err(|_| anyhow!("Docker context {} not found", context))?,
+    )
+    .map_err(|e| anyhow!("Failed to parse docker context {}: {}", context, e))?;
+
+    let tls_dir = contexts_dir.join("tls").join(&digest).join("docker");
+
+    let tls = tls_dir.join("ca.pem").exists().then(|| TlsFiles {
+        ca: tls_dir.join("ca.pem"),
+        cert: tls_dir.join("cert.pem"),
+        key: tls_dir.join("key.pem"),
+    });
+
+    Ok((Some(meta.endpoints.docker.host), tls))
+}
+
+fn default_podman_host() -> String {
+    let rootless_socket = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("podman").join("podman.sock"))
+        .filter(|path| path.exists());
+
+    match rootless_socket {
+        Some(path) => format!("unix://{}", path.display()),
+        None => "unix:///run/podman/podman.sock".to_string(),
+    }
+}
+
+/// Keeps an `ssh -L` forward of the remote daemon socket open while the connection is in use.
+pub struct SshTunnel {
+    child: Child,
+    dir: PathBuf,
+}
+
+impl SshTunnel {
+    fn open(host: &str) -> anyhow::Result<(Self, PathBuf)> {
+        let address = host.trim_start_matches("ssh://");
+        let (destination, remote_socket) = match address.find('/') {
+            Some(index) => address.split_at(index),
+            None => (address, DEFAULT_REMOTE_SOCKET),
+        };
+
+        let (destination, port) = match destination.rsplit_once(':') {
+            Some((destination, port)) => (destination, Some(port)),
+            None => (destination, None),
+        };
+
+        let dir = std::env::temp_dir().join(format!("vsnap-ssh-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let local_socket = dir.join("docker.sock");
+
+        let mut command = Command::new("ssh");
+        command
+            .args(["-nNT", "-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "StreamLocalBindUnlink=yes"])
+            .arg("-L")
+            .arg(format!("{}:{}", local_socket.display(), remote_socket))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+
+        if let Some(port) = port {
+            command.args(["-p", port]);
+        }
+
+        let child = command
+            .arg(destination)
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start ssh: {}", e))?;
+
+        let mut tunnel = SshTunnel { child, dir };
+        let started = Instant::now();
+
+        while !local_socket.exists() {
+            if let Some(status) = tunnel.child.try_wait()? {
+                return Err(Error::DockerUnavailable(format!(
+                    "ssh to {} exited with {}",
+                    destination, status
+                ))
+                .into());
+            }
+
+            if started.elapsed() > Duration::from_secs(30) {
+                return Err(Error::DockerUnavailable(format!(
+                    "Timed out connecting to {} over ssh",
+                    destination
+                ))
+                .into());
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        Ok((tunnel, local_socket))
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+pub fn connect(endpoint: &Endpoint) -> anyhow::Result<(Docker, Option<SshTunnel>)> {
+    let host = match &endpoint.host {
+        None => return Ok((Docker::connect_with_local_defaults()?, None)),
+        Some(host) => host.as_str(),
+    };
+
+    let docker = match (
+        host.split_once("://").map(|(scheme, _)| scheme),
+        &endpoint.tls,
+    ) {
+        (Some("ssh"), _) => {
+            let (tunnel, socket) = SshTunnel::open(host)?;
+            let docker = Docker::connect_with_local(
+                &socket.to_string_lossy(),
+                TIMEOUT,
+                API_DEFAULT_VERSION,
+            )?;
+
+            return Ok((docker, Some(tunnel)));
+        }
+        (Some("unix" | "npipe"), _) => {
+            Docker::connect_with_local(host, TIMEOUT, API_DEFAULT_VERSION)?
+        }
+        (Some("tcp" | "https"), Some(tls)) => Docker::connect_with_ssl(
+            host,
+            &tls.key,
+            &tls.cert,
+            &tls.ca,
+            TIMEOUT,
+            API_DEFAULT_VERSION,
+        )?,
+        (Some("tcp" | "http"), None) => {
+            Docker::connect_with_http(host, TIMEOUT, API_DEFAULT_VERSION)?
+        }
+        (Some("https"), None) => {
+            return Err(anyhow!("{} requires --tlsverify and certificates", host));
+        }
+        _ => return Err(anyhow!("Unsupported docker host: {}", host)),
+    };
+
+    Ok((docker, None))
+}
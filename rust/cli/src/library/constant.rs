@@ -1,9 +1,43 @@
 use std::sync::LazyLock;
 
-use regex::Regex;
-
 pub static VERSION: LazyLock<String> = LazyLock::new(|| env!("CARGO_PKG_VERSION").to_string());
 
-pub static SNAPSHOT_PREFIX_REGEX: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^vsnap-(\d{10,})-").expect("Failed to compile snapshot prefix regex")
-});
+pub static RUNNER_REPOSITORY: &str = "fominv/vsnap";
+
+pub static RUNNER_IMAGE: LazyLock<String> =
+    LazyLock::new(|| format!("{}:{}", RUNNER_REPOSITORY, VERSION.as_str()));
+
+/// Stock image the runner binary is injected into when no runner image is used.
+pub static BASE_IMAGE: &str = "busybox:stable";
+
+pub static RUNNER_BINARY_NAME: &str = "vsnap-runner";
+
+/// The dockerfile at the root of a source checkout that builds the runner image.
+pub static DOCKERFILE: &str = "dockerfile";
+
+/// The default prefix of snapshot volumes, see `SnapshotStorage`.
+pub static SNAPSHOT_PREFIX: &str = "vsnap-";
+
+/// Operation locks are empty volumes named after what they lock.
+pub static LOCK_PREFIX: &str = "vsnap-lock-";
+
+pub static CONFIG_PATH_ENV: &str = "VSNAP_CONFIG";
+
+/// Registries that `oci://` remotes reach over plain http, see `OciStore::from_env`.
+pub static INSECURE_REGISTRIES_ENV: &str = "VSNAP_INSECURE_REGISTRIES";
+
+pub static LABEL_SOURCE: &str = "vsnap.source";
+pub static LABEL_SOURCE_DRIVER: &str = "vsnap.source.driver";
+pub static LABEL_SOURCE_DRIVER_OPTS: &str = "vsnap.source.driver_opts";
+pub static LABEL_SOURCE_LABELS: &str = "vsnap.source.labels";
+pub static LABEL_PARENT: &str = "vsnap.parent";
+pub static LABEL_COMPRESSED: &str = "vsnap.compressed";
+pub static LABEL_VERSION: &str = "vsnap.version";
+pub static LABEL_REMOTE: &str = "vsnap.remote";
+pub static LABEL_RUNNER: &str = "vsnap.runner";
+pub static LABEL_LOCK_OWNER: &str = "vsnap.lock.owner";
+pub static LABEL_LOCK_HOST: &str = "vsnap.lock.host";
+pub static LABEL_LOCK_PID: &str = "vsnap.lock.pid";
+pub static LABEL_LOCK_OPERATION: &str = "vsnap.lock.operation";
+pub static LABEL_LOCK_CREATED: &str = "vsnap.lock.created";
+pub static LABEL_LOCK_SHARED: &str = "vsnap.lock.shared";
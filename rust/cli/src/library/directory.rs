@@ -0,0 +1,146 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::library::backend::{MountSource, VolumeInfo, VolumeSize};
+
+/// Snapshots kept as files on the host instead of in volumes. Every snapshot is a directory
+/// below `root` that runners bind mount, with its labels in `<name>.json` next to it.
+///
+/// The runner mounts the directories on the engine's host, so `root` must be a path on the
+/// machine the engine runs on.
+#[derive(Clone, Debug)]
+pub struct SnapshotDirectory {
+    root: PathBuf,
+}
+
+impl SnapshotDirectory {
+    /// `root` may start with `~` for the home directory.
+    pub fn new(root: &Path) -> anyhow::Result<Self> {
+        let root = match root.strip_prefix("~") {
+            Ok(below_home) => dirs::home_dir()
+                .ok_or(anyhow!("Failed to determine the home directory"))?
+                .join(below_home),
+            Err(_) => root.to_path_buf(),
+        };
+
+        Ok(SnapshotDirectory {
+            root: std::path::absolute(root)?,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    pub fn mount_source(&self, name: &str) -> MountSource {
+        MountSource::Directory(self.path(name))
+    }
+
+    fn labels_path(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.json", name))
+    }
+
+    /// Every directory below the root, named and labelled like a volume.
+    pub fn list(&self) -> anyhow::Result<Vec<VolumeInfo>> {
+        if !self.root.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = vec![];
+
+        for entry in fs::read_dir(&self.root)
+            .with_context(|| format!("Failed to read {}", self.root.display()))?
+        {
+            let entry = entry?;
+
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+
+            entries.push(VolumeInfo {
+                labels: self.labels(&name)?,
+                name,
+                driver: "directory".to_string(),
+                driver_opts: HashMap::new(),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).is_dir()
+    }
+
+    pub fn create(&self, name: &str, labels: &HashMap<String, String>) -> anyhow::Result<()> {
+        fs::create_dir_all(self.path(name))
+            .with_context(|| format!("Failed to create {}", self.path(name).display()))?;
+        fs::write(self.labels_path(name), serde_json::to_vec_pretty(labels)?)?;
+
+        Ok(())
+    }
+
+    /// Labels of a snapshot directory. Directories without a labels file have none.
+    pub fn labels(&self, name: &str) -> anyhow::Result<HashMap<String, String>> {
+        match fs::read(self.labels_path(name)) {
+            Ok(content) => Ok(serde_json::from_slice(&content).with_context(|| {
+                format!("Invalid labels in {}", self.labels_path(name).display())
+            })?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn remove(&self, name: &str) -> anyhow::Result<()> {
+        fs::remove_dir_all(self.path(name))
+            .with_context(|| format!("Failed to remove {}", self.path(name).display()))?;
+
+        match fs::remove_file(self.labels_path(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn sizes(&self, names: &[String]) -> anyhow::Result<HashMap<String, VolumeSize>> {
+        names
+            .iter()
+            .map(|name| {
+                let size = match dir_size(&self.path(name)) {
+                    Ok(size) => VolumeSize::Bytes(size as i64),
+                    Err(_) => VolumeSize::Unavailable,
+                };
+
+                Ok((name.clone(), size))
+            })
+            .collect()
+    }
+}
+
+pub fn dir_size(path: &Path) -> anyhow::Result<u64> {
+    let mut size = 0;
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        size += match metadata.is_dir() {
+            true => dir_size(&entry.path())?,
+            false => metadata.len(),
+        };
+    }
+
+    Ok(size)
+}
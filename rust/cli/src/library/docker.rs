@@ -1,386 +1,1006 @@
-use std::{collections::HashMap, str};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
 
 use anyhow::anyhow;
-use bollard::{
-    Docker,
-    container::{
-        Config, CreateContainerOptions, ListContainersOptions, LogOutput, LogsOptions,
-        StartContainerOptions, WaitContainerOptions,
-    },
-    image::CreateImageOptions,
-    secret::{HostConfig, Mount},
-    volume::{CreateVolumeOptions, ListVolumesOptions},
-};
 use chrono::Local;
-use futures::{StreamExt, TryStreamExt};
-use indicatif::ProgressBar;
 use itertools::Itertools;
+use regex::Regex;
+use tokio::sync::mpsc;
 
 use crate::library::{
-    Progress,
-    constant::{SNAPSHOT_PREFIX_REGEX, VERSION},
-    progress::{create_progress_bar, create_spinner},
+    DiskSpace, Progress, RunnerWarning,
+    backend::{
+        Backend, ContainerFile, ContainerInfo, ContainerSpec, MountSource, VolumeInfo, VolumeMount,
+        VolumeSize,
+    },
+    binary::RunnerBinary,
+    cancel::Cancellation,
+    capabilities::{
+        Capabilities, FEATURE_MULTI_RESTORE, FEATURE_SOURCE_NAME, FEATURE_SPACE_CHECK,
+        RunnerCommand,
+    },
+    constant::{
+        LABEL_PARENT, LABEL_RUNNER, RUNNER_BINARY_NAME, RUNNER_IMAGE, RUNNER_REPOSITORY, VERSION,
+    },
+    error::Error,
+    journal::Journal,
+    lock::LockOwner,
+    metadata::{SNAPSHOT_METADATA, SnapshotInspection, SnapshotMetadata},
+    progress::{ProgressEvent, ProgressHandler},
+    storage::SnapshotStorage,
 };
 
-pub fn get_snapshot_volume_name(timestamp: i64, name: &str) -> String {
-    format!("vsnap-{}-{}", timestamp, name)
-}
+/// Runner containers from before they were labeled were named `vsnap-<timestamp>`.
+static LEGACY_RUNNER_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^vsnap-\d{10,}$").expect("Failed to compile legacy runner name regex")
+});
 
-pub async fn verify_volume_not_in_use(docker: &Docker, volume_name: &str) -> anyhow::Result<()> {
-    let containers = docker
-        .list_containers(Some(ListContainersOptions::<&str> {
-            all: true,
-            filters: HashMap::<&str, Vec<&str>>::from([("volume", vec![volume_name])]),
-            ..Default::default()
-        }))
-        .await?;
+pub async fn verify_volume_not_in_use(
+    backend: &dyn Backend,
+    volume_name: &str,
+) -> anyhow::Result<()> {
+    let containers = backend.find_containers_using_volume(volume_name).await?;
 
     let container_names = containers
-        .iter()
-        .filter_map(|container| {
-            if container
+        .into_iter()
+        .filter(|container| {
+            !container
                 .state
                 .as_ref()
-                .map_or(false, |state| state == "exited")
-            {
-                return None;
-            }
-
-            container
-                .names
-                .iter()
-                .flatten()
-                .next()
-                .map(|name| name.strip_prefix("/"))
-                .flatten()
-                .map(|name| name.to_string())
+                .is_some_and(|state| state == "exited")
         })
+        .map(|container| container.name)
         .collect::<Vec<String>>();
 
-    if container_names.len() > 0 {
-        return Err(anyhow!(
-            "Volume is in use by {}",
-            container_names.join(", ")
-        ));
+    if !container_names.is_empty() {
+        return Err(Error::VolumeInUse {
+            volume: volume_name.to_string(),
+            containers: container_names.join(", "),
+        }
+        .into());
     }
 
     Ok(())
 }
 
 pub async fn verify_snapshot_does_not_exist(
-    docker: &Docker,
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
     snapshot_name: &str,
+    journal: &Journal,
 ) -> anyhow::Result<()> {
-    if find_snapshot_volume_name_by_snapshot_name(&docker, snapshot_name)
+    if find_snapshot_volume_name_by_snapshot_name(backend, storage, snapshot_name, journal)
         .await?
         .is_some()
     {
-        return Err(anyhow!("Snapshot already exists: {}", snapshot_name));
+        return Err(Error::SnapshotExists(snapshot_name.to_string()).into());
     }
 
     Ok(())
 }
 
-pub async fn volume_exists(docker: &Docker, volume_name: &str) -> bool {
-    docker.inspect_volume(&volume_name).await.ok().is_some()
+pub async fn volume_exists(backend: &dyn Backend, volume_name: &str) -> bool {
+    backend
+        .inspect_volume(volume_name)
+        .await
+        .ok()
+        .flatten()
+        .is_some()
 }
 
-pub async fn verify_volume_exists(docker: &Docker, volume_name: &str) -> anyhow::Result<()> {
-    if !volume_exists(docker, volume_name).await {
-        return Err(anyhow!("Volume does not exist: {}", volume_name));
+pub async fn verify_volume_exists(backend: &dyn Backend, volume_name: &str) -> anyhow::Result<()> {
+    if !volume_exists(backend, volume_name).await {
+        return Err(Error::VolumeNotFound(volume_name.to_string()).into());
     }
 
     Ok(())
 }
 
-pub async fn create_volume(docker: &Docker, volume_name: &str) -> anyhow::Result<()> {
-    docker
-        .create_volume(CreateVolumeOptions {
-            name: volume_name,
-            ..Default::default()
-        })
-        .await?;
-
-    Ok(())
+pub async fn create_volume(
+    backend: &dyn Backend,
+    volume_name: &str,
+    labels: HashMap<&str, &str>,
+) -> anyhow::Result<()> {
+    backend
+        .create_volume(
+            volume_name,
+            labels
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>()
+                .into(),
+        )
+        .await
 }
 
-pub async fn drop_volume(docker: &Docker, volume_name: &str) -> anyhow::Result<()> {
-    verify_volume_not_in_use(docker, volume_name).await?;
-    docker.remove_volume(volume_name, None).await?;
-
-    Ok(())
+pub async fn get_volume_info(
+    backend: &dyn Backend,
+    volume_name: &str,
+) -> anyhow::Result<VolumeInfo> {
+    Ok(backend
+        .inspect_volume(volume_name)
+        .await?
+        .ok_or(Error::VolumeNotFound(volume_name.to_string()))?)
 }
 
-pub async fn find_snapshot_volume_names(docker: &Docker) -> anyhow::Result<Vec<String>> {
-    let volumes = docker
-        .list_volumes(Some(ListVolumesOptions {
-            filters: HashMap::<&str, Vec<&str>>::from([("name", vec!["vsnap-"])]),
-        }))
-        .await?;
+pub async fn get_volume_labels(
+    backend: &dyn Backend,
+    volume_name: &str,
+) -> anyhow::Result<HashMap<String, String>> {
+    Ok(get_volume_info(backend, volume_name).await?.labels)
+}
 
-    Ok(volumes
-        .volumes
-        .map(|volumes| {
-            volumes
-                .into_iter()
-                .filter(|volume| SNAPSHOT_PREFIX_REGEX.is_match(&volume.name))
-                .map(|v| v.name)
-                .collect::<Vec<String>>()
+pub async fn find_dependent_snapshot_volume_names(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+    snapshot_volume_name: &str,
+) -> anyhow::Result<Vec<String>> {
+    Ok(find_snapshot_volumes(backend, storage)
+        .await?
+        .into_iter()
+        .filter(|volume| {
+            volume
+                .labels
+                .get(LABEL_PARENT)
+                .is_some_and(|parent| parent == snapshot_volume_name)
         })
-        .unwrap_or(vec![]))
+        .map(|volume| volume.name)
+        .collect())
 }
 
-pub enum VolumeSize {
-    Bytes(i64),
-    Unavailable,
+pub async fn drop_volume(backend: &dyn Backend, volume_name: &str) -> anyhow::Result<()> {
+    verify_volume_not_in_use(backend, volume_name).await?;
+    backend.remove_volume(volume_name).await
 }
 
-pub async fn get_volume_sizes_for_volume_names(
-    docker: &Docker,
-    volume_names: &Vec<String>,
-) -> anyhow::Result<HashMap<String, VolumeSize>> {
-    let mut volume_sizes = HashMap::new();
+/// Volumes, or the directories of a snapshot directory, that may hold snapshots.
+async fn list_snapshot_store(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+) -> anyhow::Result<Vec<VolumeInfo>> {
+    match &storage.directory {
+        Some(directory) => directory.list(),
+        None => backend.list_volumes().await,
+    }
+}
 
-    let df = docker.df().await?;
-    for volume in df.volumes.unwrap_or(vec![]) {
-        if !volume_names.contains(&volume.name) {
-            continue;
+pub async fn create_snapshot_volume(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+    snapshot_volume_name: &str,
+    labels: HashMap<String, String>,
+) -> anyhow::Result<()> {
+    match &storage.directory {
+        Some(directory) => directory.create(snapshot_volume_name, &labels),
+        None => {
+            backend
+                .create_volume(
+                    snapshot_volume_name,
+                    storage.volume_spec(snapshot_volume_name, labels),
+                )
+                .await
         }
+    }
+}
 
-        let size = volume
-            .usage_data
-            .map(|usage_data| VolumeSize::Bytes(usage_data.size))
-            .unwrap_or(VolumeSize::Unavailable);
+pub async fn drop_snapshot_volume(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+    snapshot_volume_name: &str,
+) -> anyhow::Result<()> {
+    match &storage.directory {
+        Some(directory) => directory.remove(snapshot_volume_name),
+        None => drop_volume(backend, snapshot_volume_name).await,
+    }
+}
 
-        volume_sizes.insert(volume.name, size);
+pub async fn get_snapshot_volume_labels(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+    snapshot_volume_name: &str,
+) -> anyhow::Result<HashMap<String, String>> {
+    match &storage.directory {
+        Some(directory) if directory.exists(snapshot_volume_name) => {
+            directory.labels(snapshot_volume_name)
+        }
+        Some(_) => Err(Error::VolumeNotFound(snapshot_volume_name.to_string()).into()),
+        None => get_volume_labels(backend, snapshot_volume_name).await,
     }
+}
 
-    Ok(volume_sizes)
+pub async fn get_snapshot_volume_sizes(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+    snapshot_volume_names: &[String],
+) -> anyhow::Result<HashMap<String, VolumeSize>> {
+    match &storage.directory {
+        Some(directory) => directory.sizes(snapshot_volume_names),
+        None => get_volume_sizes_for_volume_names(backend, snapshot_volume_names).await,
+    }
 }
 
-pub fn extract_snapshot_datetime(volume_name: &str) -> anyhow::Result<chrono::NaiveDateTime> {
-    let captures = SNAPSHOT_PREFIX_REGEX.captures(volume_name).ok_or(anyhow!(
-        "Failed to extract timestamp from volume name: {}",
-        volume_name
-    ))?;
+pub async fn find_snapshot_volumes(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+) -> anyhow::Result<Vec<VolumeInfo>> {
+    Ok(list_snapshot_store(backend, storage)
+        .await?
+        .into_iter()
+        .filter(|volume| storage.is_snapshot_volume(&volume.name))
+        .collect())
+}
 
-    let timestamp = captures.get(1).unwrap().as_str().parse::<i64>()?;
+pub async fn find_snapshot_volume_names(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+) -> anyhow::Result<Vec<String>> {
+    Ok(find_snapshot_volumes(backend, storage)
+        .await?
+        .into_iter()
+        .map(|volume| volume.name)
+        .collect())
+}
 
+pub async fn get_volume_sizes_for_volume_names(
+    backend: &dyn Backend,
+    volume_names: &[String],
+) -> anyhow::Result<HashMap<String, VolumeSize>> {
+    Ok(backend
+        .volume_sizes()
+        .await?
+        .into_iter()
+        .filter(|(name, _)| volume_names.contains(name))
+        .collect())
+}
+
+pub fn snapshot_datetime(timestamp: i64) -> anyhow::Result<chrono::NaiveDateTime> {
     Ok(chrono::DateTime::from_timestamp(timestamp, 0)
-        .ok_or(anyhow!(
-            "Failed to parse timestamp from volume name: {}",
-            volume_name
-        ))?
+        .ok_or(anyhow!("Invalid snapshot timestamp: {}", timestamp))?
         .with_timezone(&Local)
         .naive_local())
 }
 
-pub fn strip_snapshot_prefix(volume_name: &str) -> String {
-    SNAPSHOT_PREFIX_REGEX.replace(volume_name, "").to_string()
-}
-
+/// The snapshot volume named `snapshot_name`. Volumes still being written are skipped, and so is
+/// a volume `create --force` replaced once its replacement is complete. Any other duplicate is an
+/// error.
 pub async fn find_snapshot_volume_name_by_snapshot_name(
-    docker: &Docker,
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
     snapshot_name: &str,
+    journal: &Journal,
 ) -> anyhow::Result<Option<String>> {
-    let volume_names = find_snapshot_volume_names(docker).await?;
-
-    let volume_names = volume_names
+    let incomplete = journal.incomplete_snapshots();
+    let volume_names = find_snapshot_volume_names(backend, storage)
+        .await?
         .into_iter()
-        .filter(|volume_name| strip_snapshot_prefix(&volume_name) == snapshot_name);
+        .filter(|volume_name| {
+            storage.snapshot_name(volume_name) == snapshot_name && !incomplete.contains(volume_name)
+        })
+        .collect::<Vec<_>>();
 
-    volume_names.at_most_one().map_err(|_| {
-        anyhow!(
-            "More than one snapshot with the same name: {}",
-            snapshot_name
-        )
-    })
+    volume_names
+        .iter()
+        .filter(|volume_name| {
+            journal
+                .replacement(volume_name)
+                .is_none_or(|replacement| !volume_names.iter().any(|name| name == replacement))
+        })
+        .at_most_one()
+        .map(|volume_name| volume_name.cloned())
+        .map_err(|_| {
+            anyhow!(
+                "More than one snapshot with the same name: {}",
+                snapshot_name
+            )
+        })
 }
 
-pub async fn image_exists(docker: &Docker, image: &str) -> bool {
-    docker.inspect_image(image).await.ok().is_some()
+pub async fn get_snapshot_volume_name_by_snapshot_name(
+    backend: &dyn Backend,
+    storage: &SnapshotStorage,
+    snapshot_name: &str,
+    journal: &Journal,
+) -> anyhow::Result<String> {
+    find_snapshot_volume_name_by_snapshot_name(backend, storage, snapshot_name, journal)
+        .await?
+        .ok_or(Error::SnapshotNotFound(snapshot_name.to_string()).into())
 }
 
-pub async fn pull_image(docker: &Docker, image: &str) -> anyhow::Result<()> {
-    let pb = create_spinner("Downloading & Extracting Image...".to_string())?;
+/// Which image runs the vsnap runner and where to get it from when it is missing.
+#[derive(Clone, Debug)]
+pub struct RunnerOptions {
+    pub image: String,
+    /// A `docker save` archive to load instead of pulling, e.g. on offline machines.
+    pub image_archive: Option<PathBuf>,
+    /// A static runner binary to copy into `image` instead of using the one in a runner image.
+    pub binary: Option<RunnerBinary>,
+    /// Capabilities reported by runners that already answered the handshake.
+    pub capabilities: CapabilitiesCache,
+    pub cancellation: Cancellation,
+}
 
-    docker
-        .create_image(
-            Some(CreateImageOptions {
-                from_image: image,
-                ..Default::default()
-            }),
-            None,
-            None,
-        )
-        .try_for_each(async |_| Ok(()))
-        .await
-        .map_err(|_| anyhow!("Failed to pull docker image"))?;
+/// Capabilities by runner image and binary, so the handshake runs once per runner.
+pub type CapabilitiesCache = Arc<Mutex<HashMap<String, Capabilities>>>;
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        RunnerOptions {
+            image: RUNNER_IMAGE.to_string(),
+            image_archive: None,
+            binary: None,
+            capabilities: Default::default(),
+            cancellation: Cancellation::default(),
+        }
+    }
+}
+
+pub async fn pull_image(
+    backend: &dyn Backend,
+    image: &str,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    progress(ProgressEvent::PullingImage {
+        image: image.to_string(),
+    });
+
+    let result = backend.pull_image(image).await;
+
+    progress(ProgressEvent::PulledImage {
+        image: image.to_string(),
+    });
+
+    result
+}
+
+pub async fn load_image(
+    backend: &dyn Backend,
+    image: &str,
+    archive: &Path,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    progress(ProgressEvent::LoadingImage {
+        archive: archive.to_path_buf(),
+    });
+
+    let result = backend.load_image(archive).await;
 
-    pb.finish_with_message("Done");
+    progress(ProgressEvent::Finished);
+    result?;
+
+    if !backend.image_exists(image).await {
+        return Err(anyhow!(
+            "Image archive {} does not contain {}",
+            archive.display(),
+            image
+        ));
+    }
 
     Ok(())
 }
 
-async fn handle_progress(
-    log: Result<LogOutput, bollard::errors::Error>,
-    progress_bar: &ProgressBar,
-) {
-    match log {
-        Ok(bollard::container::LogOutput::StdOut { message }) => {
-            let progress: Option<Progress> = serde_json::from_slice(&message).ok();
+async fn ensure_runner_image(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    if backend.image_exists(&runner.image).await {
+        return Ok(());
+    }
 
-            if let Some(progress) = progress {
-                progress_bar.length().map(|len| {
-                    if len != progress.total as u64 {
-                        progress_bar.set_length(progress.total as u64);
-                    }
-                });
+    let result = async {
+        match &runner.image_archive {
+            Some(archive) => load_image(backend, &runner.image, archive, progress).await,
+            None => pull_image(backend, &runner.image, progress).await,
+        }
+    };
 
-                progress_bar.set_position(progress.progress);
+    tokio::select! {
+        result = result => result,
+        reason = runner.cancellation.cancelled() => Err(Error::from(reason).into()),
+    }
+}
 
-                if progress.progress == progress.total {
-                    progress_bar.finish();
-                }
-            }
+fn handle_progress(message: &[u8], progress: &ProgressHandler) {
+    for line in message.split(|byte| *byte == b'\n') {
+        if let Ok(warning) = serde_json::from_slice::<RunnerWarning>(line) {
+            progress(ProgressEvent::Warning {
+                message: warning.warning,
+            });
         }
-        _ => {}
     }
+
+    let latest = message
+        .split(|byte| *byte == b'\n')
+        .rev()
+        .find_map(|line| serde_json::from_slice::<Progress>(line).ok());
+
+    if let Some(latest) = latest {
+        progress(ProgressEvent::Progress {
+            progress: latest.progress,
+            total: latest.total,
+        });
+    }
+}
+
+/// Runner containers that no vsnap process is waiting for anymore. Runners from before they were
+/// labeled are recognized by their `vsnap-<timestamp>` name and image.
+pub async fn find_leftover_runners(backend: &dyn Backend) -> anyhow::Result<Vec<ContainerInfo>> {
+    let legacy = backend
+        .find_containers_by_name("vsnap-")
+        .await?
+        .into_iter()
+        .filter(|container| {
+            LEGACY_RUNNER_NAME_REGEX.is_match(&container.name)
+                && container
+                    .image
+                    .starts_with(&format!("{}:", RUNNER_REPOSITORY))
+                && !container.labels.contains_key(LABEL_RUNNER)
+        });
+
+    let now = chrono::Utc::now().timestamp();
+
+    Ok(backend
+        .find_containers_by_label(LABEL_RUNNER)
+        .await?
+        .into_iter()
+        .chain(legacy)
+        .filter(|container| {
+            !matches!(
+                container.state.as_deref(),
+                Some("running" | "restarting" | "paused")
+            )
+        })
+        // Like one that was created but not started yet, a runner of a live vsnap is in use.
+        .filter(|container| LockOwner::from_labels(&container.labels).is_stale(now))
+        .collect())
+}
+
+async fn runner_container_spec(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    cmd: Vec<&str>,
+    mounts: Vec<VolumeMount>,
+    progress: &ProgressHandler,
+) -> anyhow::Result<ContainerSpec> {
+    ensure_runner_image(backend, runner, progress).await?;
+
+    let cmd = cmd.into_iter().map(|arg| arg.to_string()).collect();
+
+    // Lets `gc` find runner containers left behind by a crashed vsnap, and tell them from the
+    // ones of a vsnap that is still running.
+    let mut labels = LockOwner::current("runner").labels();
+    labels.insert(LABEL_RUNNER.to_string(), VERSION.to_string());
+
+    let Some(binary) = &runner.binary else {
+        return Ok(ContainerSpec {
+            image: runner.image.clone(),
+            entrypoint: None,
+            cmd,
+            mounts,
+            files: vec![],
+            labels,
+            stdin: None,
+            stdout: None,
+        });
+    };
+
+    Ok(ContainerSpec {
+        image: runner.image.clone(),
+        entrypoint: Some(vec![format!("/{}", RUNNER_BINARY_NAME)]),
+        cmd,
+        mounts,
+        files: vec![ContainerFile {
+            path: RUNNER_BINARY_NAME.to_string(),
+            content: binary.content.to_vec(),
+            mode: 0o755,
+        }],
+        labels,
+        stdin: None,
+        stdout: None,
+    })
 }
 
 async fn run_command(
-    docker: &Docker,
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
     cmd: Vec<&str>,
-    host_config: HostConfig,
+    mounts: Vec<VolumeMount>,
+    progress: &ProgressHandler,
 ) -> anyhow::Result<()> {
-    let container_name = format!("vsnap-{}", chrono::Utc::now().timestamp());
-    let image = format!("fominv/vsnap:{}", VERSION.as_str());
+    runner.cancellation.check()?;
 
-    let options = Some(CreateContainerOptions {
-        name: container_name.to_string(),
-        platform: None,
-    });
+    let spec = runner_container_spec(backend, runner, cmd, mounts, progress).await?;
 
-    let config = Config {
-        image: Some(image.as_str()),
-        cmd: Some(cmd),
-        host_config: Some(host_config),
-        ..Default::default()
-    };
+    progress(ProgressEvent::Started);
 
-    if !image_exists(docker, &image).await {
-        pull_image(docker, &image).await?;
-    }
+    let result = backend
+        .run_container(spec, &runner.cancellation, &mut |message| {
+            handle_progress(message, progress)
+        })
+        .await;
 
-    match (async || {
-        docker.create_container(options, config).await?;
+    progress(ProgressEvent::Finished);
 
-        docker
-            .start_container(&container_name, None::<StartContainerOptions<String>>)
-            .await?;
+    result
+}
 
-        let progress_bar = create_progress_bar(0)?;
+async fn run_command_with_output(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    cmd: Vec<&str>,
+    mounts: Vec<VolumeMount>,
+    progress: &ProgressHandler,
+) -> anyhow::Result<Vec<u8>> {
+    runner.cancellation.check()?;
 
-        docker
-            .logs(
-                &container_name,
-                Some(LogsOptions::<String> {
-                    follow: true,
-                    stdout: true,
-                    ..Default::default()
-                }),
-            )
-            .for_each(async |log| handle_progress(log, &progress_bar).await)
-            .await;
+    let spec = runner_container_spec(backend, runner, cmd, mounts, progress).await?;
 
-        docker
-            .wait_container(&container_name, None::<WaitContainerOptions<String>>)
-            .for_each(async |_| {})
-            .await;
+    let mut output = vec![];
 
-        Ok::<(), anyhow::Error>(())
-    })()
+    backend
+        .run_container(spec, &runner.cancellation, &mut |message| {
+            output.extend_from_slice(message)
+        })
+        .await?;
+
+    Ok(output)
+}
+
+/// Asks the runner what it supports. Runners from before the handshake fail on the unknown
+/// subcommand and are treated as legacy runners.
+pub async fn runner_capabilities(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    progress: &ProgressHandler,
+) -> anyhow::Result<Capabilities> {
+    let key = format!(
+        "{}:{:?}",
+        runner.image,
+        runner.binary.as_ref().map(|binary| &binary.path)
+    );
+
+    if let Some(capabilities) = runner.capabilities.lock().unwrap().get(&key) {
+        return Ok(capabilities.clone());
+    }
+
+    if let Some(binary) = &runner.binary {
+        verify_binary_arch(backend, binary).await?;
+    }
+
+    let capabilities = match run_command_with_output(
+        backend,
+        runner,
+        vec!["capabilities", "--json"],
+        vec![],
+        progress,
+    )
     .await
     {
-        _ => {
-            docker.remove_container(&container_name, None).await.ok();
-        }
+        Ok(output) => serde_json::from_slice(&output).map_err(|e| {
+            Error::RunnerIncompatible(format!(
+                "{} reported invalid capabilities: {}",
+                runner.image, e
+            ))
+        })?,
+        Err(e) => match e.downcast_ref::<Error>() {
+            Some(Error::RunnerFailed { message, .. })
+                if message.contains("unrecognized subcommand") =>
+            {
+                Capabilities::legacy()
+            }
+            _ => return Err(e),
+        },
+    };
+
+    runner
+        .capabilities
+        .lock()
+        .unwrap()
+        .insert(key, capabilities.clone());
+
+    Ok(capabilities)
+}
+
+/// A binary for another architecture fails in the container with an unhelpful exec format error.
+async fn verify_binary_arch(backend: &dyn Backend, binary: &RunnerBinary) -> anyhow::Result<()> {
+    let engine = backend.engine_info().await?;
+
+    match engine.arch {
+        Some(arch) if arch != binary.arch => Err(Error::RunnerIncompatible(format!(
+            "Runner binary {} is built for {}, but the engine runs {}",
+            binary.path.display(),
+            binary.arch,
+            arch
+        ))
+        .into()),
+        _ => Ok(()),
     }
+}
 
-    Ok(())
+async fn check_runner(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    command: RunnerCommand,
+    progress: &ProgressHandler,
+) -> anyhow::Result<Capabilities> {
+    let capabilities = runner_capabilities(backend, runner, progress).await?;
+
+    capabilities
+        .check(command)
+        .map_err(|message| Error::RunnerIncompatible(format!("{} ({})", message, runner.image)))?;
+
+    Ok(capabilities)
+}
+
+pub async fn volume_free_space(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    volume: MountSource,
+    progress: &ProgressHandler,
+) -> anyhow::Result<DiskSpace> {
+    const VOLUME_DIR: &str = "/mnt/volume";
+
+    check_runner(backend, runner, RunnerCommand::FreeSpace, progress).await?;
+
+    let mounts = vec![mount(volume, VOLUME_DIR, true)];
+    let output = run_command_with_output(
+        backend,
+        runner,
+        vec!["free-space", VOLUME_DIR],
+        mounts,
+        progress,
+    )
+    .await?;
+
+    Ok(serde_json::from_slice(&output)?)
+}
+
+fn mount(source: MountSource, target: &str, read_only: bool) -> VolumeMount {
+    VolumeMount {
+        source,
+        target: target.to_string(),
+        read_only,
+    }
+}
+
+fn volume_mount(volume_name: &str, target: &str, read_only: bool) -> VolumeMount {
+    mount(
+        MountSource::Volume(volume_name.to_string()),
+        target,
+        read_only,
+    )
 }
 
 pub async fn snapshot(
-    docker: &Docker,
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
     source_volume_name: &str,
-    snapshot_volume_name: &str,
+    snapshot: MountSource,
     compress: bool,
+    ignore_space: bool,
+    progress: &ProgressHandler,
 ) -> anyhow::Result<()> {
     const SOURCE_DIR: &str = "/mnt/source";
     const SNAPSHOT_DIR: &str = "/mnt/snapshot";
 
+    let capabilities = check_runner(
+        backend,
+        runner,
+        RunnerCommand::Snapshot { compress },
+        progress,
+    )
+    .await?;
+
     let mut cmd = vec!["snapshot"];
 
+    // Older runners don't record the source, the snapshot just lacks it in its metadata.
+    if capabilities.has_feature(FEATURE_SOURCE_NAME) {
+        cmd.extend(["--source-name", source_volume_name]);
+    }
+
     if compress {
         cmd.push("--compress");
     }
 
+    // Older runners don't check free space, so there is nothing to ignore.
+    if ignore_space && capabilities.has_feature(FEATURE_SPACE_CHECK) {
+        cmd.push("--force");
+    }
+
     cmd.extend(vec![SOURCE_DIR, SNAPSHOT_DIR]);
 
-    let host_config = HostConfig {
-        mounts: Some(vec![
-            Mount {
-                source: Some(source_volume_name.to_string()),
-                target: Some(SOURCE_DIR.to_string()),
-                typ: Some(bollard::secret::MountTypeEnum::VOLUME),
-                read_only: Some(true),
-                ..Default::default()
-            },
-            Mount {
-                source: Some(snapshot_volume_name.to_string()),
-                target: Some(SNAPSHOT_DIR.to_string()),
-                typ: Some(bollard::secret::MountTypeEnum::VOLUME),
-                ..Default::default()
-            },
-        ]),
-        ..Default::default()
-    };
+    let mounts = vec![
+        volume_mount(source_volume_name, SOURCE_DIR, true),
+        mount(snapshot, SNAPSHOT_DIR, false),
+    ];
 
-    run_command(docker, cmd, host_config).await
+    run_command(backend, runner, cmd, mounts, progress).await
 }
 
 pub async fn restore_snapshot(
-    docker: &Docker,
-    snapshot_volume_name: &str,
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
     restore_volume_name: &str,
+    ignore_space: bool,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    restore_snapshot_many(
+        backend,
+        runner,
+        snapshot,
+        &[restore_volume_name.to_string()],
+        ignore_space,
+        progress,
+    )
+    .await
+}
+
+/// Restores a snapshot into several volumes with one runner that reads the archive once. Older
+/// runners restore them one after the other.
+pub async fn restore_snapshot_many(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    restore_volume_names: &[String],
+    ignore_space: bool,
+    progress: &ProgressHandler,
 ) -> anyhow::Result<()> {
     const SNAPSHOT_DIR: &str = "/mnt/snapshot";
     const RESTORE_DIR: &str = "/mnt/restore";
 
-    let mut cmd = vec!["restore"];
-
-    cmd.extend(vec![SNAPSHOT_DIR, RESTORE_DIR]);
-
-    let host_config = HostConfig {
-        mounts: Some(vec![
-            Mount {
-                source: Some(snapshot_volume_name.to_string()),
-                target: Some(SNAPSHOT_DIR.to_string()),
-                typ: Some(bollard::secret::MountTypeEnum::VOLUME),
-                read_only: Some(true),
-                ..Default::default()
-            },
-            Mount {
-                source: Some(restore_volume_name.to_string()),
-                target: Some(RESTORE_DIR.to_string()),
-                typ: Some(bollard::secret::MountTypeEnum::VOLUME),
-                ..Default::default()
-            },
-        ]),
-        ..Default::default()
+    let capabilities = check_runner(backend, runner, RunnerCommand::Restore, progress).await?;
+
+    let batches = match capabilities.has_feature(FEATURE_MULTI_RESTORE) {
+        true => vec![restore_volume_names],
+        false => restore_volume_names.chunks(1).collect(),
     };
 
-    run_command(docker, cmd, host_config).await?;
+    for restore_volume_names in batches {
+        let restore_dirs = match restore_volume_names.len() {
+            1 => vec![RESTORE_DIR.to_string()],
+            _ => (0..restore_volume_names.len())
+                .map(|i| format!("{}-{}", RESTORE_DIR, i))
+                .collect(),
+        };
+
+        let mut cmd = vec!["restore"];
+
+        if ignore_space && capabilities.has_feature(FEATURE_SPACE_CHECK) {
+            cmd.push("--force");
+        }
+
+        cmd.push(SNAPSHOT_DIR);
+        cmd.extend(restore_dirs.iter().map(|dir| dir.as_str()));
+
+        let mut mounts = vec![mount(snapshot.clone(), SNAPSHOT_DIR, true)];
+
+        mounts.extend(
+            restore_volume_names
+                .iter()
+                .zip(&restore_dirs)
+                .map(|(volume_name, dir)| volume_mount(volume_name, dir, false)),
+        );
+
+        run_command(backend, runner, cmd, mounts, progress).await?;
+    }
 
     Ok(())
 }
+
+/// Checks, before replaced volumes are dropped for good, that the runner can restore and that
+/// `count` copies of the snapshot fit once the replaced volumes are gone. Runners without space
+/// checks don't check during the restore either.
+pub async fn verify_restore_fits(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    replaced_volume_names: &[String],
+    count: usize,
+    ignore_space: bool,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    let capabilities = check_runner(backend, runner, RunnerCommand::Restore, progress).await?;
+
+    let measurable = capabilities.has_feature(FEATURE_SPACE_CHECK)
+        && capabilities.check(RunnerCommand::FreeSpace).is_ok()
+        && capabilities.check(RunnerCommand::Export).is_ok();
+
+    let Some(replaced_volume_name) = replaced_volume_names.first().filter(|_| measurable) else {
+        return Ok(());
+    };
+
+    let metadata: SnapshotMetadata = serde_json::from_slice(
+        &read_snapshot_file(backend, runner, snapshot, SNAPSHOT_METADATA, progress).await?,
+    )?;
+
+    // Restored volumes usually share the filesystem of the volumes they replace.
+    let space = volume_free_space(
+        backend,
+        runner,
+        MountSource::Volume(replaced_volume_name.clone()),
+        progress,
+    )
+    .await?;
+
+    let freed = get_volume_sizes_for_volume_names(backend, replaced_volume_names)
+        .await?
+        .into_values()
+        .map(|size| match size {
+            VolumeSize::Bytes(bytes) => bytes.max(0) as u64,
+            VolumeSize::Unavailable => 0,
+        })
+        .sum::<u64>();
+
+    let required = (metadata.total_size * count as u64).saturating_sub(freed);
+
+    match (space.check_fits(required), ignore_space) {
+        (Ok(()), _) => Ok(()),
+        (Err(message), true) => {
+            progress(ProgressEvent::Warning {
+                message: format!("Disk may run full: {}", message),
+            });
+
+            Ok(())
+        }
+        (Err(message), false) => Err(anyhow!(
+            "Not enough disk space: {}, free up space or use --ignore-space to try anyway",
+            message
+        )),
+    }
+}
+
+/// Copies one volume into another with a runner that mounts both.
+pub async fn clone_volume(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    source_volume_name: &str,
+    destination_volume_name: &str,
+    ignore_space: bool,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    const SOURCE_DIR: &str = "/mnt/source";
+    const DESTINATION_DIR: &str = "/mnt/destination";
+
+    check_runner(backend, runner, RunnerCommand::Clone, progress).await?;
+
+    let mut cmd = vec!["clone"];
+
+    if ignore_space {
+        cmd.push("--force");
+    }
+
+    cmd.extend(vec![SOURCE_DIR, DESTINATION_DIR]);
+
+    let mounts = vec![
+        volume_mount(source_volume_name, SOURCE_DIR, true),
+        volume_mount(destination_volume_name, DESTINATION_DIR, false),
+    ];
+
+    run_command(backend, runner, cmd, mounts, progress).await
+}
+
+pub async fn copy_snapshot(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    destination: MountSource,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    const SNAPSHOT_DIR: &str = "/mnt/snapshot";
+    const DESTINATION_DIR: &str = "/mnt/destination";
+
+    check_runner(backend, runner, RunnerCommand::Copy, progress).await?;
+
+    let mut cmd = vec!["copy"];
+
+    cmd.extend(vec![SNAPSHOT_DIR, DESTINATION_DIR]);
+
+    let mounts = vec![
+        mount(snapshot, SNAPSHOT_DIR, true),
+        mount(destination, DESTINATION_DIR, false),
+    ];
+
+    run_command(backend, runner, cmd, mounts, progress).await
+}
+
+/// Reads a small file of a snapshot, e.g. its metadata.
+pub async fn read_snapshot_file(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    file_name: &str,
+    progress: &ProgressHandler,
+) -> anyhow::Result<Vec<u8>> {
+    const SNAPSHOT_DIR: &str = "/mnt/snapshot";
+
+    check_runner(backend, runner, RunnerCommand::Export, progress).await?;
+
+    let cmd = vec!["export", SNAPSHOT_DIR, file_name];
+    let mounts = vec![mount(snapshot, SNAPSHOT_DIR, true)];
+
+    run_command_with_output(backend, runner, cmd, mounts, progress).await
+}
+
+/// Streams a file of a snapshot out of the runner into `sender`, e.g. to upload the archive.
+pub async fn export_snapshot_file(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    file_name: &str,
+    sender: mpsc::Sender<Vec<u8>>,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    const SNAPSHOT_DIR: &str = "/mnt/snapshot";
+
+    check_runner(backend, runner, RunnerCommand::Export, progress).await?;
+
+    let cmd = vec!["export", SNAPSHOT_DIR, file_name];
+    let mounts = vec![mount(snapshot, SNAPSHOT_DIR, true)];
+
+    runner.cancellation.check()?;
+
+    let mut spec = runner_container_spec(backend, runner, cmd, mounts, progress).await?;
+
+    spec.stdout = Some(sender);
+
+    backend
+        .run_container(spec, &runner.cancellation, &mut |_| {})
+        .await
+}
+
+/// Streams what `receiver` gets into a file of a snapshot, e.g. a downloaded archive.
+pub async fn import_snapshot_file(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    file_name: &str,
+    receiver: mpsc::Receiver<Vec<u8>>,
+    progress: &ProgressHandler,
+) -> anyhow::Result<()> {
+    const SNAPSHOT_DIR: &str = "/mnt/snapshot";
+
+    check_runner(backend, runner, RunnerCommand::Import, progress).await?;
+
+    let cmd = vec!["import", SNAPSHOT_DIR, file_name];
+    let mounts = vec![mount(snapshot, SNAPSHOT_DIR, false)];
+
+    runner.cancellation.check()?;
+
+    let mut spec = runner_container_spec(backend, runner, cmd, mounts, progress).await?;
+
+    spec.stdin = Some(receiver);
+
+    backend
+        .run_container(spec, &runner.cancellation, &mut |_| {})
+        .await
+}
+
+pub async fn inspect_snapshot(
+    backend: &dyn Backend,
+    runner: &RunnerOptions,
+    snapshot: MountSource,
+    verify: bool,
+    progress: &ProgressHandler,
+) -> anyhow::Result<SnapshotInspection> {
+    const SNAPSHOT_DIR: &str = "/mnt/snapshot";
+
+    check_runner(backend, runner, RunnerCommand::Inspect { verify }, progress).await?;
+
+    let mut cmd = vec!["inspect"];
+
+    if verify {
+        cmd.push("--verify");
+    }
+
+    cmd.push(SNAPSHOT_DIR);
+
+    let mounts = vec![mount(snapshot, SNAPSHOT_DIR, true)];
+
+    let output = run_command_with_output(backend, runner, cmd, mounts, progress).await?;
+
+    Ok(serde_json::from_slice(&output)?)
+}
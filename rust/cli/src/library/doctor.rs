@@ -0,0 +1,125 @@
+use indicatif::HumanBytes;
+use serde::Serialize;
+
+use crate::library::{DiskSpace, constant::VERSION};
+
+/// Below this much free space snapshots are likely to fail.
+const MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What to do about a warning or failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    pub fn pass(name: &str, message: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn warn(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    pub fn fail(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+pub fn engine_unreachable(message: impl Into<String>) -> Check {
+    Check::fail(
+        "engine",
+        message,
+        "Start the docker daemon, or point --host or --context at a reachable one",
+    )
+}
+
+/// The result of `vsnap doctor`, meant to be attached to bug reports.
+#[derive(Serialize, Clone, Debug)]
+pub struct DoctorReport {
+    pub version: String,
+    /// The worst status of all checks.
+    pub health: CheckStatus,
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    pub fn new(checks: Vec<Check>) -> Self {
+        DoctorReport {
+            version: VERSION.to_string(),
+            health: checks
+                .iter()
+                .map(|check| check.status)
+                .max()
+                .unwrap_or(CheckStatus::Pass),
+            checks,
+        }
+    }
+}
+
+/// Whether API version `a` is older than `b`, comparing `major.minor` numerically.
+pub fn is_older_api_version(a: &str, b: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split('.')
+            .map(|part| part.parse::<u64>().unwrap_or_default())
+            .collect::<Vec<_>>()
+    };
+
+    parse(a) < parse(b)
+}
+
+pub fn check_disk_space(space: DiskSpace) -> Check {
+    const NAME: &str = "disk_space";
+
+    let message = format!(
+        "{} of {} free for volumes",
+        HumanBytes(space.available),
+        HumanBytes(space.total)
+    );
+
+    if space.available < MIN_FREE_SPACE {
+        return Check::fail(
+            NAME,
+            message,
+            "Free up space, e.g. with `docker system prune` or `vsnap drop`",
+        );
+    }
+
+    if space.available < space.total / 10 {
+        return Check::warn(
+            NAME,
+            message,
+            "Less than 10% is free, large snapshots may not fit",
+        );
+    }
+
+    Check::pass(NAME, message)
+}
@@ -0,0 +1,138 @@
+use thiserror::Error as ThisError;
+
+/// Errors with a stable exit code, so automation can tell failures apart.
+///
+/// | Exit code | Error                                      |
+/// |-----------|--------------------------------------------|
+/// | 0         | Success                                    |
+/// | 1         | Any other error                            |
+/// | 2         | Invalid arguments                          |
+/// | 3         | Snapshot not found                         |
+/// | 4         | Snapshot already exists                    |
+/// | 5         | Volume not found                           |
+/// | 6         | Volume in use                              |
+/// | 7         | Snapshot is pinned                         |
+/// | 8         | Volume is protected or reserved            |
+/// | 9         | Docker daemon unreachable                  |
+/// | 10        | Runner container failed                    |
+/// | 11        | Volume already exists                      |
+/// | 12        | Runner image incompatible                  |
+/// | 13        | Locked by another operation                |
+/// | 124       | Timed out                                  |
+/// | 130       | Interrupted                                |
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    #[error("Snapshot {0} not found")]
+    SnapshotNotFound(String),
+
+    #[error("Snapshot already exists: {0}")]
+    SnapshotExists(String),
+
+    #[error("Volume does not exist: {0}")]
+    VolumeNotFound(String),
+
+    #[error("Volume {volume} is in use by {containers}")]
+    VolumeInUse { volume: String, containers: String },
+
+    /// `hint` says how to get past it for the operation at hand.
+    #[error("Snapshot {snapshot} is pinned, {hint}")]
+    SnapshotPinned { snapshot: String, hint: String },
+
+    #[error("Volume {0} is protected and cannot be overwritten")]
+    VolumeProtected(String),
+
+    #[error("Docker is unreachable: {0}")]
+    DockerUnavailable(String),
+
+    #[error("Runner exited with code {code}: {message}")]
+    RunnerFailed { code: i64, message: String },
+
+    #[error("Volume already exists: {0}")]
+    VolumeExists(String),
+
+    #[error("Incompatible runner: {0}")]
+    RunnerIncompatible(String),
+
+    #[error("{0}, retry later or wait without --no-wait")]
+    Locked(String),
+
+    #[error("Timed out, partially created volumes were removed")]
+    TimedOut,
+
+    #[error("Interrupted, partially created volumes were removed")]
+    Interrupted,
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Other(_) => 1,
+            Error::InvalidArgument(_) => 2,
+            Error::SnapshotNotFound(_) => 3,
+            Error::SnapshotExists(_) => 4,
+            Error::VolumeNotFound(_) => 5,
+            Error::VolumeInUse { .. } => 6,
+            Error::SnapshotPinned { .. } => 7,
+            Error::VolumeProtected(_) => 8,
+            Error::DockerUnavailable(_) => 9,
+            Error::RunnerFailed { .. } => 10,
+            Error::VolumeExists(_) => 11,
+            Error::RunnerIncompatible(_) => 12,
+            Error::Locked(_) => 13,
+            Error::TimedOut => 124,
+            Error::Interrupted => 130,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Other(_) => "other",
+            Error::InvalidArgument(_) => "invalid_argument",
+            Error::SnapshotNotFound(_) => "snapshot_not_found",
+            Error::SnapshotExists(_) => "snapshot_exists",
+            Error::VolumeNotFound(_) => "volume_not_found",
+            Error::VolumeInUse { .. } => "volume_in_use",
+            Error::SnapshotPinned { .. } => "snapshot_pinned",
+            Error::VolumeProtected(_) => "volume_protected",
+            Error::DockerUnavailable(_) => "docker_unavailable",
+            Error::RunnerFailed { .. } => "runner_failed",
+            Error::VolumeExists(_) => "volume_exists",
+            Error::RunnerIncompatible(_) => "runner_incompatible",
+            Error::Locked(_) => "locked",
+            Error::TimedOut => "timed_out",
+            Error::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<Error>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+
+        let docker_unavailable = error.chain().any(|cause| {
+            matches!(
+                cause.downcast_ref::<bollard::errors::Error>(),
+                Some(
+                    bollard::errors::Error::IOError { .. }
+                        | bollard::errors::Error::HyperResponseError { .. }
+                        | bollard::errors::Error::HyperLegacyError { .. }
+                        | bollard::errors::Error::SocketNotFoundError(_)
+                )
+            )
+        });
+
+        match docker_unavailable {
+            true => Error::DockerUnavailable(error.to_string()),
+            false => Error::Other(error),
+        }
+    }
+}
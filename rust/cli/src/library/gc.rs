@@ -0,0 +1,31 @@
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GarbageKind {
+    /// A stopped runner container that was not removed.
+    Container,
+    /// A snapshot volume without a complete archive and metadata.
+    Snapshot,
+    /// A snapshot that an interrupted `create --force` had already replaced.
+    ReplacedSnapshot,
+    /// A volume whose restore never finished.
+    RestoredVolume,
+    /// An operation lock whose process died or that expired.
+    Lock,
+}
+
+/// A leftover of an interrupted operation and why it is considered broken.
+#[derive(Serialize, Clone, Debug)]
+pub struct Garbage {
+    pub kind: GarbageKind,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GcResult {
+    pub removed: Vec<Garbage>,
+    /// Found but left in place, e.g. for a dry run.
+    pub kept: Vec<Garbage>,
+}
@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::library::state;
+
+/// A restore that was started but has not finished or been rolled back yet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingRestore {
+    pub volume: String,
+    #[serde(default)]
+    pub snapshot: String,
+    /// Set instead of `snapshot` when the volume is cloned from another volume.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_volume: Option<String>,
+    pub started_at: i64,
+}
+
+/// A snapshot volume that is still being written.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingCreate {
+    pub volume: String,
+    pub snapshot: String,
+    pub started_at: i64,
+}
+
+/// A snapshot volume that `create --force` replaces with `replacement`. It is dropped once the
+/// replacement is complete, until then both exist.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingReplace {
+    pub volume: String,
+    pub replacement: String,
+    pub started_at: i64,
+}
+
+/// Operations in progress, kept next to the config so that `gc` can tell a half-restored volume
+/// from one that was restored completely, and name resolution can skip snapshots that are still
+/// being written.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Journal {
+    pub restores: Vec<PendingRestore>,
+    pub creates: Vec<PendingCreate>,
+    pub replaces: Vec<PendingReplace>,
+}
+
+impl Journal {
+    pub fn path(config_path: &Path) -> PathBuf {
+        config_path.with_file_name("journal.json")
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        state::load(path, "journal")
+    }
+
+    pub fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        state::save(self, path)
+    }
+
+    /// Changes the journal without losing entries other vsnap processes record at the same time.
+    pub fn update_at<T>(path: &Path, change: impl FnOnce(&mut Journal) -> T) -> anyhow::Result<T> {
+        state::update(path, "journal", change)
+    }
+
+    pub fn start_restore(&mut self, volume: &str, snapshot: &str) {
+        self.finish_restore(volume);
+        self.restores.push(PendingRestore {
+            volume: volume.to_string(),
+            snapshot: snapshot.to_string(),
+            source_volume: None,
+            started_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    /// Clones are rolled back and collected like restores.
+    pub fn start_clone(&mut self, volume: &str, source_volume: &str) {
+        self.finish_restore(volume);
+        self.restores.push(PendingRestore {
+            volume: volume.to_string(),
+            snapshot: String::new(),
+            source_volume: Some(source_volume.to_string()),
+            started_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    pub fn finish_restore(&mut self, volume: &str) -> bool {
+        let len = self.restores.len();
+
+        self.restores.retain(|restore| restore.volume != volume);
+
+        len != self.restores.len()
+    }
+
+    pub fn start_create(&mut self, volume: &str, snapshot: &str) {
+        self.finish_create(volume);
+        self.creates.push(PendingCreate {
+            volume: volume.to_string(),
+            snapshot: snapshot.to_string(),
+            started_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    pub fn finish_create(&mut self, volume: &str) -> bool {
+        let len = self.creates.len();
+
+        self.creates.retain(|create| create.volume != volume);
+
+        len != self.creates.len()
+    }
+
+    pub fn start_replace(&mut self, volume: &str, replacement: &str) {
+        self.finish_replace(volume);
+        self.replaces.push(PendingReplace {
+            volume: volume.to_string(),
+            replacement: replacement.to_string(),
+            started_at: chrono::Utc::now().timestamp(),
+        });
+    }
+
+    pub fn finish_replace(&mut self, volume: &str) -> bool {
+        let len = self.replaces.len();
+
+        self.replaces.retain(|replace| replace.volume != volume);
+
+        len != self.replaces.len()
+    }
+
+    /// The volume replacing the snapshot volume `volume`, if it is being replaced.
+    pub fn replacement(&self, volume: &str) -> Option<&str> {
+        self.replaces
+            .iter()
+            .find(|replace| replace.volume == volume)
+            .map(|replace| replace.replacement.as_str())
+    }
+
+    /// Snapshot volumes that are not completely written yet.
+    pub fn incomplete_snapshots(&self) -> Vec<String> {
+        self.creates
+            .iter()
+            .map(|create| create.volume.clone())
+            .collect()
+    }
+}
@@ -0,0 +1,250 @@
+use std::{str::FromStr, sync::LazyLock, time::Duration};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use itertools::Itertools;
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::library::{pattern::matches_pattern, table::snapshot_table};
+
+static TEMPLATE_FIELD_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*\.(\w+)\s*\}\}").expect("Failed to compile template field regex")
+});
+
+static DURATION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d+)\s*([smhdw])$").expect("Failed to compile duration regex"));
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub volume_name: String,
+    pub created_at: i64,
+    pub source: Option<String>,
+    pub size: Option<u64>,
+    pub pinned: bool,
+}
+
+impl SnapshotEntry {
+    pub fn local_datetime(&self) -> String {
+        DateTime::from_timestamp(self.created_at, 0)
+            .map(|datetime| datetime.with_timezone(&Local).naive_local().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn age(&self) -> String {
+        format_age(Utc::now().timestamp() - self.created_at)
+    }
+
+    fn field(&self, field: &str) -> anyhow::Result<String> {
+        Ok(match field {
+            "Name" => self.name.clone(),
+            "Volume" | "VolumeName" => self.volume_name.clone(),
+            "Created" | "CreatedAt" => self.created_at.to_string(),
+            "Datetime" | "LocalDatetime" => self.local_datetime(),
+            "Age" => self.age(),
+            "Source" => self.source.clone().unwrap_or_default(),
+            "Size" => self.size.map(|size| size.to_string()).unwrap_or_default(),
+            "Pinned" => self.pinned.to_string(),
+            _ => return Err(anyhow!("Unknown format field: {}", field)),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+    Names,
+    /// Go-template style format such as `{{.Name}}\t{{.Size}}`.
+    Template(String),
+}
+
+impl FromStr for ListFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(ListFormat::Table),
+            "json" => Ok(ListFormat::Json),
+            "csv" => Ok(ListFormat::Csv),
+            "names" => Ok(ListFormat::Names),
+            s if s.contains("{{") => Ok(ListFormat::Template(s.to_string())),
+            s => Err(anyhow!(
+                "unknown format {}, expected table, json, csv, names or a template like {{{{.Name}}}}",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum SortKey {
+    #[default]
+    Date,
+    Name,
+    Size,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct ListFilter {
+    pub source: Option<String>,
+    pub since: Option<i64>,
+    pub pattern: Option<String>,
+}
+
+impl ListFilter {
+    pub fn matches(&self, entry: &SnapshotEntry) -> bool {
+        self.source
+            .as_ref()
+            .is_none_or(|source| entry.source.as_ref() == Some(source))
+            && self.since.is_none_or(|since| entry.created_at >= since)
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| matches_pattern(pattern, &entry.name))
+    }
+}
+
+fn duration_seconds(value: &str) -> Option<u64> {
+    let captures = DURATION_REGEX.captures(value.trim())?;
+    let amount = captures[1].parse::<u64>().ok()?;
+    let seconds = match &captures[2] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => 60 * 60 * 24 * 7,
+    };
+
+    amount.checked_mul(seconds)
+}
+
+/// Parses a duration like `90s`, `10m` or `2h`. A plain number is taken as seconds.
+pub fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    duration_seconds(value)
+        .or_else(|| value.trim().parse().ok())
+        .map(Duration::from_secs)
+        .ok_or(anyhow!(
+            "Invalid duration {}, expected e.g. 90s, 10m or 2h",
+            value
+        ))
+}
+
+/// Parses `--since` as a relative duration (`30m`, `2h`, `7d`), a date or an RFC 3339 datetime.
+pub fn parse_since(value: &str) -> anyhow::Result<i64> {
+    if let Some(seconds) = duration_seconds(value) {
+        return Ok(Utc::now().timestamp() - seconds as i64);
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.timestamp());
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+            .map(|datetime| datetime.timestamp())
+            .ok_or(anyhow!("Invalid date: {}", value));
+    }
+
+    Err(anyhow!(
+        "Invalid --since value {}, expected a duration like 2h or 7d, a date or an RFC 3339 datetime",
+        value
+    ))
+}
+
+pub fn format_age(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+
+    let (amount, unit) = match seconds {
+        s if s < 60 => (s, "second"),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 60 * 60 * 24 => (s / 60 / 60, "hour"),
+        s if s < 60 * 60 * 24 * 7 => (s / 60 / 60 / 24, "day"),
+        s if s < 60 * 60 * 24 * 30 => (s / 60 / 60 / 24 / 7, "week"),
+        s if s < 60 * 60 * 24 * 365 => (s / 60 / 60 / 24 / 30, "month"),
+        s => (s / 60 / 60 / 24 / 365, "year"),
+    };
+
+    match amount {
+        1 => format!("1 {} ago", unit),
+        amount => format!("{} {}s ago", amount, unit),
+    }
+}
+
+pub fn sort_entries(entries: &mut [SnapshotEntry], key: SortKey) {
+    match key {
+        SortKey::Date => entries.sort_by(|a, b| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        SortKey::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Size => {
+            entries.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)))
+        }
+    }
+}
+
+/// Renders the entries for `list`, `include_size` adds a size column to tables.
+pub fn render_entries(
+    entries: &[SnapshotEntry],
+    format: &ListFormat,
+    include_size: bool,
+) -> anyhow::Result<String> {
+    Ok(match format {
+        ListFormat::Table if entries.is_empty() => "No snapshots found.".to_string(),
+        ListFormat::Table => snapshot_table(entries, include_size),
+        ListFormat::Json => serde_json::to_string_pretty(entries)?,
+        ListFormat::Csv => {
+            std::iter::once("name,volume_name,created_at,age,source,size,pinned".to_string())
+                .chain(entries.iter().map(|entry| {
+                    [
+                        escape_csv(&entry.name),
+                        escape_csv(&entry.volume_name),
+                        entry.created_at.to_string(),
+                        escape_csv(&entry.age()),
+                        escape_csv(entry.source.as_deref().unwrap_or_default()),
+                        entry.size.map(|size| size.to_string()).unwrap_or_default(),
+                        entry.pinned.to_string(),
+                    ]
+                    .join(",")
+                }))
+                .join("\n")
+        }
+        ListFormat::Names => entries.iter().map(|entry| entry.name.clone()).join("\n"),
+        ListFormat::Template(template) => entries
+            .iter()
+            .map(|entry| render_template(template, entry))
+            .collect::<anyhow::Result<Vec<String>>>()?
+            .join("\n"),
+    })
+}
+
+fn render_template(template: &str, entry: &SnapshotEntry) -> anyhow::Result<String> {
+    let template = template.replace("\\t", "\t").replace("\\n", "\n");
+
+    let mut error = None;
+
+    let rendered = TEMPLATE_FIELD_REGEX.replace_all(&template, |captures: &Captures| {
+        entry.field(&captures[1]).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            String::new()
+        })
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(rendered.to_string()),
+    }
+}
+
+fn escape_csv(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
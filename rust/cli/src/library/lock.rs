@@ -0,0 +1,462 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::library::{
+    backend::Backend,
+    cancel::Cancellation,
+    constant::{
+        LABEL_LOCK_CREATED, LABEL_LOCK_HOST, LABEL_LOCK_OPERATION, LABEL_LOCK_OWNER,
+        LABEL_LOCK_PID, LABEL_LOCK_SHARED, LOCK_PREFIX,
+    },
+    error::Error,
+    progress::{ProgressEvent, ProgressHandler},
+};
+
+/// Locks held by another host can't be checked for a live process and only expire.
+pub static FOREIGN_LOCK_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+static POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Something an operation locks.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockTarget {
+    Volume(String),
+    /// A snapshot under a prefix, see `SnapshotStorage::snapshot_lock`.
+    Snapshot {
+        prefix: String,
+        name: String,
+    },
+}
+
+impl LockTarget {
+    /// Locks are empty volumes, so that every client of the daemon sees them. Snapshots of
+    /// different prefixes don't share locks.
+    pub fn volume_name(&self) -> String {
+        match self {
+            LockTarget::Volume(name) => format!("{}volume-{}", LOCK_PREFIX, name),
+            LockTarget::Snapshot { prefix, name } => {
+                format!("{}snapshot-{}{}", LOCK_PREFIX, prefix, name)
+            }
+        }
+    }
+}
+
+/// Operations that only read a target share it, everything else needs it to itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+impl fmt::Display for LockTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockTarget::Volume(name) => write!(f, "volume {}", name),
+            LockTarget::Snapshot { name, .. } => write!(f, "snapshot {}", name),
+        }
+    }
+}
+
+/// Who holds a lock, as recorded in the labels of its volume.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct LockOwner {
+    pub token: String,
+    pub host: String,
+    pub pid: u32,
+    pub operation: String,
+    pub created_at: i64,
+}
+
+impl LockOwner {
+    pub fn current(operation: &str) -> Self {
+        let host = host_name();
+        let pid = std::process::id();
+        let now = chrono::Utc::now();
+
+        LockOwner {
+            token: format!(
+                "{}-{}-{}-{}",
+                host,
+                pid,
+                now.timestamp_nanos_opt().unwrap_or_default(),
+                SEQUENCE.fetch_add(1, Ordering::Relaxed)
+            ),
+            host,
+            pid,
+            operation: operation.to_string(),
+            created_at: now.timestamp(),
+        }
+    }
+
+    pub fn from_labels(labels: &HashMap<String, String>) -> Self {
+        let label = |key: &str| labels.get(key).cloned().unwrap_or_default();
+
+        LockOwner {
+            token: label(LABEL_LOCK_OWNER),
+            host: label(LABEL_LOCK_HOST),
+            pid: label(LABEL_LOCK_PID).parse().unwrap_or_default(),
+            operation: label(LABEL_LOCK_OPERATION),
+            created_at: label(LABEL_LOCK_CREATED).parse().unwrap_or_default(),
+        }
+    }
+
+    pub fn labels(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (LABEL_LOCK_OWNER.to_string(), self.token.clone()),
+            (LABEL_LOCK_HOST.to_string(), self.host.clone()),
+            (LABEL_LOCK_PID.to_string(), self.pid.to_string()),
+            (LABEL_LOCK_OPERATION.to_string(), self.operation.clone()),
+            (LABEL_LOCK_CREATED.to_string(), self.created_at.to_string()),
+        ])
+    }
+
+    /// A lock is stale when its process died on this host, or when it was taken on another
+    /// host longer than `FOREIGN_LOCK_EXPIRY` ago.
+    pub fn is_stale(&self, now: i64) -> bool {
+        match self.host == host_name() {
+            true => self.pid != std::process::id() && !process_alive(self.pid),
+            false => now - self.created_at > FOREIGN_LOCK_EXPIRY.as_secs() as i64,
+        }
+    }
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (pid {} on {})", self.operation, self.pid, self.host)
+    }
+}
+
+/// Locks taken by `acquire_locks`, released with `release`.
+pub struct LockGuard {
+    owner: LockOwner,
+    volume_names: Vec<String>,
+}
+
+impl LockGuard {
+    /// Removes the lock volumes that still belong to this guard.
+    pub async fn release(self, backend: &dyn Backend) {
+        for volume_name in self.volume_names.iter().rev() {
+            let owned = match backend.inspect_volume(volume_name).await {
+                Ok(Some(volume)) => {
+                    LockOwner::from_labels(&volume.labels).token == self.owner.token
+                }
+                _ => false,
+            };
+
+            if owned {
+                backend.remove_volume(volume_name).await.ok();
+            }
+        }
+    }
+}
+
+/// Takes the locks in a fixed order, so that two operations never wait on each other. Waits for
+/// other holders unless `wait` is false, and takes over stale locks.
+pub async fn acquire_locks(
+    backend: &dyn Backend,
+    operation: &str,
+    mut targets: Vec<(LockTarget, LockMode)>,
+    wait: bool,
+    cancellation: &Cancellation,
+    progress: &ProgressHandler,
+) -> Result<LockGuard, Error> {
+    // A target that is both read and written only needs the exclusive lock.
+    targets.sort_by(|(target, mode), (other_target, other_mode)| {
+        target.cmp(other_target).then(other_mode.cmp(mode))
+    });
+    targets.dedup_by(|(target, _), (other_target, _)| target == other_target);
+
+    let mut guard = LockGuard {
+        owner: LockOwner::current(operation),
+        volume_names: vec![],
+    };
+
+    for (target, mode) in targets {
+        let result = acquire_lock(
+            backend,
+            &guard.owner,
+            &target,
+            mode,
+            wait,
+            cancellation,
+            progress,
+        )
+        .await;
+
+        match result {
+            Ok(volume_name) => guard.volume_names.push(volume_name),
+            Err(e) => {
+                guard.release(backend).await;
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(guard)
+}
+
+/// The outcome of one attempt to take a lock.
+enum Attempt {
+    /// Holds the lock through this volume.
+    Acquired(String),
+    Held(LockOwner),
+    /// A stale lock was taken over, or a reader backed off from a writer.
+    Retry,
+}
+
+/// Takes one lock and returns the volume that holds it.
+async fn acquire_lock(
+    backend: &dyn Backend,
+    owner: &LockOwner,
+    target: &LockTarget,
+    mode: LockMode,
+    wait: bool,
+    cancellation: &Cancellation,
+    progress: &ProgressHandler,
+) -> Result<String, Error> {
+    let volume_name = target.volume_name();
+    let mut waiting = false;
+
+    let result = loop {
+        if let Err(e) = cancellation.check() {
+            break Err(e);
+        }
+
+        let attempt = match mode {
+            LockMode::Exclusive => try_exclusive(backend, owner, &volume_name).await,
+            LockMode::Shared => try_shared(backend, owner, &volume_name).await,
+        };
+
+        let holder = match attempt {
+            Ok(Attempt::Acquired(volume_name)) => break Ok(volume_name),
+            Ok(Attempt::Held(holder)) => holder,
+            Ok(Attempt::Retry) => continue,
+            Err(e) => break Err(e),
+        };
+
+        if !wait {
+            break Err(Error::Locked(format!("{} is locked by {}", target, holder)));
+        }
+
+        if !waiting {
+            progress(ProgressEvent::WaitingForLock {
+                target: target.to_string(),
+                holder: holder.to_string(),
+            });
+            waiting = true;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            reason = cancellation.cancelled() => break Err(reason.into()),
+        }
+    };
+
+    if waiting {
+        progress(ProgressEvent::Finished);
+    }
+
+    // A writer gives up the lock it holds while waiting for readers.
+    if result.is_err() {
+        LockGuard {
+            owner: owner.clone(),
+            volume_names: vec![volume_name],
+        }
+        .release(backend)
+        .await;
+    }
+
+    result
+}
+
+async fn try_exclusive(
+    backend: &dyn Backend,
+    owner: &LockOwner,
+    volume_name: &str,
+) -> Result<Attempt, Error> {
+    // Creating an existing volume keeps its labels, so only one owner's labels stick.
+    backend
+        .create_volume(volume_name, owner.labels().into())
+        .await?;
+
+    let Some(volume) = backend.inspect_volume(volume_name).await? else {
+        return Ok(Attempt::Retry);
+    };
+
+    let holder = LockOwner::from_labels(&volume.labels);
+
+    if holder.token != owner.token {
+        return take_over_if_stale(backend, owner, volume_name, holder).await;
+    }
+
+    // Readers that came first get to finish, new ones back off while this lock is held.
+    Ok(match find_live_reader(backend, volume_name).await? {
+        Some(reader) => Attempt::Held(reader),
+        None => Attempt::Acquired(volume_name.to_string()),
+    })
+}
+
+/// Readers hold a lock through a volume of their own each, labeled with the lock's volume.
+async fn try_shared(
+    backend: &dyn Backend,
+    owner: &LockOwner,
+    volume_name: &str,
+) -> Result<Attempt, Error> {
+    if let Some(volume) = backend.inspect_volume(volume_name).await? {
+        let holder = LockOwner::from_labels(&volume.labels);
+
+        return take_over_if_stale(backend, owner, volume_name, holder).await;
+    }
+
+    let reader_volume_name = format!("{}-reader-{}", volume_name, owner.token);
+    let mut labels = owner.labels();
+    labels.insert(LABEL_LOCK_SHARED.to_string(), volume_name.to_string());
+
+    backend
+        .create_volume(&reader_volume_name, labels.into())
+        .await?;
+
+    // A writer that came in at the same time either finds this reader and waits for it, or is
+    // found here.
+    match backend.inspect_volume(volume_name).await? {
+        Some(_) => {
+            backend.remove_volume(&reader_volume_name).await.ok();
+            Ok(Attempt::Retry)
+        }
+        None => Ok(Attempt::Acquired(reader_volume_name)),
+    }
+}
+
+async fn take_over_if_stale(
+    backend: &dyn Backend,
+    owner: &LockOwner,
+    volume_name: &str,
+    holder: LockOwner,
+) -> Result<Attempt, Error> {
+    if !holder.is_stale(chrono::Utc::now().timestamp()) {
+        return Ok(Attempt::Held(holder));
+    }
+
+    if !take_over(backend, owner, volume_name, &holder).await? {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    Ok(Attempt::Retry)
+}
+
+/// Removes a stale lock, unless another process is taking it over at the same time. Takeovers
+/// are serialised by their own lock, so that one process can't remove the lock another one just
+/// took over. Returns whether the takeover was attempted.
+pub async fn take_over(
+    backend: &dyn Backend,
+    owner: &LockOwner,
+    volume_name: &str,
+    stale_holder: &LockOwner,
+) -> Result<bool, Error> {
+    let takeover_volume_name = format!("{}-takeover", volume_name);
+
+    backend
+        .create_volume(&takeover_volume_name, owner.labels().into())
+        .await?;
+
+    let taker = match backend.inspect_volume(&takeover_volume_name).await? {
+        Some(volume) => LockOwner::from_labels(&volume.labels),
+        None => return Ok(false),
+    };
+
+    if taker.token != owner.token {
+        // Left behind by a process that died during its takeover.
+        if taker.is_stale(chrono::Utc::now().timestamp()) {
+            backend.remove_volume(&takeover_volume_name).await.ok();
+        }
+
+        return Ok(false);
+    }
+
+    // The lock may have been taken over and taken again since it was found stale.
+    let still_stale = match backend.inspect_volume(volume_name).await? {
+        Some(volume) => LockOwner::from_labels(&volume.labels).token == stale_holder.token,
+        None => false,
+    };
+
+    if still_stale {
+        backend.remove_volume(volume_name).await.ok();
+    }
+
+    backend.remove_volume(&takeover_volume_name).await.ok();
+
+    Ok(true)
+}
+
+/// The holder of a lock that is neither released nor stale, the writer or one of the readers.
+pub async fn find_live_lock(
+    backend: &dyn Backend,
+    target: &LockTarget,
+) -> anyhow::Result<Option<LockOwner>> {
+    let volume_name = target.volume_name();
+
+    if let Some(volume) = backend.inspect_volume(&volume_name).await? {
+        let holder = LockOwner::from_labels(&volume.labels);
+
+        if !holder.is_stale(chrono::Utc::now().timestamp()) {
+            return Ok(Some(holder));
+        }
+    }
+
+    find_live_reader(backend, &volume_name).await
+}
+
+/// A reader of the lock held by `volume_name` that is neither released nor stale. Stale readers
+/// are left to `vsnap gc`.
+async fn find_live_reader(
+    backend: &dyn Backend,
+    volume_name: &str,
+) -> anyhow::Result<Option<LockOwner>> {
+    let now = chrono::Utc::now().timestamp();
+
+    Ok(backend
+        .list_volumes()
+        .await?
+        .into_iter()
+        .filter(|volume| {
+            volume.labels.get(LABEL_LOCK_SHARED).map(String::as_str) == Some(volume_name)
+        })
+        .map(|volume| LockOwner::from_labels(&volume.labels))
+        .find(|reader| !reader.is_stale(now)))
+}
+
+#[cfg(unix)]
+fn host_name() -> String {
+    nix::unistd::gethostname()
+        .ok()
+        .and_then(|name| name.into_string().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+
+    match i32::try_from(pid) {
+        Ok(pid) if pid > 0 => !matches!(kill(Pid::from_raw(pid), None), Err(Errno::ESRCH)),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
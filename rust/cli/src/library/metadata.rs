@@ -0,0 +1,112 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+pub static METADATA_VERSION: u32 = 2;
+
+pub static SNAPSHOT_TAR_ZST: &str = "snapshot.tar.zst";
+pub static SNAPSHOT_TAR: &str = "snapshot.tar";
+
+pub static SNAPSHOT_METADATA: &str = "metadata.json";
+
+fn legacy_metadata_version() -> u32 {
+    1
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SnapshotMetadata {
+    #[serde(default = "legacy_metadata_version")]
+    pub version: u32,
+    pub total_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_volume: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+}
+
+impl SnapshotMetadata {
+    pub fn new(total_size: u64) -> Self {
+        SnapshotMetadata {
+            version: METADATA_VERSION,
+            total_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The file the archive of a snapshot with this metadata is stored in.
+    pub fn archive_name(&self) -> &'static str {
+        match self.compression {
+            Some(_) => SNAPSHOT_TAR_ZST,
+            None => SNAPSHOT_TAR,
+        }
+    }
+}
+
+/// Formats a SHA-256 digest the way snapshot metadata records it.
+pub fn format_checksum(digest: &[u8]) -> String {
+    let hex = digest
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    format!("sha256:{}", hex)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumState {
+    /// The snapshot was created without a checksum.
+    Unavailable,
+    /// A checksum is recorded but was not verified.
+    Unverified,
+    Valid,
+    Invalid,
+}
+
+/// What the runner finds when inspecting a snapshot directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotInspection {
+    pub metadata: Option<SnapshotMetadata>,
+    pub archive: Option<String>,
+    pub archive_size: Option<u64>,
+    pub checksum: ChecksumState,
+}
+
+/// Everything `vsnap inspect` reports about a snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotDetails {
+    pub name: String,
+    pub volume_name: String,
+    pub created_at: i64,
+    pub pinned: bool,
+    pub parent: Option<String>,
+    pub dependents: Vec<String>,
+    /// Sorted, so that `vsnap --output json inspect` prints them in a stable order.
+    pub labels: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub inspection: SnapshotInspection,
+}
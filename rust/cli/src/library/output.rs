@@ -0,0 +1,102 @@
+use serde::Serialize;
+use serde_json::json;
+
+use crate::library::{
+    batch::BatchResult,
+    client::{
+        CloneResult, CopyResult, CreateResult, DropResult, ImageResult, PullResult, PushResult,
+        RestoreCopiesResult, RestoreResult,
+    },
+    doctor::{CheckStatus, DoctorReport},
+    error::Error,
+    gc::GcResult,
+    listing::SnapshotEntry,
+    metadata::SnapshotDetails,
+    remote::RemoteSnapshot,
+};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// The structured result of a command, printed with `--output json`.
+#[derive(Serialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum CommandResult {
+    Create(CreateResult),
+    CreateBatch(BatchResult<CreateResult>),
+    Restore(RestoreResult),
+    RestoreBatch(BatchResult<RestoreResult>),
+    RestoreCopies(RestoreCopiesResult),
+    Cleanup { dropped_volumes: Vec<String> },
+    Clone(CloneResult),
+    Drop(DropResult),
+    Rename(CopyResult),
+    Copy(CopyResult),
+    List { snapshots: Vec<SnapshotEntry> },
+    Push(PushResult),
+    Pull(PullResult),
+    RemoteList { snapshots: Vec<RemoteSnapshot> },
+    RemoteDelete { remote: String },
+    Inspect { snapshot: Box<SnapshotDetails> },
+    Pin { snapshot: String, pinned: bool },
+    Protect { protected_volumes: Vec<String> },
+    Image(ImageResult),
+    Gc(GcResult),
+    Doctor(DoctorReport),
+}
+
+impl CommandResult {
+    /// Whether the command should exit with a failure despite printing a result.
+    pub fn failed(&self) -> bool {
+        match self {
+            CommandResult::CreateBatch(result) => result.failed > 0,
+            CommandResult::RestoreBatch(result) => result.failed > 0,
+            CommandResult::Drop(result) => !result.failed.is_empty(),
+            CommandResult::Doctor(report) => report.health == CheckStatus::Fail,
+            _ => false,
+        }
+    }
+}
+
+pub fn print_json_result(result: &CommandResult) -> anyhow::Result<()> {
+    let mut value = serde_json::to_value(result)?;
+
+    // A failed doctor check is part of the report, not a failure to produce it.
+    value["status"] = match result {
+        CommandResult::Doctor(_) => json!("ok"),
+        result if result.failed() => json!("failed"),
+        _ => json!("ok"),
+    };
+
+    println!("{}", serde_json::to_string_pretty(&value)?);
+
+    Ok(())
+}
+
+pub fn print_error(error: &Error, output: OutputFormat) {
+    match output {
+        OutputFormat::Text => match error {
+            Error::Other(error) => eprintln!("Error: {:?}", error),
+            error => eprintln!("Error: {}", error),
+        },
+        OutputFormat::Json => {
+            let value = json!({
+                "status": "error",
+                "error": {
+                    "kind": error.kind(),
+                    "message": error.to_string(),
+                    "exit_code": error.exit_code(),
+                }
+            });
+
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&value).unwrap_or_default()
+            );
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use regex::Regex;
+
+use crate::library::error::Error;
+
+/// Matches `name` against a glob-style pattern supporting `*` and `?`.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let regex = pattern
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect::<String>();
+
+    Regex::new(&format!("^{}$", regex)).is_ok_and(|regex| regex.is_match(name))
+}
+
+/// Placeholder for the index in volume name templates like `test-db-{i}`.
+pub static TEMPLATE_INDEX: &str = "{i}";
+
+/// Names `count` volumes after `template`, numbering them from `start`.
+pub fn expand_template(template: &str, start: usize, count: usize) -> Result<Vec<String>, Error> {
+    if !template.contains(TEMPLATE_INDEX) || count == 0 {
+        return Err(Error::InvalidArgument(format!(
+            "Name template {} needs a {} placeholder and a count of at least one",
+            template, TEMPLATE_INDEX
+        )));
+    }
+
+    Ok((start..start + count)
+        .map(|i| template.replace(TEMPLATE_INDEX, &i.to_string()))
+        .collect())
+}
+
+/// Whether `name` is one of the names `template` expands to, for any index.
+pub fn matches_template(template: &str, name: &str) -> bool {
+    let regex = template
+        .split(TEMPLATE_INDEX)
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"\d+");
+
+    template.contains(TEMPLATE_INDEX)
+        && Regex::new(&format!("^{}$", regex)).is_ok_and(|regex| regex.is_match(name))
+}
@@ -1,6 +1,10 @@
-use std::time::Duration;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
 pub fn create_spinner(message: String) -> anyhow::Result<ProgressBar> {
     let pb = ProgressBar::new_spinner();
@@ -8,7 +12,7 @@ pub fn create_spinner(message: String) -> anyhow::Result<ProgressBar> {
     pb.set_message(message);
     pb.enable_steady_tick(Duration::from_millis(100));
     pb.set_style(
-        ProgressStyle::with_template("{spinner:.green} {msg}")?.tick_strings(&[
+        ProgressStyle::with_template("{spinner:.green} {prefix}{msg}")?.tick_strings(&[
             "▹▹▹▹▹",
             "▸▹▹▹▹",
             "▹▸▹▹▹",
@@ -29,9 +33,117 @@ pub fn create_progress_bar(total_size: u64) -> anyhow::Result<ProgressBar> {
 
     pb.set_style(
         ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")? 
+            .template("{spinner:.green} {prefix}[{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")? 
             .progress_chars("#>-"),
     );
 
     Ok(pb)
 }
+
+/// Progress reported by long running operations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    PullingImage { image: String },
+    PulledImage { image: String },
+    LoadingImage { archive: PathBuf },
+    BuildingImage { image: String },
+    Started,
+    Progress { progress: u64, total: u64 },
+    Finished,
+    Warning { message: String },
+    WaitingForLock { target: String, holder: String },
+}
+
+pub type ProgressHandler = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+pub fn no_progress() -> ProgressHandler {
+    Arc::new(|_| {})
+}
+
+/// Draws progress events as indicatif spinners and progress bars.
+pub fn terminal_progress() -> ProgressHandler {
+    bar_progress(None, String::new())
+}
+
+/// Draws progress events on a line of `multi` labelled with `label`, so that concurrent
+/// operations each get their own line.
+pub fn multi_terminal_progress(multi: &MultiProgress, label: &str) -> ProgressHandler {
+    bar_progress(Some(multi.clone()), format!("{}: ", label))
+}
+
+fn bar_progress(multi: Option<MultiProgress>, prefix: String) -> ProgressHandler {
+    let current: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+    // On a shared display a new bar takes the place of the previous one.
+    let show = move |current: &mut Option<ProgressBar>, pb: anyhow::Result<ProgressBar>| {
+        let Ok(pb) = pb else {
+            return;
+        };
+
+        pb.set_prefix(prefix.clone());
+
+        *current = match &multi {
+            Some(multi) => Some(match current.take() {
+                Some(previous) => {
+                    let pb = multi.insert_after(&previous, pb);
+                    multi.remove(&previous);
+                    pb
+                }
+                None => multi.add(pb),
+            }),
+            None => Some(pb),
+        };
+    };
+
+    Arc::new(move |event| {
+        let Ok(mut current) = current.lock() else {
+            return;
+        };
+
+        match event {
+            ProgressEvent::PullingImage { .. } => show(
+                &mut current,
+                create_spinner("Downloading & Extracting Image...".to_string()),
+            ),
+            ProgressEvent::LoadingImage { .. } => {
+                show(&mut current, create_spinner("Loading Image...".to_string()))
+            }
+            ProgressEvent::BuildingImage { .. } => show(
+                &mut current,
+                create_spinner("Building Image...".to_string()),
+            ),
+            ProgressEvent::WaitingForLock { target, holder } => show(
+                &mut current,
+                create_spinner(format!("Waiting for {} to release {}...", holder, target)),
+            ),
+            ProgressEvent::PulledImage { .. } => {
+                if let Some(pb) = current.as_ref() {
+                    pb.finish_with_message("Done");
+                }
+            }
+            ProgressEvent::Started => show(&mut current, create_progress_bar(0)),
+            ProgressEvent::Progress { progress, total } => {
+                if let Some(pb) = current.as_ref() {
+                    if pb.length() != Some(total) {
+                        pb.set_length(total);
+                    }
+
+                    pb.set_position(progress);
+
+                    if progress == total {
+                        pb.finish();
+                    }
+                }
+            }
+            ProgressEvent::Finished => {
+                if let Some(pb) = current.take() {
+                    pb.finish();
+                }
+            }
+            ProgressEvent::Warning { message } => match current.as_ref() {
+                Some(pb) => pb.suspend(|| eprintln!("Warning: {}", message)),
+                None => eprintln!("Warning: {}", message),
+            },
+        }
+    })
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::anyhow;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bollard::auth::DockerCredentials;
+use serde::Deserialize;
+
+const DOCKER_HUB_REGISTRY: &str = "docker.io";
+const DOCKER_HUB_SERVER: &str = "https://index.docker.io/v1/";
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct DockerConfigFile {
+    #[serde(default)]
+    auths: HashMap<String, AuthEntry>,
+    creds_store: Option<String>,
+    #[serde(default)]
+    cred_helpers: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Default)]
+struct AuthEntry {
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    identitytoken: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperCredentials {
+    username: String,
+    secret: String,
+}
+
+/// The registry an image reference is pulled from, `docker.io` for unqualified names.
+pub fn image_registry(image: &str) -> String {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            first.to_string()
+        }
+        _ => DOCKER_HUB_REGISTRY.to_string(),
+    }
+}
+
+/// Looks up credentials for the image's registry like the docker CLI does: a registry specific
+/// credential helper first, then the `auths` section and finally the default credential store.
+pub fn registry_credentials(
+    docker_config_dir: &Path,
+    image: &str,
+) -> anyhow::Result<Option<DockerCredentials>> {
+    let path = docker_config_dir.join("config.json");
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let config: DockerConfigFile = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+
+    let registry = image_registry(image);
+    let server = match registry.as_str() {
+        DOCKER_HUB_REGISTRY => DOCKER_HUB_SERVER.to_string(),
+        registry => registry.to_string(),
+    };
+
+    if let Some(helper) = config.cred_helpers.get(&registry) {
+        return credentials_from_helper(helper, &server);
+    }
+
+    let auth = config
+        .auths
+        .iter()
+        .find(|(key, _)| normalize_registry(key) == registry);
+
+    if let Some((key, auth)) = auth {
+        let (username, password) = match &auth.auth {
+            Some(encoded) => {
+                let decoded = String::from_utf8(STANDARD.decode(encoded.trim())?)?;
+                let (username, password) = decoded.split_once(':').ok_or(anyhow!(
+                    "Invalid auth entry for {} in {}",
+                    key,
+                    path.display()
+                ))?;
+
+                (Some(username.to_string()), Some(password.to_string()))
+            }
+            None => (auth.username.clone(), auth.password.clone()),
+        };
+
+        return Ok(Some(DockerCredentials {
+            username,
+            password,
+            identitytoken: auth.identitytoken.clone(),
+            serveraddress: Some(key.clone()),
+            ..Default::default()
+        }));
+    }
+
+    match &config.creds_store {
+        Some(helper) => credentials_from_helper(helper, &server),
+        None => Ok(None),
+    }
+}
+
+fn normalize_registry(key: &str) -> String {
+    let host = key
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or_default();
+
+    match host {
+        "index.docker.io" | "registry-1.docker.io" => DOCKER_HUB_REGISTRY.to_string(),
+        host => host.to_string(),
+    }
+}
+
+fn credentials_from_helper(
+    helper: &str,
+    server: &str,
+) -> anyhow::Result<Option<DockerCredentials>> {
+    let program = format!("docker-credential-{}", helper);
+
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {}: {}", program, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(server.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+
+    // Helpers exit with an error when they hold no credentials for the server.
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let credentials: HelperCredentials = serde_json::from_slice(&output.stdout)?;
+
+    Ok(Some(match credentials.username.as_str() {
+        "<token>" => DockerCredentials {
+            identitytoken: Some(credentials.secret),
+            serveraddress: Some(server.to_string()),
+            ..Default::default()
+        },
+        _ => DockerCredentials {
+            username: Some(credentials.username),
+            password: Some(credentials.secret),
+            serveraddress: Some(server.to_string()),
+            ..Default::default()
+        },
+    }))
+}
@@ -0,0 +1,203 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::{StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::library::{error::Error, metadata::format_checksum};
+
+pub mod oci;
+pub mod plugin;
+pub mod s3;
+
+/// Chunks of a snapshot archive on its way to or from a remote. An error ends the transfer
+/// without completing it.
+pub type ArchiveStream = BoxStream<'static, anyhow::Result<Vec<u8>>>;
+
+/// A snapshot on its way to a remote.
+pub struct RemoteUpload {
+    /// The snapshot's `metadata.json`, as written by the runner.
+    pub metadata: Vec<u8>,
+    pub archive_name: String,
+    pub archive_size: u64,
+    pub archive: ArchiveStream,
+}
+
+/// A snapshot on its way back from a remote.
+pub struct RemoteDownload {
+    pub metadata: Vec<u8>,
+    pub archive_name: String,
+    pub archive: ArchiveStream,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteSnapshot {
+    pub remote: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<i64>,
+}
+
+/// Somewhere outside the engine that snapshots are pushed to and pulled from. Locations are
+/// remote URLs without the scheme, e.g. `bucket/path/snapshot` for `s3://bucket/path/snapshot`.
+#[async_trait]
+pub trait RemoteStore: Send + Sync {
+    /// Stores the snapshot, replacing one at the same location. A failing archive stream must
+    /// not leave a complete looking snapshot behind.
+    async fn put(&self, location: &str, upload: RemoteUpload) -> anyhow::Result<()>;
+
+    async fn get(&self, location: &str) -> anyhow::Result<RemoteDownload>;
+
+    /// Snapshots at or below `location`, with `remote` set to their location.
+    async fn list(&self, location: &str) -> anyhow::Result<Vec<RemoteSnapshot>>;
+
+    async fn delete(&self, location: &str) -> anyhow::Result<()>;
+}
+
+/// Where a snapshot is pushed to or pulled from, `<scheme>://<location>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteUrl {
+    pub scheme: String,
+    pub location: String,
+}
+
+impl RemoteUrl {
+    /// A location ending in `/` is a folder, the snapshot is stored in it under its own name.
+    pub fn with_name(&self, snapshot_name: &str) -> RemoteUrl {
+        match self.location.is_empty() || self.location.ends_with('/') {
+            true => RemoteUrl {
+                scheme: self.scheme.clone(),
+                location: format!("{}{}", self.location, snapshot_name),
+            },
+            false => self.clone(),
+        }
+    }
+}
+
+impl FromStr for RemoteUrl {
+    type Err = Error;
+
+    fn from_str(remote: &str) -> Result<Self, Self::Err> {
+        match remote.split_once("://") {
+            Some((scheme, location))
+                if !scheme.is_empty()
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                Ok(RemoteUrl {
+                    scheme: scheme.to_string(),
+                    location: location.to_string(),
+                })
+            }
+            _ => Err(Error::InvalidArgument(format!(
+                "Invalid remote {}, expected e.g. s3://bucket/path",
+                remote
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for RemoteUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.location)
+    }
+}
+
+/// Passes an archive through, failing at its end when its size or checksum is not what the
+/// metadata recorded. `on_progress` gets the bytes transferred so far.
+pub fn verify_archive(
+    archive: ArchiveStream,
+    expected_size: Option<u64>,
+    expected_checksum: Option<String>,
+    on_progress: impl Fn(u64) + Send + 'static,
+) -> ArchiveStream {
+    struct State<F> {
+        archive: ArchiveStream,
+        hasher: Sha256,
+        size: u64,
+        on_progress: F,
+        done: bool,
+    }
+
+    let state = State {
+        archive,
+        hasher: Sha256::new(),
+        size: 0,
+        on_progress,
+        done: false,
+    };
+
+    futures::stream::unfold(state, move |mut state| {
+        let expected_checksum = expected_checksum.clone();
+
+        async move {
+            if state.done {
+                return None;
+            }
+
+            match state.archive.next().await {
+                Some(Ok(chunk)) => {
+                    state.hasher.update(&chunk);
+                    state.size += chunk.len() as u64;
+                    (state.on_progress)(state.size);
+
+                    Some((Ok(chunk), state))
+                }
+                Some(Err(e)) => {
+                    state.done = true;
+                    Some((Err(e), state))
+                }
+                None => {
+                    state.done = true;
+
+                    let checksum = format_checksum(state.hasher.clone().finalize().as_slice());
+
+                    let error = match (expected_size, expected_checksum) {
+                        (Some(expected), _) if expected != state.size => Some(anyhow!(
+                            "Archive has {} bytes, but the snapshot recorded {}",
+                            state.size,
+                            expected
+                        )),
+                        (_, Some(expected)) if expected != checksum => Some(anyhow!(
+                            "Archive checksum {} does not match the snapshot's {}",
+                            checksum,
+                            expected
+                        )),
+                        _ => None,
+                    };
+
+                    error.map(|error| (Err(error), state))
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Reads up to `part_size` bytes of an archive, less only at its end. The archive must not be
+/// read again after a short part.
+pub async fn read_part(archive: &mut ArchiveStream, part_size: usize) -> anyhow::Result<Vec<u8>> {
+    let mut part = Vec::with_capacity(part_size);
+
+    while part.len() < part_size {
+        match archive.next().await {
+            Some(chunk) => part.extend(chunk?),
+            None => break,
+        }
+    }
+
+    Ok(part)
+}
+
+/// The chunks `receiver` gets, as an archive stream.
+pub fn receiver_stream(receiver: mpsc::Receiver<Vec<u8>>) -> ArchiveStream {
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (Ok(chunk), receiver))
+    })
+    .boxed()
+}
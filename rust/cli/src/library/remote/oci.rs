@@ -0,0 +1,773 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bollard::auth::DockerCredentials;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::library::{
+    constant::INSECURE_REGISTRIES_ENV,
+    error::Error,
+    metadata::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST, format_checksum},
+    registry::{image_registry, registry_credentials},
+    remote::{
+        ArchiveStream, RemoteDownload, RemoteSnapshot, RemoteStore, RemoteUpload, read_part,
+        s3::uri_encode,
+    },
+};
+
+pub static ARTIFACT_TYPE: &str = "application/vnd.vsnap.snapshot.v1";
+pub static CONFIG_MEDIA_TYPE: &str = "application/vnd.vsnap.snapshot.config.v1+json";
+pub static METADATA_MEDIA_TYPE: &str = "application/vnd.vsnap.snapshot.metadata.v1+json";
+pub static ARCHIVE_MEDIA_TYPE: &str = "application/vnd.vsnap.snapshot.archive.v1.tar";
+pub static COMPRESSED_ARCHIVE_MEDIA_TYPE: &str =
+    "application/vnd.vsnap.snapshot.archive.v1.tar+zstd";
+
+static MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+static ANNOTATION_TITLE: &str = "org.opencontainers.image.title";
+static ANNOTATION_CREATED: &str = "org.opencontainers.image.created";
+
+const DOCKER_HUB_REGISTRY: &str = "docker.io";
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+const DEFAULT_TAG: &str = "latest";
+
+/// Blobs are uploaded in chunks of this size.
+const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// A snapshot in a registry, `registry/repository:tag` or `registry/repository@digest`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OciReference {
+    pub registry: String,
+    pub repository: String,
+    /// A tag or digest, `latest` if none was given.
+    pub reference: String,
+    /// Served over plain http, like the docker daemon's `insecure-registries`.
+    pub insecure: bool,
+}
+
+impl OciReference {
+    /// Parses a reference the way the docker CLI does, unqualified names are on Docker Hub.
+    pub fn parse(location: &str) -> Result<Self, Error> {
+        let invalid = || {
+            Error::InvalidArgument(format!(
+                "Invalid remote oci://{}, expected oci://registry/repository:tag",
+                location
+            ))
+        };
+
+        let registry = image_registry(location);
+        let name = match location.strip_prefix(&format!("{}/", registry)) {
+            Some(name) => name,
+            None => location,
+        };
+
+        let (repository, reference) = match name.split_once('@') {
+            Some((repository, digest)) => (repository, digest),
+            None => match name.rsplit_once(':') {
+                Some((repository, tag)) if !tag.contains('/') => (repository, tag),
+                _ => (name, DEFAULT_TAG),
+            },
+        };
+
+        if repository.is_empty() || reference.is_empty() || repository.ends_with('/') {
+            return Err(invalid());
+        }
+
+        let repository = match (registry.as_str(), repository.contains('/')) {
+            (DOCKER_HUB_REGISTRY, false) => format!("library/{}", repository),
+            _ => repository.to_string(),
+        };
+
+        Ok(OciReference {
+            registry,
+            repository,
+            reference: reference.to_string(),
+            insecure: false,
+        })
+    }
+
+    fn with_reference(&self, reference: &str) -> OciReference {
+        OciReference {
+            reference: reference.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Where the registry API is served, plain http only for insecure registries and the ones
+    /// on this machine.
+    pub fn base_url(&self) -> String {
+        let host = self.registry.split(':').next().unwrap_or_default();
+
+        match (self.registry.as_str(), host) {
+            (DOCKER_HUB_REGISTRY, _) => format!("https://{}", DOCKER_HUB_API),
+            (_, "localhost" | "127.0.0.1") => format!("http://{}", self.registry),
+            _ if self.insecure => format!("http://{}", self.registry),
+            _ => format!("https://{}", self.registry),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v2/{}/{}", self.base_url(), self.repository, path)
+    }
+}
+
+impl std::fmt::Display for OciReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reference.contains(':') {
+            true => write!(
+                f,
+                "{}/{}@{}",
+                self.registry, self.repository, self.reference
+            ),
+            false => write!(
+                f,
+                "{}/{}:{}",
+                self.registry, self.repository, self.reference
+            ),
+        }
+    }
+}
+
+/// The parameters of a `WWW-Authenticate` challenge, keyed by lowercase name, with the scheme
+/// under `scheme`.
+pub fn parse_challenge(header: &str) -> HashMap<String, String> {
+    let (scheme, parameters) = header.trim().split_once(' ').unwrap_or((header.trim(), ""));
+    let mut challenge = HashMap::from([("scheme".to_string(), scheme.to_lowercase())]);
+    let mut rest = parameters.trim();
+
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+
+        challenge.insert(key, value.to_string());
+        rest = remaining.trim().trim_start_matches(',');
+    }
+
+    challenge
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    media_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    artifact_type: Option<String>,
+    config: Descriptor,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    fn is_snapshot(&self) -> bool {
+        self.artifact_type.as_deref() == Some(ARTIFACT_TYPE)
+            || self.config.media_type == CONFIG_MEDIA_TYPE
+    }
+
+    fn layer(&self, media_types: &[&str]) -> Option<&Descriptor> {
+        self.layers
+            .iter()
+            .find(|layer| media_types.contains(&layer.media_type.as_str()))
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TagList {
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+struct RegistryErrors {
+    #[serde(default)]
+    errors: Vec<RegistryError>,
+}
+
+#[derive(Deserialize)]
+struct RegistryError {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Snapshots as OCI artifacts in a container registry: the archive and `metadata.json` are
+/// layers with vsnap media types, credentials come from the docker config.
+pub struct OciStore {
+    client: Client,
+    docker_config: Option<PathBuf>,
+    insecure_registries: Vec<String>,
+    /// `Authorization` headers by registry and scope.
+    authorizations: Mutex<HashMap<(String, String), String>>,
+}
+
+impl OciStore {
+    pub fn new(docker_config: Option<PathBuf>) -> Self {
+        OciStore {
+            client: Client::new(),
+            docker_config,
+            insecure_registries: vec![],
+            authorizations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the insecure registries from `VSNAP_INSECURE_REGISTRIES`, a comma separated list
+    /// like `registry:5000,10.0.0.2:5000`.
+    pub fn from_env(docker_config: Option<PathBuf>) -> Self {
+        let insecure_registries = env::var(INSECURE_REGISTRIES_ENV)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|registry| !registry.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Self::new(docker_config).with_insecure_registries(insecure_registries)
+    }
+
+    /// Registries reached over plain http, by `host:port`.
+    pub fn with_insecure_registries(mut self, insecure_registries: Vec<String>) -> Self {
+        self.insecure_registries = insecure_registries;
+        self
+    }
+
+    fn reference(&self, location: &str) -> Result<OciReference, Error> {
+        let mut reference = OciReference::parse(location)?;
+
+        reference.insecure = self.insecure_registries.contains(&reference.registry);
+
+        Ok(reference)
+    }
+
+    fn scope(reference: &OciReference, push: bool) -> String {
+        match push {
+            true => format!("repository:{}:pull,push", reference.repository),
+            false => format!("repository:{}:pull", reference.repository),
+        }
+    }
+
+    /// Sends a request, authenticating and retrying once when the registry asks for it.
+    async fn send(
+        &self,
+        reference: &OciReference,
+        push: bool,
+        request: impl Fn(&Client) -> RequestBuilder,
+    ) -> anyhow::Result<Response> {
+        let key = (reference.registry.clone(), Self::scope(reference, push));
+        let cached = self.authorizations.lock().unwrap().get(&key).cloned();
+
+        let with_authorization = |authorization: Option<String>| match authorization {
+            Some(authorization) => request(&self.client).header("authorization", authorization),
+            None => request(&self.client),
+        };
+
+        let response = with_authorization(cached).send().await?;
+
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let challenge = response
+            .headers()
+            .get("www-authenticate")
+            .and_then(|header| header.to_str().ok())
+            .map(parse_challenge)
+            .ok_or(anyhow!(
+                "Registry {} refused access without saying how to authenticate",
+                reference.registry
+            ))?;
+
+        let authorization = self.authenticate(reference, &key.1, &challenge).await?;
+
+        self.authorizations
+            .lock()
+            .unwrap()
+            .insert(key, authorization.clone());
+
+        Ok(with_authorization(Some(authorization)).send().await?)
+    }
+
+    fn credentials(&self, reference: &OciReference) -> anyhow::Result<Option<DockerCredentials>> {
+        match &self.docker_config {
+            Some(docker_config) => {
+                registry_credentials(docker_config, &format!("{}/", reference.registry))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn authenticate(
+        &self,
+        reference: &OciReference,
+        scope: &str,
+        challenge: &HashMap<String, String>,
+    ) -> anyhow::Result<String> {
+        let credentials = self.credentials(reference)?.unwrap_or_default();
+
+        let basic = match (&credentials.username, &credentials.password) {
+            (Some(username), Some(password)) => Some(format!(
+                "Basic {}",
+                STANDARD.encode(format!("{}:{}", username, password))
+            )),
+            _ => None,
+        };
+
+        let realm = match challenge.get("scheme").map(String::as_str) {
+            Some("basic") => {
+                return basic.ok_or(anyhow!(
+                    "No credentials for {} in the docker config, run docker login",
+                    reference.registry
+                ));
+            }
+            Some("bearer") => challenge.get("realm").ok_or(anyhow!(
+                "Registry {} sent no token realm",
+                reference.registry
+            ))?,
+            _ => {
+                return Err(anyhow!(
+                    "Registry {} asks for an unsupported authentication scheme",
+                    reference.registry
+                ));
+            }
+        };
+
+        let service = challenge.get("service").cloned().unwrap_or_default();
+
+        // Identity tokens are OAuth refresh tokens, everything else logs in with basic auth.
+        let request = match &credentials.identitytoken {
+            Some(identity_token) => self
+                .client
+                .post(realm)
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(
+                    [
+                        ("grant_type", "refresh_token"),
+                        ("service", &service),
+                        ("scope", scope),
+                        ("client_id", "vsnap"),
+                        ("refresh_token", identity_token),
+                    ]
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, uri_encode(value, true)))
+                    .collect::<Vec<_>>()
+                    .join("&"),
+                ),
+            None => {
+                let request = self
+                    .client
+                    .get(realm)
+                    .query(&[("service", service.as_str()), ("scope", scope)]);
+
+                match basic {
+                    Some(basic) => request.header("authorization", basic),
+                    None => request,
+                }
+            }
+        };
+
+        let token: TokenResponse =
+            serde_json::from_slice(&check(request.send().await?).await?.bytes().await?)?;
+
+        token
+            .token
+            .or(token.access_token)
+            .map(|token| format!("Bearer {}", token))
+            .ok_or(anyhow!(
+                "Registry {} returned no token, check the credentials in the docker config",
+                reference.registry
+            ))
+    }
+
+    /// Uploads a blob in chunks as it is read, returning its descriptor.
+    async fn upload_blob(
+        &self,
+        reference: &OciReference,
+        media_type: &str,
+        archive: &mut ArchiveStream,
+    ) -> anyhow::Result<Descriptor> {
+        let response = check(
+            self.send(reference, true, |client| {
+                client
+                    .post(reference.url("blobs/uploads/"))
+                    .header("content-length", "0")
+            })
+            .await?,
+        )
+        .await?;
+
+        let mut location = upload_location(reference, &response)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+
+        let mut chunk = read_part(archive, CHUNK_SIZE).await?;
+
+        while !chunk.is_empty() {
+            hasher.update(&chunk);
+
+            let range = format!("{}-{}", size, size + chunk.len() as u64 - 1);
+            let response = check(
+                self.send(reference, true, |client| {
+                    client
+                        .patch(location.clone())
+                        .header("content-type", "application/octet-stream")
+                        .header("content-range", range.clone())
+                        .body(chunk.clone())
+                })
+                .await?,
+            )
+            .await?;
+
+            size += chunk.len() as u64;
+            location = upload_location(reference, &response)?;
+
+            chunk = match chunk.len() < CHUNK_SIZE {
+                true => vec![],
+                false => read_part(archive, CHUNK_SIZE).await?,
+            };
+        }
+
+        let digest = format_checksum(hasher.finalize().as_slice());
+
+        location.query_pairs_mut().append_pair("digest", &digest);
+
+        check(
+            self.send(reference, true, |client| {
+                client
+                    .put(location.clone())
+                    .header("content-type", "application/octet-stream")
+                    .header("content-length", "0")
+            })
+            .await?,
+        )
+        .await?;
+
+        Ok(Descriptor {
+            media_type: media_type.to_string(),
+            digest,
+            size,
+            annotations: BTreeMap::new(),
+        })
+    }
+
+    async fn upload_bytes(
+        &self,
+        reference: &OciReference,
+        media_type: &str,
+        content: Vec<u8>,
+    ) -> anyhow::Result<Descriptor> {
+        let mut stream = futures::stream::iter([Ok(content)]).boxed();
+
+        self.upload_blob(reference, media_type, &mut stream).await
+    }
+
+    /// The manifest of a reference, `None` if there is none.
+    async fn get_manifest(
+        &self,
+        reference: &OciReference,
+    ) -> anyhow::Result<Option<(Manifest, String)>> {
+        let response = self
+            .send(reference, false, |client| {
+                client
+                    .get(reference.url(&format!("manifests/{}", reference.reference)))
+                    .header("accept", MANIFEST_MEDIA_TYPE)
+            })
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = check(response).await?;
+        let digest = response
+            .headers()
+            .get("docker-content-digest")
+            .and_then(|digest| digest.to_str().ok())
+            .map(str::to_string);
+
+        let body = response.bytes().await?;
+        let digest = digest.unwrap_or(format_checksum(&Sha256::digest(&body)));
+
+        Ok(Some((serde_json::from_slice(&body)?, digest)))
+    }
+
+    async fn get_snapshot_manifest(&self, reference: &OciReference) -> anyhow::Result<Manifest> {
+        match self.get_manifest(reference).await? {
+            Some((manifest, _)) if manifest.is_snapshot() => Ok(manifest),
+            Some(_) => Err(anyhow!("oci://{} is not a vsnap snapshot", reference)),
+            None => Err(Error::SnapshotNotFound(format!("oci://{}", reference)).into()),
+        }
+    }
+
+    async fn get_blob(
+        &self,
+        reference: &OciReference,
+        descriptor: &Descriptor,
+    ) -> anyhow::Result<Response> {
+        check(
+            self.send(reference, false, |client| {
+                client.get(reference.url(&format!("blobs/{}", descriptor.digest)))
+            })
+            .await?,
+        )
+        .await
+    }
+}
+
+/// Where the next request of a blob upload goes, relative locations resolved.
+fn upload_location(reference: &OciReference, response: &Response) -> anyhow::Result<Url> {
+    let location = response
+        .headers()
+        .get("location")
+        .and_then(|location| location.to_str().ok())
+        .ok_or(anyhow!(
+            "Registry {} did not say where to upload",
+            reference.registry
+        ))?;
+
+    Ok(Url::parse(&reference.base_url())?.join(location)?)
+}
+
+/// Fails with the registry's error codes and messages for an unsuccessful response.
+async fn check(response: Response) -> anyhow::Result<Response> {
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let errors = serde_json::from_str::<RegistryErrors>(&body).unwrap_or_default();
+
+    let message = match errors.errors.is_empty() {
+        true => body,
+        false => errors
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.code, error.message))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    Err(anyhow!(
+        "Registry request failed with {}: {}",
+        status,
+        message
+    ))
+}
+
+#[async_trait]
+impl RemoteStore for OciStore {
+    async fn put(&self, location: &str, mut upload: RemoteUpload) -> anyhow::Result<()> {
+        let reference = self.reference(location)?;
+
+        let archive_media_type = match upload.archive_name.as_str() {
+            name if name == SNAPSHOT_TAR_ZST => COMPRESSED_ARCHIVE_MEDIA_TYPE,
+            _ => ARCHIVE_MEDIA_TYPE,
+        };
+
+        let mut archive = self
+            .upload_blob(&reference, archive_media_type, &mut upload.archive)
+            .await?;
+        let mut metadata = self
+            .upload_bytes(&reference, METADATA_MEDIA_TYPE, upload.metadata)
+            .await?;
+        let config = self
+            .upload_bytes(&reference, CONFIG_MEDIA_TYPE, b"{}".to_vec())
+            .await?;
+
+        archive
+            .annotations
+            .insert(ANNOTATION_TITLE.to_string(), upload.archive_name.clone());
+        metadata
+            .annotations
+            .insert(ANNOTATION_TITLE.to_string(), SNAPSHOT_METADATA.to_string());
+
+        let manifest = Manifest {
+            schema_version: 2,
+            media_type: Some(MANIFEST_MEDIA_TYPE.to_string()),
+            artifact_type: Some(ARTIFACT_TYPE.to_string()),
+            config,
+            layers: vec![archive, metadata],
+            annotations: BTreeMap::from([(
+                ANNOTATION_CREATED.to_string(),
+                Utc::now().to_rfc3339(),
+            )]),
+        };
+
+        // The manifest is pushed last, so a snapshot is only tagged once all of it is there.
+        let manifest = serde_json::to_vec(&manifest)?;
+
+        check(
+            self.send(&reference, true, |client| {
+                client
+                    .put(reference.url(&format!("manifests/{}", reference.reference)))
+                    .header("content-type", MANIFEST_MEDIA_TYPE)
+                    .body(manifest.clone())
+            })
+            .await?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, location: &str) -> anyhow::Result<RemoteDownload> {
+        let reference = self.reference(location)?;
+        let manifest = self.get_snapshot_manifest(&reference).await?;
+
+        let (Some(metadata), Some(archive)) = (
+            manifest.layer(&[METADATA_MEDIA_TYPE]),
+            manifest.layer(&[ARCHIVE_MEDIA_TYPE, COMPRESSED_ARCHIVE_MEDIA_TYPE]),
+        ) else {
+            return Err(anyhow!(
+                "oci://{} is missing the archive or metadata",
+                reference
+            ));
+        };
+
+        let metadata_file = self.get_blob(&reference, metadata).await?.bytes().await?;
+
+        if format_checksum(&Sha256::digest(&metadata_file)) != metadata.digest {
+            return Err(anyhow!(
+                "The metadata of oci://{} does not match its digest",
+                reference
+            ));
+        }
+
+        let archive_name = match archive.media_type.as_str() {
+            media_type if media_type == COMPRESSED_ARCHIVE_MEDIA_TYPE => SNAPSHOT_TAR_ZST,
+            _ => SNAPSHOT_TAR,
+        };
+
+        let archive = self
+            .get_blob(&reference, archive)
+            .await?
+            .bytes_stream()
+            .map(|chunk| Ok(chunk?.to_vec()))
+            .boxed();
+
+        Ok(RemoteDownload {
+            metadata: metadata_file.to_vec(),
+            archive_name: archive_name.to_string(),
+            archive,
+        })
+    }
+
+    /// The snapshots tagged in a repository.
+    async fn list(&self, location: &str) -> anyhow::Result<Vec<RemoteSnapshot>> {
+        let reference = self.reference(location)?;
+        let mut url = Url::parse(&reference.url("tags/list"))?;
+        let mut tags = vec![];
+
+        loop {
+            let response = self
+                .send(&reference, false, |client| client.get(url.clone()))
+                .await?;
+
+            if response.status() == StatusCode::NOT_FOUND {
+                return Ok(vec![]);
+            }
+
+            let response = check(response).await?;
+            let next = response
+                .headers()
+                .get("link")
+                .and_then(|link| link.to_str().ok())
+                .and_then(|link| link.split_once('<'))
+                .and_then(|(_, link)| link.split_once('>'))
+                .map(|(next, _)| next.to_string());
+
+            tags.extend(
+                serde_json::from_slice::<TagList>(&response.bytes().await?)?
+                    .tags
+                    .unwrap_or_default(),
+            );
+
+            match next {
+                Some(next) => url = url.join(&next)?,
+                None => break,
+            }
+        }
+
+        let mut snapshots = vec![];
+
+        for tag in tags {
+            let reference = reference.with_reference(&tag);
+
+            let Some((manifest, _)) = self.get_manifest(&reference).await? else {
+                continue;
+            };
+
+            if !manifest.is_snapshot() {
+                continue;
+            }
+
+            snapshots.push(RemoteSnapshot {
+                remote: reference.to_string(),
+                size: manifest
+                    .layer(&[ARCHIVE_MEDIA_TYPE, COMPRESSED_ARCHIVE_MEDIA_TYPE])
+                    .map(|archive| archive.size),
+                modified_at: manifest
+                    .annotations
+                    .get(ANNOTATION_CREATED)
+                    .and_then(|created| DateTime::parse_from_rfc3339(created).ok())
+                    .map(|created| created.timestamp()),
+            });
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Deletes the manifest, the registry's garbage collection removes the blobs.
+    async fn delete(&self, location: &str) -> anyhow::Result<()> {
+        let reference = self.reference(location)?;
+
+        let digest = match self.get_manifest(&reference).await? {
+            Some((manifest, digest)) if manifest.is_snapshot() => digest,
+            Some(_) => return Err(anyhow!("oci://{} is not a vsnap snapshot", reference)),
+            None => return Err(Error::SnapshotNotFound(format!("oci://{}", reference)).into()),
+        };
+
+        check(
+            self.send(&reference, true, |client| {
+                client.delete(reference.url(&format!("manifests/{}", digest)))
+            })
+            .await?,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
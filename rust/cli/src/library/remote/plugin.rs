@@ -0,0 +1,358 @@
+use std::{
+    env,
+    ffi::OsStr,
+    path::PathBuf,
+    process::{Output, Stdio},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    task::JoinHandle,
+};
+
+use crate::library::{
+    error::Error,
+    metadata::SnapshotMetadata,
+    remote::{RemoteDownload, RemoteSnapshot, RemoteStore, RemoteUpload},
+};
+
+/// Remotes with any other scheme than the built-in ones are handled by the executable named
+/// `vsnap-backend-<scheme>` on `PATH`.
+pub static PLUGIN_PREFIX: &str = "vsnap-backend-";
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The first line a plugin reads from stdin. See the readme for the protocol.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PluginRequest {
+    pub version: u32,
+    #[serde(flatten)]
+    pub operation: PluginOperation,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum PluginOperation {
+    /// Followed by `archive_size` bytes of archive and the end of stdin.
+    Put {
+        location: String,
+        archive_name: String,
+        archive_size: u64,
+        metadata: Value,
+    },
+    Get {
+        location: String,
+    },
+    List {
+        location: String,
+    },
+    Delete {
+        location: String,
+    },
+}
+
+/// The first line a plugin writes to stdout.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PluginResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set along with `error` when there is no snapshot at the location.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_found: bool,
+    /// The metadata of a `get`, the archive follows on stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshots: Option<Vec<RemoteSnapshot>>,
+}
+
+/// The plugin executable for `scheme` in the directories of `path`, a `PATH` like list.
+pub fn find_plugin(scheme: &str, path: &OsStr) -> Option<PathBuf> {
+    let name = format!("{}{}{}", PLUGIN_PREFIX, scheme, env::consts::EXE_SUFFIX);
+
+    env::split_paths(path)
+        .map(|dir| dir.join(&name))
+        .find(|program| program.is_file())
+}
+
+/// A running plugin. Its stderr is read on the side, a chatty plugin would otherwise block on a
+/// full pipe while vsnap blocks on its stdin or stdout.
+struct PluginProcess {
+    child: Child,
+    stderr: JoinHandle<Vec<u8>>,
+}
+
+impl PluginProcess {
+    /// Waits for the plugin to exit, with what is left on stdout and everything on stderr.
+    async fn wait(self) -> anyhow::Result<Output> {
+        let mut output = self.child.wait_with_output().await?;
+
+        output.stderr = self.stderr.await.unwrap_or_default();
+
+        Ok(output)
+    }
+}
+
+/// A remote handled by an external plugin, one plugin process per operation.
+pub struct PluginStore {
+    scheme: String,
+    program: PathBuf,
+}
+
+impl PluginStore {
+    pub fn new(scheme: &str, program: PathBuf) -> Self {
+        PluginStore {
+            scheme: scheme.to_string(),
+            program,
+        }
+    }
+
+    /// Looks for the plugin of `scheme` on `PATH`.
+    pub fn discover(scheme: &str) -> Result<Self, Error> {
+        find_plugin(scheme, &env::var_os("PATH").unwrap_or_default())
+            .map(|program| Self::new(scheme, program))
+            .ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "Unsupported remote {}://, expected s3://, oci:// or a {}{} plugin on PATH",
+                    scheme, PLUGIN_PREFIX, scheme
+                ))
+            })
+    }
+
+    fn name(&self) -> String {
+        format!("{}{}", PLUGIN_PREFIX, self.scheme)
+    }
+
+    /// Starts the plugin and sends it the request, stdin stays open for the archive of a put.
+    async fn spawn(
+        &self,
+        operation: PluginOperation,
+    ) -> anyhow::Result<(PluginProcess, ChildStdin)> {
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run {}: {}", self.program.display(), e))?;
+
+        let mut stdin = child.stdin.take().ok_or(anyhow!("No stdin"))?;
+        let mut stderr = child.stderr.take().ok_or(anyhow!("No stderr"))?;
+
+        let process = PluginProcess {
+            child,
+            stderr: tokio::spawn(async move {
+                let mut content = vec![];
+                stderr.read_to_end(&mut content).await.ok();
+                content
+            }),
+        };
+
+        let mut request = serde_json::to_vec(&PluginRequest {
+            version: PROTOCOL_VERSION,
+            operation,
+        })?;
+        request.push(b'\n');
+
+        // A plugin that exits right away explains why on stderr.
+        if let Err(e) = stdin.write_all(&request).await {
+            let output = process.wait().await?;
+
+            return Err(match output.status.success() {
+                true => e.into(),
+                false => failure(&self.name(), &output),
+            });
+        }
+
+        Ok((process, stdin))
+    }
+
+    /// Waits for the plugin to exit and reads its response.
+    async fn response(
+        &self,
+        location: &str,
+        process: PluginProcess,
+    ) -> anyhow::Result<PluginResponse> {
+        let output = process.wait().await?;
+
+        let response = output
+            .stdout
+            .split(|byte| *byte == b'\n')
+            .next()
+            .and_then(|line| serde_json::from_slice::<PluginResponse>(line).ok());
+
+        match response {
+            Some(response) if response.error.is_some() => self.check(location, response),
+            Some(response) if output.status.success() => Ok(response),
+            _ => Err(failure(&self.name(), &output)),
+        }
+    }
+
+    fn check(&self, location: &str, response: PluginResponse) -> anyhow::Result<PluginResponse> {
+        match (response.not_found, &response.error) {
+            (true, _) => {
+                Err(Error::SnapshotNotFound(format!("{}://{}", self.scheme, location)).into())
+            }
+            (false, Some(error)) => Err(anyhow!("{} failed: {}", self.name(), error)),
+            (false, None) => Ok(response),
+        }
+    }
+}
+
+fn failure(name: &str, output: &Output) -> anyhow::Error {
+    anyhow!(
+        "{} exited with {}: {}",
+        name,
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    )
+}
+
+#[async_trait]
+impl RemoteStore for PluginStore {
+    async fn put(&self, location: &str, upload: RemoteUpload) -> anyhow::Result<()> {
+        let metadata = serde_json::from_slice(&upload.metadata)?;
+
+        let (mut process, mut stdin) = self
+            .spawn(PluginOperation::Put {
+                location: location.to_string(),
+                archive_name: upload.archive_name,
+                archive_size: upload.archive_size,
+                metadata,
+            })
+            .await?;
+
+        let mut archive = upload.archive;
+        let mut write_error = None;
+
+        while let Some(chunk) = archive.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    // Killed before stdin ends, the plugin never completes the snapshot.
+                    process.child.kill().await?;
+
+                    return Err(e);
+                }
+            };
+
+            // The plugin stopped reading, its response tells why.
+            if let Err(e) = stdin.write_all(&chunk).await {
+                write_error = Some(e);
+                break;
+            }
+        }
+
+        drop(stdin);
+
+        self.response(location, process).await?;
+
+        match write_error {
+            Some(e) => Err(anyhow!(
+                "Failed to send the archive to {}: {}",
+                self.name(),
+                e
+            )),
+            None => Ok(()),
+        }
+    }
+
+    async fn get(&self, location: &str) -> anyhow::Result<RemoteDownload> {
+        let (mut process, stdin) = self
+            .spawn(PluginOperation::Get {
+                location: location.to_string(),
+            })
+            .await?;
+
+        drop(stdin);
+
+        let mut stdout = BufReader::new(process.child.stdout.take().ok_or(anyhow!("No stdout"))?);
+        let mut line = String::new();
+
+        stdout.read_line(&mut line).await?;
+
+        let response = match serde_json::from_str::<PluginResponse>(&line) {
+            Ok(response) => self.check(location, response)?,
+            Err(_) => return Err(failure(&self.name(), &process.wait().await?)),
+        };
+
+        let metadata = serde_json::to_vec(
+            &response
+                .metadata
+                .ok_or(anyhow!("{} sent no metadata", self.name()))?,
+        )?;
+
+        let archive_name = serde_json::from_slice::<SnapshotMetadata>(&metadata)
+            .map_err(|e| anyhow!("Invalid metadata in {}://{}: {}", self.scheme, location, e))?
+            .archive_name()
+            .to_string();
+
+        let name = self.name();
+
+        // The archive ends with stdout, and fails when the plugin does.
+        let archive = futures::stream::unfold(Some((stdout, process)), move |state| {
+            let name = name.clone();
+
+            async move {
+                let (mut stdout, process) = state?;
+                let mut chunk = vec![0; CHUNK_SIZE];
+
+                match stdout.read(&mut chunk).await {
+                    Ok(0) => match process.wait().await {
+                        Ok(output) if output.status.success() => None,
+                        Ok(output) => Some((Err(failure(&name, &output)), None)),
+                        Err(e) => Some((Err(e), None)),
+                    },
+                    Ok(read) => {
+                        chunk.truncate(read);
+                        Some((Ok(chunk), Some((stdout, process))))
+                    }
+                    Err(e) => Some((Err(e.into()), None)),
+                }
+            }
+        })
+        .boxed();
+
+        Ok(RemoteDownload {
+            metadata,
+            archive_name,
+            archive,
+        })
+    }
+
+    async fn list(&self, location: &str) -> anyhow::Result<Vec<RemoteSnapshot>> {
+        let (process, stdin) = self
+            .spawn(PluginOperation::List {
+                location: location.to_string(),
+            })
+            .await?;
+
+        drop(stdin);
+
+        Ok(self
+            .response(location, process)
+            .await?
+            .snapshots
+            .unwrap_or_default())
+    }
+
+    async fn delete(&self, location: &str) -> anyhow::Result<()> {
+        let (process, stdin) = self
+            .spawn(PluginOperation::Delete {
+                location: location.to_string(),
+            })
+            .await?;
+
+        drop(stdin);
+
+        self.response(location, process).await.map(|_| ())
+    }
+}
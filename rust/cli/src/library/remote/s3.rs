@@ -0,0 +1,872 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use itertools::Itertools;
+use reqwest::{Client, Method, Response, StatusCode, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::library::{
+    error::Error,
+    metadata::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST, SnapshotMetadata},
+    remote::{RemoteDownload, RemoteSnapshot, RemoteStore, RemoteUpload, read_part},
+};
+
+const DEFAULT_REGION: &str = "us-east-1";
+const DEFAULT_PROFILE: &str = "default";
+
+/// Parts are at least this big, S3 rejects smaller ones except for the last.
+const MIN_PART_SIZE: u64 = 8 * 1024 * 1024;
+
+/// S3 allows 10000 parts, some are left for archives growing while they are uploaded.
+const MAX_PARTS: u64 = 9000;
+
+/// User metadata of `metadata.json` naming the archive object it belongs to. Snapshots pushed
+/// before archives were versioned lack it, their archive is named by `archive_name()`.
+const ARCHIVE_HEADER: &str = "x-amz-meta-vsnap-archive";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// How to reach S3, resolved like the AWS CLI does from `AWS_*` variables and the shared
+/// `~/.aws/credentials` and `~/.aws/config` files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S3Settings {
+    pub credentials: S3Credentials,
+    pub region: String,
+    /// An S3-compatible endpoint such as MinIO, addressed path-style.
+    pub endpoint: Option<String>,
+}
+
+impl S3Settings {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::load(
+            &|name| std::env::var(name).ok(),
+            dirs::home_dir().as_deref(),
+        )
+    }
+
+    /// Environment variables take precedence over the profile selected by `AWS_PROFILE`.
+    pub fn load(env: &dyn Fn(&str) -> Option<String>, home: Option<&Path>) -> anyhow::Result<Self> {
+        let env = |name: &str| env(name).filter(|value| !value.is_empty());
+        let profile = env("AWS_PROFILE").unwrap_or(DEFAULT_PROFILE.to_string());
+
+        let aws_file = |variable: &str, file_name: &str| {
+            env(variable)
+                .map(PathBuf::from)
+                .or_else(|| home.map(|home| home.join(".aws").join(file_name)))
+        };
+
+        let credentials_file = read_profile(
+            aws_file("AWS_SHARED_CREDENTIALS_FILE", "credentials").as_deref(),
+            &profile,
+        )?;
+
+        // The config file names every profile but the default one `profile <name>`.
+        let config_section = match profile.as_str() {
+            DEFAULT_PROFILE => profile.clone(),
+            profile => format!("profile {}", profile),
+        };
+
+        let config_file = read_profile(
+            aws_file("AWS_CONFIG_FILE", "config").as_deref(),
+            &config_section,
+        )?;
+
+        let profile_value = |key: &str| {
+            credentials_file
+                .get(key)
+                .or_else(|| config_file.get(key))
+                .cloned()
+        };
+
+        let credentials = match (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key_id), Some(secret_access_key)) => S3Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: env("AWS_SESSION_TOKEN"),
+            },
+            _ => match (
+                profile_value("aws_access_key_id"),
+                profile_value("aws_secret_access_key"),
+            ) {
+                (Some(access_key_id), Some(secret_access_key)) => S3Credentials {
+                    access_key_id,
+                    secret_access_key,
+                    session_token: profile_value("aws_session_token"),
+                },
+                _ => {
+                    return Err(anyhow!(
+                        "No AWS credentials found, set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY or configure the {} profile",
+                        profile
+                    ));
+                }
+            },
+        };
+
+        Ok(S3Settings {
+            credentials,
+            region: env("AWS_REGION")
+                .or_else(|| env("AWS_DEFAULT_REGION"))
+                .or_else(|| config_file.get("region").cloned())
+                .unwrap_or(DEFAULT_REGION.to_string()),
+            endpoint: env("AWS_ENDPOINT_URL_S3")
+                .or_else(|| env("AWS_ENDPOINT_URL"))
+                .or_else(|| config_file.get("endpoint_url").cloned()),
+        })
+    }
+}
+
+/// The keys of a section of an AWS ini file, nothing if the file doesn't exist.
+fn read_profile(path: Option<&Path>, section: &str) -> anyhow::Result<HashMap<String, String>> {
+    let content = match path {
+        Some(path) if path.exists() => fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?,
+        _ => return Ok(HashMap::new()),
+    };
+
+    let mut current = None;
+    let mut values = HashMap::new();
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        match line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            Some(name) => current = Some(name.trim().to_string()),
+            None => match line.split_once('=') {
+                Some((key, value)) if current.as_deref() == Some(section) => {
+                    values.insert(key.trim().to_string(), value.trim().to_string());
+                }
+                _ => {}
+            },
+        }
+    }
+
+    Ok(values)
+}
+
+/// Percent-encodes everything but unreserved characters, and `/` unless `encode_slash`.
+pub fn uri_encode(value: &str, encode_slash: bool) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            b'/' if !encode_slash => "/".to_string(),
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// What Signature Version 4 signs of an S3 request.
+pub struct CanonicalRequest<'a> {
+    pub method: &'a str,
+    /// The URI encoded path.
+    pub path: &'a str,
+    pub query: &'a [(String, String)],
+    /// Lowercase names, including `host` and every `x-amz-*` header.
+    pub headers: &'a BTreeMap<String, String>,
+    pub payload_hash: &'a str,
+}
+
+/// The `Authorization` header of a Signature Version 4 signed S3 request.
+pub fn authorization(
+    credentials: &S3Credentials,
+    region: &str,
+    request: &CanonicalRequest,
+    time: DateTime<Utc>,
+) -> String {
+    let CanonicalRequest {
+        method,
+        path,
+        query,
+        headers,
+        payload_hash,
+    } = request;
+
+    let date = time.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+
+    let mut query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", uri_encode(key, true), uri_encode(value, true)))
+        .collect::<Vec<_>>();
+
+    query.sort();
+
+    let canonical_headers = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect::<String>();
+
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+
+    let canonical_request = [
+        *method,
+        *path,
+        &query.join("&"),
+        &canonical_headers,
+        &signed_headers,
+        *payload_hash,
+    ]
+    .join("\n");
+
+    let string_to_sign = [
+        "AWS4-HMAC-SHA256",
+        &time.format("%Y%m%dT%H%M%SZ").to_string(),
+        &scope,
+        &sha256_hex(canonical_request.as_bytes()),
+    ]
+    .join("\n");
+
+    let key = [region, "s3", "aws4_request"].iter().fold(
+        hmac_sha256(
+            format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+            &date,
+        ),
+        |key, part| hmac_sha256(&key, part),
+    );
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{},SignedHeaders={},Signature={}",
+        credentials.access_key_id,
+        scope,
+        signed_headers,
+        hex(&hmac_sha256(&key, &string_to_sign))
+    )
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InitiateMultipartUploadResult {
+    upload_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    contents: Vec<ListedObject>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedObject {
+    key: String,
+    size: u64,
+    last_modified: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorResponse {
+    code: Option<String>,
+    message: Option<String>,
+}
+
+/// Snapshots in an S3 bucket, each a folder holding the archive and `metadata.json`.
+pub struct S3Store {
+    client: Client,
+    settings: S3Settings,
+}
+
+impl S3Store {
+    pub fn new(settings: S3Settings) -> Self {
+        S3Store {
+            client: Client::new(),
+            settings,
+        }
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self::new(S3Settings::from_env()?))
+    }
+
+    /// Splits `bucket/path` into the bucket and the key prefix of a snapshot.
+    fn split_location(location: &str) -> anyhow::Result<(&str, &str)> {
+        match location.split_once('/') {
+            Some((bucket, prefix))
+                if !bucket.is_empty() && !prefix.trim_matches('/').is_empty() =>
+            {
+                Ok((bucket, prefix.trim_matches('/')))
+            }
+            _ => Err(Error::InvalidArgument(format!(
+                "Invalid remote s3://{}, expected s3://bucket/path",
+                location
+            ))
+            .into()),
+        }
+    }
+
+    /// The URL of an object, virtual-hosted on AWS and path-style on custom endpoints.
+    fn object_url(&self, bucket: &str, key: &str) -> anyhow::Result<(Url, String)> {
+        let key = uri_encode(key, false);
+
+        let (base, path) = match &self.settings.endpoint {
+            Some(endpoint) => (
+                endpoint.trim_end_matches('/').to_string(),
+                format!("/{}/{}", uri_encode(bucket, true), key),
+            ),
+            // Dotted bucket names don't match the wildcard certificate.
+            None if bucket.contains('.') => (
+                format!("https://s3.{}.amazonaws.com", self.settings.region),
+                format!("/{}/{}", bucket, key),
+            ),
+            None => (
+                format!(
+                    "https://{}.s3.{}.amazonaws.com",
+                    bucket, self.settings.region
+                ),
+                format!("/{}", key),
+            ),
+        };
+
+        let url = Url::parse(&format!("{}{}", base, path))
+            .map_err(|e| anyhow!("Invalid S3 endpoint {}: {}", base, e))?;
+
+        // The signed path is exactly what is sent, including a prefix of the endpoint.
+        let signed_path = url.path().to_string();
+
+        Ok((url, signed_path))
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        query: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> anyhow::Result<Response> {
+        self.send_with_headers(method, bucket, key, query, vec![], body)
+            .await
+    }
+
+    /// Sends a request with extra signed headers, like the `x-amz-copy-source` of a copy.
+    async fn send_with_headers(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        query: Vec<(String, String)>,
+        extra_headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> anyhow::Result<Response> {
+        let (mut url, path) = self.object_url(bucket, key)?;
+        let now = Utc::now();
+        let payload_hash = sha256_hex(&body);
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow!("S3 endpoint {} has no host", url)),
+        };
+
+        let mut headers = BTreeMap::from([
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            (
+                "x-amz-date".to_string(),
+                now.format("%Y%m%dT%H%M%SZ").to_string(),
+            ),
+        ]);
+
+        if let Some(token) = &self.settings.credentials.session_token {
+            headers.insert("x-amz-security-token".to_string(), token.clone());
+        }
+
+        headers.extend(extra_headers);
+
+        let authorization = authorization(
+            &self.settings.credentials,
+            &self.settings.region,
+            &CanonicalRequest {
+                method: method.as_str(),
+                path: &path,
+                query: &query,
+                headers: &headers,
+                payload_hash: &payload_hash,
+            },
+            now,
+        );
+
+        let query_string = query
+            .iter()
+            .map(|(key, value)| format!("{}={}", uri_encode(key, true), uri_encode(value, true)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        url.set_query((!query_string.is_empty()).then_some(query_string.as_str()));
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("authorization", authorization);
+
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(name, value);
+        }
+
+        Ok(request.body(body).send().await?)
+    }
+
+    /// Fails with the S3 error code and message of an unsuccessful response.
+    async fn check(response: Response) -> anyhow::Result<Response> {
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        let error = quick_xml::de::from_str::<ErrorResponse>(&body).unwrap_or_default();
+
+        Err(anyhow!(
+            "S3 request failed with {}: {}",
+            status,
+            match (error.code, error.message) {
+                (Some(code), Some(message)) => format!("{}: {}", code, message),
+                (Some(code), None) => code,
+                _ => body,
+            }
+        ))
+    }
+
+    /// A snapshot's `metadata.json` and the name of the archive object it publishes, if it
+    /// names one.
+    async fn get_metadata(&self, location: &str) -> anyhow::Result<(Vec<u8>, Option<String>)> {
+        let (bucket, prefix) = Self::split_location(location)?;
+        let key = format!("{}/{}", prefix, SNAPSHOT_METADATA);
+
+        let response = self.send(Method::GET, bucket, &key, vec![], vec![]).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::SnapshotNotFound(format!("s3://{}", location)).into());
+        }
+
+        let response = Self::check(response).await?;
+        let archive_object = response
+            .headers()
+            .get(ARCHIVE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        Ok((response.bytes().await?.to_vec(), archive_object))
+    }
+
+    /// Every object below `prefix`, or in the bucket without one, paging through the listing.
+    async fn list_objects(&self, bucket: &str, prefix: &str) -> anyhow::Result<Vec<ListedObject>> {
+        let mut objects = vec![];
+        let mut continuation_token = None;
+
+        loop {
+            let mut query = vec![("list-type".to_string(), "2".to_string())];
+
+            if !prefix.is_empty() {
+                query.push(("prefix".to_string(), format!("{}/", prefix)));
+            }
+
+            if let Some(token) = continuation_token.take() {
+                query.push(("continuation-token".to_string(), token));
+            }
+
+            let response = self.send(Method::GET, bucket, "", query, vec![]).await?;
+            let result: ListBucketResult =
+                quick_xml::de::from_str(&Self::check(response).await?.text().await?)?;
+
+            objects.extend(result.contents);
+
+            match (result.is_truncated, result.next_continuation_token) {
+                (true, Some(token)) => continuation_token = Some(token),
+                _ => return Ok(objects),
+            }
+        }
+    }
+
+    /// Deletes the archives in a snapshot's folder, but the one `metadata.json` publishes.
+    async fn delete_archives(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        keep: Option<&str>,
+    ) -> anyhow::Result<()> {
+        for object in self.list_objects(bucket, prefix).await? {
+            let Some((folder, name)) = object.key.rsplit_once('/') else {
+                continue;
+            };
+
+            if folder == prefix && is_archive(name) && Some(name) != keep {
+                self.delete_object(bucket, &object.key).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_object(&self, bucket: &str, key: &str) -> anyhow::Result<()> {
+        let response = self
+            .send(Method::DELETE, bucket, key, vec![], vec![])
+            .await?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            _ => Self::check(response).await.map(|_| ()),
+        }
+    }
+
+    /// Uploads the archive in parts as it streams in, or in one request if it fits in one.
+    /// Returns the size of the uploaded object.
+    async fn upload_archive(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload: &mut RemoteUpload,
+    ) -> anyhow::Result<u64> {
+        let part_size = MIN_PART_SIZE.max(upload.archive_size / MAX_PARTS) as usize;
+
+        let first_part = read_part(&mut upload.archive, part_size).await?;
+
+        if first_part.len() < part_size {
+            let size = first_part.len() as u64;
+            let response = self
+                .send(Method::PUT, bucket, key, vec![], first_part)
+                .await?;
+
+            return Self::check(response).await.map(|_| size);
+        }
+
+        let upload_id = self.initiate_upload(bucket, key).await?;
+
+        let result = async {
+            let (etags, size) = self
+                .upload_parts(bucket, key, &upload_id, first_part, upload, part_size)
+                .await?;
+
+            self.complete_upload(bucket, key, &upload_id, &etags)
+                .await
+                .map(|_| size)
+        }
+        .await;
+
+        if result.is_err() {
+            self.abort_upload(bucket, key, &upload_id).await;
+        }
+
+        result
+    }
+
+    async fn upload_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        first_part: Vec<u8>,
+        upload: &mut RemoteUpload,
+        part_size: usize,
+    ) -> anyhow::Result<(Vec<String>, u64)> {
+        let mut etags = vec![];
+        let mut size = 0;
+        let mut part = first_part;
+
+        while !part.is_empty() {
+            let last = part.len() < part_size;
+            let query = vec![
+                ("partNumber".to_string(), (etags.len() + 1).to_string()),
+                ("uploadId".to_string(), upload_id.to_string()),
+            ];
+
+            size += part.len() as u64;
+
+            let response =
+                Self::check(self.send(Method::PUT, bucket, key, query, part).await?).await?;
+
+            let etag = response
+                .headers()
+                .get("etag")
+                .and_then(|etag| etag.to_str().ok())
+                .ok_or(anyhow!(
+                    "S3 did not return an ETag for part {}",
+                    etags.len() + 1
+                ))?;
+
+            etags.push(etag.to_string());
+
+            part = match last {
+                true => vec![],
+                false => read_part(&mut upload.archive, part_size).await?,
+            };
+        }
+
+        Ok((etags, size))
+    }
+
+    async fn initiate_upload(&self, bucket: &str, key: &str) -> anyhow::Result<String> {
+        let response = self
+            .send(
+                Method::POST,
+                bucket,
+                key,
+                vec![("uploads".to_string(), String::new())],
+                vec![],
+            )
+            .await?;
+
+        let initiated: InitiateMultipartUploadResult =
+            quick_xml::de::from_str(&Self::check(response).await?.text().await?)?;
+
+        Ok(initiated.upload_id)
+    }
+
+    async fn complete_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        etags: &[String],
+    ) -> anyhow::Result<()> {
+        let parts = etags
+            .iter()
+            .enumerate()
+            .map(|(index, etag)| {
+                format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                    index + 1,
+                    quick_xml::escape::escape(etag.as_str())
+                )
+            })
+            .collect::<String>();
+
+        let body = format!(
+            "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+            parts
+        );
+
+        let query = vec![("uploadId".to_string(), upload_id.to_string())];
+        let response = Self::check(
+            self.send(Method::POST, bucket, key, query, body.into_bytes())
+                .await?,
+        )
+        .await?;
+
+        Self::check_body(response, "complete the upload").await
+    }
+
+    /// Best effort, S3 also drops incomplete uploads by the bucket's lifecycle rules.
+    async fn abort_upload(&self, bucket: &str, key: &str, upload_id: &str) {
+        let query = vec![("uploadId".to_string(), upload_id.to_string())];
+
+        self.send(Method::DELETE, bucket, key, query, vec![])
+            .await
+            .ok();
+    }
+
+    /// Completing an upload can fail after S3 already answered 200, the error is in the body
+    /// then.
+    async fn check_body(response: Response, action: &str) -> anyhow::Result<()> {
+        let body = response.text().await?;
+
+        match body.contains("<Error>") {
+            true => {
+                let error = quick_xml::de::from_str::<ErrorResponse>(&body).unwrap_or_default();
+
+                Err(anyhow!(
+                    "S3 failed to {}: {}",
+                    action,
+                    error.message.or(error.code).unwrap_or(body)
+                ))
+            }
+            false => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl RemoteStore for S3Store {
+    async fn put(&self, location: &str, mut upload: RemoteUpload) -> anyhow::Result<()> {
+        let (bucket, prefix) = Self::split_location(location)?;
+
+        // Uploaded under a name of its own and published by the metadata naming it last, so a
+        // failed or interrupted push leaves the snapshot it replaces intact.
+        let archive_object = format!(
+            "{}.{}",
+            upload.archive_name,
+            Utc::now().format("%Y%m%d%H%M%S%9f")
+        );
+        let archive_key = format!("{}/{}", prefix, archive_object);
+
+        self.upload_archive(bucket, &archive_key, &mut upload)
+            .await?;
+
+        let response = self
+            .send_with_headers(
+                Method::PUT,
+                bucket,
+                &format!("{}/{}", prefix, SNAPSHOT_METADATA),
+                vec![],
+                vec![(ARCHIVE_HEADER.to_string(), archive_object)],
+                upload.metadata,
+            )
+            .await?;
+
+        // Without an answer the metadata may have been written, the archive is then left for
+        // the next push or delete to clean up.
+        if let Err(e) = Self::check(response).await {
+            return Err(match self.delete_object(bucket, &archive_key).await {
+                Ok(()) => e,
+                Err(cleanup) => anyhow!(
+                    "{:#}, and deleting the uploaded archive {} failed: {:#}",
+                    e,
+                    archive_key,
+                    cleanup
+                ),
+            });
+        }
+
+        // Whatever is published now, in case another push to the same location came last.
+        let (_, published) = self.get_metadata(location).await?;
+
+        self.delete_archives(bucket, prefix, published.as_deref())
+            .await
+    }
+
+    async fn get(&self, location: &str) -> anyhow::Result<RemoteDownload> {
+        let (bucket, prefix) = Self::split_location(location)?;
+        let (metadata, archive_object) = self.get_metadata(location).await?;
+
+        let archive_name = serde_json::from_slice::<SnapshotMetadata>(&metadata)
+            .map_err(|e| anyhow!("Invalid metadata in s3://{}: {}", location, e))?
+            .archive_name()
+            .to_string();
+
+        let response = self
+            .send(
+                Method::GET,
+                bucket,
+                &format!(
+                    "{}/{}",
+                    prefix,
+                    archive_object.as_deref().unwrap_or(&archive_name)
+                ),
+                vec![],
+                vec![],
+            )
+            .await?;
+
+        let archive = Self::check(response)
+            .await?
+            .bytes_stream()
+            .map(|chunk| Ok(chunk?.to_vec()))
+            .boxed();
+
+        Ok(RemoteDownload {
+            metadata,
+            archive_name,
+            archive,
+        })
+    }
+
+    async fn list(&self, location: &str) -> anyhow::Result<Vec<RemoteSnapshot>> {
+        let (bucket, prefix) = match location.split_once('/') {
+            Some((bucket, prefix)) => (bucket, prefix.trim_matches('/')),
+            None => (location, ""),
+        };
+
+        if bucket.is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid remote s3://{}, expected s3://bucket or s3://bucket/path",
+                location
+            ))
+            .into());
+        }
+
+        let objects = self.list_objects(bucket, prefix).await?;
+
+        // Folders hold one archive, unless a push is running or was interrupted, the newest is
+        // the one it publishes then.
+        let archive_sizes = objects
+            .iter()
+            .filter_map(|object| {
+                let (folder, name) = object.key.rsplit_once('/')?;
+
+                is_archive(name).then_some((folder, object))
+            })
+            .sorted_by(|(_, a), (_, b)| a.last_modified.cmp(&b.last_modified))
+            .map(|(folder, object)| (folder.to_string(), object.size))
+            .collect::<HashMap<_, _>>();
+
+        Ok(objects
+            .iter()
+            .filter_map(|object| {
+                let folder = object
+                    .key
+                    .strip_suffix(SNAPSHOT_METADATA)?
+                    .strip_suffix('/')?;
+
+                Some(RemoteSnapshot {
+                    remote: format!("{}/{}", bucket, folder),
+                    size: archive_sizes.get(folder).copied(),
+                    modified_at: DateTime::parse_from_rfc3339(&object.last_modified)
+                        .ok()
+                        .map(|time| time.timestamp()),
+                })
+            })
+            .collect())
+    }
+
+    async fn delete(&self, location: &str) -> anyhow::Result<()> {
+        let (bucket, prefix) = Self::split_location(location)?;
+
+        // Fails with not found before anything is deleted.
+        self.get_metadata(location).await?;
+        self.delete_object(bucket, &format!("{}/{}", prefix, SNAPSHOT_METADATA))
+            .await?;
+
+        // Including what interrupted pushes left behind.
+        self.delete_archives(bucket, prefix, None).await
+    }
+}
+
+/// Whether `name` is an archive in a snapshot's folder, named like before archives were
+/// versioned or with a version appended.
+fn is_archive(name: &str) -> bool {
+    [SNAPSHOT_TAR, SNAPSHOT_TAR_ZST].iter().any(|archive| {
+        name.strip_prefix(archive)
+            .is_some_and(|version| version.is_empty() || version.starts_with('.'))
+    })
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::anyhow;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Tells apart the temporary files of saves running at the same time in this process.
+static SAVE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Reads a JSON file vsnap keeps its state in, like the config or the journal, or the default
+/// state when there is none yet. `kind` names the file in errors.
+pub fn load<T: DeserializeOwned + Default>(path: &Path, kind: &str) -> anyhow::Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let content = fs::read_to_string(path)?;
+
+    serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {} {}: {}", kind, path.display(), e))
+}
+
+/// Writes a temporary file next to `path` and renames it into place, so that readers never see
+/// a half-written state.
+pub fn save<T: Serialize>(state: &T, path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = sibling(
+        path,
+        &format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            SAVE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+
+    fs::write(&temp_path, serde_json::to_string_pretty(state)?)?;
+
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        fs::remove_file(&temp_path).ok();
+    })?;
+
+    Ok(())
+}
+
+/// Reads, changes and saves the state while holding an exclusive lock on a `.lock` file next to
+/// it, so that vsnap processes updating it at the same time don't lose each other's changes.
+pub fn update<T: Serialize + DeserializeOwned + Default, R>(
+    path: &Path,
+    kind: &str,
+    change: impl FnOnce(&mut T) -> R,
+) -> anyhow::Result<R> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // The state file itself is replaced on every save, so the lock lives in a file of its own.
+    let lock_path = sibling(path, "lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| anyhow!("Failed to open {}: {}", lock_path.display(), e))?;
+
+    lock.lock()
+        .map_err(|e| anyhow!("Failed to lock {}: {}", lock_path.display(), e))?;
+
+    let mut state = load(path, kind)?;
+    let result = change(&mut state);
+
+    save(&state, path)?;
+
+    Ok(result)
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `config.json.lock`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(suffix);
+
+    path.with_file_name(file_name)
+}
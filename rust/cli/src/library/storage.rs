@@ -0,0 +1,145 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use anyhow::anyhow;
+use regex::Regex;
+
+use crate::library::{
+    backend::{MountSource, VolumeSpec},
+    constant::{LOCK_PREFIX, SNAPSHOT_PREFIX},
+    directory::SnapshotDirectory,
+    error::Error,
+    lock::LockTarget,
+};
+
+static DEFAULT_STORAGE: LazyLock<SnapshotStorage> = LazyLock::new(SnapshotStorage::default);
+
+static VOLUME_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_.-]*$").expect("Failed to compile volume name regex")
+});
+
+/// Placeholder for the volume name in snapshot driver options.
+pub static VOLUME_PLACEHOLDER: &str = "{volume}";
+
+/// Where snapshot volumes live: the prefix that namespaces their names, so that projects sharing
+/// a host only see their own snapshots, and the driver they are created with. With a directory,
+/// snapshots are kept below it instead of in volumes, named the same way.
+#[derive(Clone, Debug)]
+pub struct SnapshotStorage {
+    prefix: String,
+    snapshot_regex: Regex,
+    pub driver: Option<String>,
+    pub driver_opts: HashMap<String, String>,
+    pub directory: Option<SnapshotDirectory>,
+}
+
+impl SnapshotStorage {
+    /// Snapshot volumes are named `<prefix><timestamp>-<snapshot>`.
+    pub fn new(prefix: &str) -> Result<Self, Error> {
+        if !VOLUME_NAME_REGEX.is_match(prefix) {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid snapshot prefix {}, expected letters, digits, '_', '.' or '-'",
+                prefix
+            )));
+        }
+
+        let escaped = regex::escape(prefix);
+
+        Ok(SnapshotStorage {
+            prefix: prefix.to_string(),
+            snapshot_regex: Regex::new(&format!(r"^{}(\d{{10,}})-", escaped))
+                .expect("Failed to compile snapshot prefix regex"),
+            driver: None,
+            driver_opts: HashMap::new(),
+            directory: None,
+        })
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn snapshot_lock(&self, snapshot_name: &str) -> LockTarget {
+        LockTarget::Snapshot {
+            prefix: self.prefix.clone(),
+            name: snapshot_name.to_string(),
+        }
+    }
+
+    pub fn snapshot_volume_name(&self, timestamp: i64, snapshot_name: &str) -> String {
+        format!("{}{}-{}", self.prefix, timestamp, snapshot_name)
+    }
+
+    pub fn is_snapshot_volume(&self, volume_name: &str) -> bool {
+        self.snapshot_regex.is_match(volume_name)
+    }
+
+    pub fn snapshot_name(&self, snapshot_volume_name: &str) -> String {
+        self.snapshot_regex
+            .replace(snapshot_volume_name, "")
+            .to_string()
+    }
+
+    pub fn snapshot_timestamp(&self, snapshot_volume_name: &str) -> anyhow::Result<i64> {
+        let captures = self
+            .snapshot_regex
+            .captures(snapshot_volume_name)
+            .ok_or(anyhow!(
+                "Failed to extract timestamp from volume name: {}",
+                snapshot_volume_name
+            ))?;
+
+        Ok(captures[1].parse::<i64>()?)
+    }
+
+    /// Restored and cloned volumes must not look like snapshots or locks. Snapshot names of the
+    /// default prefix stay reserved under any other prefix, so that switching back finds no
+    /// strangers among its snapshots.
+    pub fn verify_not_reserved(&self, volume_name: &str) -> Result<(), Error> {
+        let looks_like_snapshot = [self, &DEFAULT_STORAGE]
+            .into_iter()
+            .any(|storage| storage.is_snapshot_volume(volume_name));
+
+        let reason = match (looks_like_snapshot, volume_name.starts_with(LOCK_PREFIX)) {
+            (true, _) => "named like snapshots".to_string(),
+            (false, true) => format!("prefixed with {}", LOCK_PREFIX),
+            (false, false) => return Ok(()),
+        };
+
+        Err(Error::VolumeProtected(format!(
+            "{} (volumes {} are reserved for vsnap)",
+            volume_name, reason
+        )))
+    }
+
+    pub fn is_reserved(&self, volume_name: &str) -> bool {
+        self.verify_not_reserved(volume_name).is_err()
+    }
+
+    /// What runners mount to read or write the snapshot volume `volume_name`.
+    pub fn mount_source(&self, volume_name: &str) -> MountSource {
+        match &self.directory {
+            Some(directory) => directory.mount_source(volume_name),
+            None => MountSource::Volume(volume_name.to_string()),
+        }
+    }
+
+    /// How to create the snapshot volume `volume_name`. `{volume}` in driver options is
+    /// replaced by the volume name, so that e.g. bind mounted volumes get a directory each.
+    pub fn volume_spec(&self, volume_name: &str, labels: HashMap<String, String>) -> VolumeSpec {
+        VolumeSpec {
+            driver: self.driver.clone(),
+            driver_opts: self
+                .driver_opts
+                .iter()
+                .map(|(key, value)| (key.clone(), value.replace(VOLUME_PLACEHOLDER, volume_name)))
+                .collect(),
+            labels,
+        }
+    }
+}
+
+impl Default for SnapshotStorage {
+    fn default() -> Self {
+        SnapshotStorage::new(SNAPSHOT_PREFIX).expect("The default snapshot prefix is valid")
+    }
+}
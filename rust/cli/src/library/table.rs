@@ -1,20 +1,21 @@
-use std::collections::HashMap;
-
 use console::style;
+use indicatif::HumanBytes;
+use itertools::Itertools;
 use tabled::{
     builder::Builder,
     settings::{Style, Theme},
 };
 
-use crate::library::docker::{VolumeSize, extract_snapshot_datetime, strip_snapshot_prefix};
+use crate::library::{
+    docker::snapshot_datetime,
+    listing::SnapshotEntry,
+    metadata::{ChecksumState, SnapshotDetails},
+};
 
-pub fn print_snapshot_table(
-    snapshot_volume_names: Vec<String>,
-    volume_sizes: Option<HashMap<String, VolumeSize>>,
-) -> anyhow::Result<()> {
-    let mut header = vec!["Snapshot Name", "Local Datetime"];
+pub fn snapshot_table(entries: &[SnapshotEntry], include_size: bool) -> String {
+    let mut header = vec!["Snapshot Name", "Local Datetime", "Age"];
 
-    if volume_sizes.is_some() {
+    if include_size {
         header.push("Size");
     }
 
@@ -28,22 +29,19 @@ pub fn print_snapshot_table(
     let mut builder = Builder::default();
     builder.push_record(header);
 
-    for snapshot_volume_name in snapshot_volume_names {
-        let snapshot_name = strip_snapshot_prefix(&snapshot_volume_name);
-        let local_datetime = extract_snapshot_datetime(&snapshot_volume_name)?;
-
-        let mut record: Vec<String> = vec![snapshot_name, local_datetime.to_string()];
+    for entry in entries {
+        let mut record: Vec<String> = vec![entry.name.clone(), entry.local_datetime(), entry.age()];
 
-        if let Some(volume_size) = &volume_sizes {
-            let size = match volume_size.get(&snapshot_volume_name) {
-                Some(VolumeSize::Bytes(size)) => (size / 1024 / 1024).to_string() + " MB",
-                _ => "Unavailable".to_string(),
+        if include_size {
+            let size = match entry.size {
+                Some(size) => HumanBytes(size).to_string(),
+                None => "Unavailable".to_string(),
             };
 
             record.push(size);
         }
 
-        record.push(snapshot_volume_name);
+        record.push(entry.volume_name.clone());
         builder.push_record(record);
     }
 
@@ -54,7 +52,112 @@ pub fn print_snapshot_table(
 
     table.with(style);
 
-    println!("{}", table.to_string());
+    table.to_string()
+}
+
+pub fn print_snapshot_details(details: &SnapshotDetails) -> anyhow::Result<()> {
+    let metadata = details.inspection.metadata.as_ref();
+    let unknown = || "Unknown".to_string();
+    let size = |bytes: u64| format!("{} ({} bytes)", HumanBytes(bytes), bytes);
+
+    let checksum = match details.inspection.checksum {
+        ChecksumState::Unavailable => "Unavailable".to_string(),
+        ChecksumState::Unverified => "Not verified (use --verify)".to_string(),
+        ChecksumState::Valid => style("Valid").green().to_string(),
+        ChecksumState::Invalid => style("Invalid").red().to_string(),
+    };
+
+    let records = vec![
+        ("Snapshot Name", details.name.clone()),
+        ("Volume Name", details.volume_name.clone()),
+        (
+            "Local Datetime",
+            snapshot_datetime(details.created_at)?.to_string(),
+        ),
+        ("Pinned", details.pinned.to_string()),
+        (
+            "Source Volume",
+            metadata
+                .and_then(|m| m.source_volume.clone())
+                .unwrap_or_else(unknown),
+        ),
+        (
+            "Compression",
+            match metadata {
+                Some(m) => m.compression.clone().unwrap_or("None".to_string()),
+                None => unknown(),
+            },
+        ),
+        (
+            "Original Size",
+            metadata.map(|m| size(m.total_size)).unwrap_or_else(unknown),
+        ),
+        (
+            "Archive Size",
+            details
+                .inspection
+                .archive_size
+                .map(size)
+                .unwrap_or_else(unknown),
+        ),
+        (
+            "Files",
+            metadata
+                .and_then(|m| m.file_count)
+                .map(|count| count.to_string())
+                .unwrap_or_else(unknown),
+        ),
+        (
+            "Directories",
+            metadata
+                .and_then(|m| m.dir_count)
+                .map(|count| count.to_string())
+                .unwrap_or_else(unknown),
+        ),
+        (
+            "Tool Version",
+            metadata
+                .and_then(|m| m.tool_version.clone())
+                .unwrap_or_else(unknown),
+        ),
+        (
+            "Metadata Version",
+            metadata
+                .map(|m| m.version.to_string())
+                .unwrap_or("Missing".to_string()),
+        ),
+        ("Checksum", checksum),
+        (
+            "Parent",
+            details.parent.clone().unwrap_or("None".to_string()),
+        ),
+        (
+            "Dependents",
+            match details.dependents.is_empty() {
+                true => "None".to_string(),
+                false => details.dependents.join(", "),
+            },
+        ),
+        (
+            "Labels",
+            details
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .join("\n"),
+        ),
+    ];
+
+    let mut builder = Builder::default();
+
+    for (key, value) in records {
+        builder.push_record(vec![style(key).green().bold().to_string(), value]);
+    }
+
+    let mut table = builder.build();
+    table.with(Style::blank());
+
+    println!("{}", table);
 
     Ok(())
 }
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+
+use crate::library::{
+    backend::{VolumeInfo, VolumeSpec},
+    constant::{LABEL_SOURCE_DRIVER, LABEL_SOURCE_DRIVER_OPTS, LABEL_SOURCE_LABELS},
+    error::Error,
+    pattern::matches_pattern,
+};
+
+/// Changes to the driver, driver options and labels a restored volume inherits from the volume
+/// its snapshot was taken of.
+#[derive(Clone, Debug, Default)]
+pub struct VolumeOverrides {
+    pub driver: Option<String>,
+    pub driver_opts: HashMap<String, String>,
+    /// Driver options to leave out, by name or glob pattern.
+    pub strip_driver_opts: Vec<String>,
+    pub labels: HashMap<String, String>,
+    /// Labels to leave out, by name or glob pattern like `com.docker.compose.*`.
+    pub strip_labels: Vec<String>,
+}
+
+impl VolumeOverrides {
+    pub fn apply(&self, mut spec: VolumeSpec) -> VolumeSpec {
+        let stripped = |patterns: &[String], key: &str| {
+            patterns.iter().any(|pattern| matches_pattern(pattern, key))
+        };
+
+        spec.driver_opts
+            .retain(|key, _| !stripped(&self.strip_driver_opts, key));
+        spec.labels
+            .retain(|key, _| !stripped(&self.strip_labels, key));
+
+        spec.driver_opts.extend(self.driver_opts.clone());
+        spec.labels.extend(self.labels.clone());
+
+        if self.driver.is_some() {
+            spec.driver = self.driver.clone();
+        }
+
+        spec
+    }
+
+    /// Fails when the snapshotted volume was backed by a device, like the host directory of a
+    /// bind mounted local volume, and the overrides don't say what to do with it, if restored
+    /// volumes would share the device with another volume and write into its data. Restoring
+    /// over the snapshotted volume itself is fine.
+    pub fn verify_device(
+        &self,
+        snapshot_name: &str,
+        recorded: &VolumeSpec,
+        restore_volume_names: &[String],
+        volumes: &[VolumeInfo],
+    ) -> Result<(), Error> {
+        let device = match recorded.driver_opts.get("type").map(String::as_str) {
+            Some("tmpfs") => None,
+            _ => recorded.driver_opts.get("device"),
+        };
+
+        let overridden = self.driver_opts.contains_key("device")
+            || self
+                .strip_driver_opts
+                .iter()
+                .any(|pattern| matches_pattern(pattern, "device"));
+
+        let Some(device) = device.filter(|_| !overridden) else {
+            return Ok(());
+        };
+
+        let sharing = volumes.iter().find(|volume| {
+            volume.driver_opts.get("device") == Some(device)
+                && !restore_volume_names.contains(&volume.name)
+        });
+
+        let conflict = match (sharing, restore_volume_names.len()) {
+            (Some(volume), _) => format!("volume {} still uses", volume.name),
+            (None, 1) => return Ok(()),
+            (None, _) => "every restored volume would share".to_string(),
+        };
+
+        Err(Error::InvalidArgument(format!(
+            "Snapshot {} was taken of a volume backed by {}, which {}. Pass --driver-opt \
+             device=<path> for another location or --strip-driver-opt '*' for a plain volume",
+            snapshot_name, device, conflict
+        )))
+    }
+}
+
+/// Labels that record how `volume` was created, for its snapshots.
+pub fn source_spec_labels(volume: &VolumeInfo) -> anyhow::Result<HashMap<String, String>> {
+    Ok(HashMap::from([
+        (LABEL_SOURCE_DRIVER.to_string(), volume.driver.clone()),
+        (
+            LABEL_SOURCE_DRIVER_OPTS.to_string(),
+            serde_json::to_string(&volume.driver_opts)?,
+        ),
+        (
+            LABEL_SOURCE_LABELS.to_string(),
+            serde_json::to_string(&volume.labels)?,
+        ),
+    ]))
+}
+
+/// How the snapshotted volume was created, read from the labels of its snapshot. Snapshots from
+/// before these were recorded restore to a volume with the engine's defaults.
+pub fn recorded_source_spec(snapshot_labels: &HashMap<String, String>) -> VolumeSpec {
+    let map = |key: &str| {
+        snapshot_labels
+            .get(key)
+            .and_then(|value| serde_json::from_str(value).ok())
+            .unwrap_or_default()
+    };
+
+    VolumeSpec {
+        driver: snapshot_labels
+            .get(LABEL_SOURCE_DRIVER)
+            .filter(|driver| !driver.is_empty())
+            .cloned(),
+        driver_opts: map(LABEL_SOURCE_DRIVER_OPTS),
+        labels: map(LABEL_SOURCE_LABELS),
+    }
+}
+
+/// Parses a `KEY=VALUE` argument.
+pub fn parse_key_value(value: &str) -> anyhow::Result<(String, String)> {
+    value
+        .split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or(anyhow!("Invalid value {}, expected KEY=VALUE", value))
+}
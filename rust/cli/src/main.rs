@@ -1,8 +1,8 @@
-mod library;
+use std::process::ExitCode;
 
-use crate::library::cli::run;
+use vsnap::library::cli::run;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> ExitCode {
     run().await
 }
@@ -0,0 +1,58 @@
+mod common;
+
+use anyhow::Result;
+use common::{create_client, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::Backend,
+    batch::run_batch,
+    client::{CreateOptions, DropOptions, ListOptions},
+};
+
+#[tokio::test]
+async fn test_batch() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    for volume_name in ["a", "b", "c"] {
+        backend
+            .create_volume(volume_name, Default::default())
+            .await?;
+        write_files(&backend.volume_path(volume_name))?;
+    }
+
+    let targets = ["a", "b", "c", "missing"]
+        .map(|volume_name| (volume_name.to_string(), volume_name.to_string()))
+        .to_vec();
+
+    let result = run_batch(targets, 2, |source_volume| {
+        client.create(CreateOptions {
+            snapshot: format!("{}-snap", source_volume),
+            source_volume,
+            ..Default::default()
+        })
+    })
+    .await;
+
+    assert_eq!((result.succeeded, result.failed), (3, 1));
+    assert_eq!(result.items[3].target, "missing");
+    assert_eq!(
+        result.items[3].error.as_ref().map(|error| error.kind),
+        Some("volume_not_found")
+    );
+    assert_eq!(client.list(ListOptions::default()).await?.len(), 3);
+
+    let dropped = client
+        .drop(DropOptions {
+            all: true,
+            parallelism: 2,
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(dropped.dropped.len(), 3);
+    assert!(dropped.failed.is_empty());
+    assert!(client.list(ListOptions::default()).await?.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use vsnap::library::binary::{is_static_elf, static_elf_arch};
+
+fn elf_with_program_headers(types: &[u32]) -> Vec<u8> {
+    elf_for_machine(0x3e, types)
+}
+
+fn elf_for_machine(machine: u16, types: &[u32]) -> Vec<u8> {
+    let mut content = vec![0; 0x40 + types.len() * 0x38];
+
+    content[..6].copy_from_slice(b"\x7fELF\x02\x01");
+    content[0x12..0x14].copy_from_slice(&machine.to_le_bytes());
+    content[0x20..0x28].copy_from_slice(&0x40u64.to_le_bytes());
+    content[0x36..0x38].copy_from_slice(&0x38u16.to_le_bytes());
+    content[0x38..0x3a].copy_from_slice(&(types.len() as u16).to_le_bytes());
+
+    for (index, typ) in types.iter().enumerate() {
+        let offset = 0x40 + index * 0x38;
+        content[offset..offset + 4].copy_from_slice(&typ.to_le_bytes());
+    }
+
+    content
+}
+
+#[test]
+fn test_is_static_elf() -> Result<()> {
+    // PT_LOAD only, like a static musl binary.
+    assert!(is_static_elf(&elf_with_program_headers(&[1, 1])));
+
+    // PT_INTERP names a dynamic loader.
+    assert!(!is_static_elf(&elf_with_program_headers(&[6, 3, 1])));
+
+    assert!(!is_static_elf(b"#!/bin/sh\n"));
+    assert!(!is_static_elf(&elf_with_program_headers(&[1])[..0x30]));
+
+    // Program headers pointing past the end of the address space.
+    let mut content = elf_with_program_headers(&[1, 1]);
+    content[0x20..0x28].copy_from_slice(&(u64::MAX - 0x10).to_le_bytes());
+    assert!(!is_static_elf(&content));
+
+    Ok(())
+}
+
+#[test]
+fn test_static_elf_arch() -> Result<()> {
+    assert_eq!(static_elf_arch(&elf_for_machine(0x3e, &[1])), Some("amd64"));
+    assert_eq!(static_elf_arch(&elf_for_machine(0xb7, &[1])), Some("arm64"));
+
+    // i386 binaries can't be 64-bit, and s390x is big-endian.
+    assert_eq!(static_elf_arch(&elf_for_machine(0x03, &[1])), None);
+    assert_eq!(static_elf_arch(&elf_for_machine(0x16, &[1])), None);
+
+    Ok(())
+}
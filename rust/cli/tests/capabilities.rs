@@ -0,0 +1,147 @@
+mod common;
+
+use std::{
+    fs,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::{Result, anyhow};
+use common::{create_client, create_client_with_runner, run, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::{Backend, fake::FakeBackend},
+    capabilities::PROTOCOL_VERSION,
+    client::{CreateOptions, InspectOptions, RestoreOptions, VsnapClient},
+    error::Error,
+    metadata::METADATA_VERSION,
+};
+
+#[tokio::test]
+async fn test_capabilities_handshake() -> Result<()> {
+    let root = tempdir()?;
+    let (client, _) = create_client(&root)?;
+
+    let capabilities = client.runner_capabilities().await?;
+
+    assert_eq!(capabilities.protocol_version, PROTOCOL_VERSION);
+    assert!(capabilities.commands.contains(&"copy".to_string()));
+
+    // A runner from before the handshake rejects the subcommand and can't copy snapshots.
+    let root = tempdir()?;
+    let (client, backend) = create_client_with_runner(
+        &root,
+        Arc::new(|args, input, output| match args[0].as_str() {
+            "snapshot" | "restore" => run(args, input, output),
+            command => Err(anyhow!("error: unrecognized subcommand '{}'", command)),
+        }),
+    )?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let result = client
+        .inspect("snap", InspectOptions { verify: false })
+        .await;
+
+    assert!(matches!(result, Err(Error::RunnerIncompatible(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_checks_metadata_version() -> Result<()> {
+    // Snapshots are created by this runner, then it's swapped for one from a newer vsnap that
+    // only understands snapshot metadata this vsnap doesn't know.
+    let newer = Arc::new(AtomicBool::new(false));
+    let root = tempdir()?;
+    let (client, backend) = create_client_with_runner(&root, {
+        let newer = newer.clone();
+
+        Arc::new(move |args, input, output| {
+            match (args[0].as_str(), newer.load(Ordering::SeqCst)) {
+                ("capabilities", true) => Ok(writeln!(
+                    output,
+                    r#"{{"protocol_version":{},"commands":["snapshot","restore"],"metadata_versions":[{}]}}"#,
+                    PROTOCOL_VERSION,
+                    METADATA_VERSION + 1
+                )?),
+                _ => run(args, input, output),
+            }
+        })
+    })?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    // Capabilities are cached per client.
+    newer.store(true, Ordering::SeqCst);
+    let client = VsnapClient::with_backend(backend.clone())?
+        .with_config_path(root.path().join("config.json"));
+
+    let result = client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "restored".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(matches!(result, Err(Error::RunnerIncompatible(_))));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_runner_binary_arch() -> Result<()> {
+    let root = tempdir()?;
+    let volumes_dir = root.path().join("volumes");
+    fs::create_dir_all(&volumes_dir)?;
+
+    // A static arm64 binary with only a PT_LOAD program header.
+    let mut binary = vec![0; 0x40 + 0x38];
+    binary[..6].copy_from_slice(b"\x7fELF\x02\x01");
+    binary[0x12..0x14].copy_from_slice(&0xb7u16.to_le_bytes());
+    binary[0x20..0x28].copy_from_slice(&0x40u64.to_le_bytes());
+    binary[0x36..0x38].copy_from_slice(&0x38u16.to_le_bytes());
+    binary[0x38..0x3a].copy_from_slice(&1u16.to_le_bytes());
+    binary[0x40..0x44].copy_from_slice(&1u32.to_le_bytes());
+    fs::write(root.path().join("vsnap-runner"), binary)?;
+
+    let backend = Arc::new(FakeBackend::new(&volumes_dir, Arc::new(run)).with_arch("amd64"));
+    let client = VsnapClient::with_backend(backend.clone())?
+        .with_config_path(root.path().join("config.json"))
+        .with_runner_binary(root.path().join("vsnap-runner"));
+
+    backend.create_volume("source", Default::default()).await?;
+
+    let result = client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(matches!(result, Err(Error::RunnerIncompatible(message)) if message.contains("arm64")));
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+//! A minimal HTTP/1.1 server for the fakes of remote stores, enough for reqwest.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+pub struct Request {
+    pub method: String,
+    /// Percent-decoded.
+    pub path: String,
+    /// Percent-decoded, in order.
+    pub query: Vec<(String, String)>,
+    /// Lowercase names.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+}
+
+/// Serves `handler` on a free local port until the test ends, returns `http://127.0.0.1:<port>`.
+pub async fn serve(
+    handler: impl Fn(Request) -> Response + Send + Sync + 'static,
+) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = format!("http://{}", listener.local_addr()?);
+    let handler = Arc::new(handler);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+
+            tokio::spawn(async move { connection(stream, handler.as_ref()).await.ok() });
+        }
+    });
+
+    Ok(address)
+}
+
+async fn connection(
+    stream: TcpStream,
+    handler: &(dyn Fn(Request) -> Response + Send + Sync),
+) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    loop {
+        let mut line = String::new();
+
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+
+        let mut request_line = line.split_whitespace();
+        let method = request_line.next().ok_or(anyhow!("No method"))?.to_string();
+        let target = request_line.next().ok_or(anyhow!("No target"))?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        let mut headers = HashMap::new();
+
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await?;
+
+            match line.trim_end().split_once(':') {
+                Some((name, value)) => {
+                    headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                }
+                None => break,
+            }
+        }
+
+        let length = headers
+            .get("content-length")
+            .map(|length| length.parse::<usize>())
+            .transpose()?
+            .unwrap_or(0);
+
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await?;
+
+        let response = handler(Request {
+            method,
+            path: percent_decode(path),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (percent_decode(key), percent_decode(value))
+                })
+                .collect(),
+            headers,
+            body,
+        });
+
+        let mut head = format!(
+            "HTTP/1.1 {} Fake\r\ncontent-length: {}\r\n",
+            response.status,
+            response.body.len()
+        );
+
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        head.push_str("\r\n");
+
+        let stream = stream.get_mut();
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&response.body).await?;
+    }
+}
+
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = vec![];
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
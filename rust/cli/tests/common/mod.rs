@@ -0,0 +1,87 @@
+//! A client on the fake backend, running the real runner in-process, and fakes of the services
+//! vsnap talks to.
+
+// Each test crate uses only part of it.
+#![allow(dead_code)]
+
+pub mod http;
+
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{Result, anyhow};
+use clap::Parser;
+use futures::StreamExt;
+use tempfile::TempDir;
+use vsnap::library::{
+    backend::fake::{FakeBackend, FakeRunner},
+    client::VsnapClient,
+    metadata::{SNAPSHOT_TAR, SnapshotMetadata},
+    remote::RemoteUpload,
+};
+use vsnap_runner::library::cli::{Cli, execute};
+
+/// Runs a runner command like the runner container would.
+pub fn run(args: Vec<String>, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+    let cli = Cli::try_parse_from(std::iter::once("vsnap-runner".to_string()).chain(args))?;
+
+    execute(cli.command, input, output)
+}
+
+pub fn create_client(root: &TempDir) -> Result<(VsnapClient, Arc<FakeBackend>)> {
+    create_client_with_runner(root, Arc::new(run))
+}
+
+/// Like `create_client`, with runner commands executed by `runner`, e.g. to slow them down or
+/// to pretend to be an older runner.
+pub fn create_client_with_runner(
+    root: &TempDir,
+    runner: FakeRunner,
+) -> Result<(VsnapClient, Arc<FakeBackend>)> {
+    let volumes_dir = root.path().join("volumes");
+    fs::create_dir_all(&volumes_dir)?;
+
+    let backend = Arc::new(FakeBackend::new(&volumes_dir, runner));
+
+    let client = VsnapClient::with_backend(backend.clone())?
+        .with_config_path(root.path().join("config.json"));
+
+    Ok((client, backend))
+}
+
+/// Uploads `archive` to a remote store in a few chunks, failing at the end like a checksum
+/// mismatch when `fail` is set.
+pub fn upload(archive: &[u8], fail: bool) -> RemoteUpload {
+    let metadata = SnapshotMetadata {
+        archive_size: Some(archive.len() as u64),
+        ..SnapshotMetadata::new(archive.len() as u64)
+    };
+
+    let mut chunks = archive
+        .chunks(4096)
+        .map(|chunk| Ok(chunk.to_vec()))
+        .collect::<Vec<_>>();
+
+    if fail {
+        chunks.push(Err(anyhow!("Archive checksum does not match")));
+    }
+
+    RemoteUpload {
+        metadata: serde_json::to_vec(&metadata).unwrap(),
+        archive_name: SNAPSHOT_TAR.to_string(),
+        archive_size: archive.len() as u64,
+        archive: futures::stream::iter(chunks).boxed(),
+    }
+}
+
+pub fn write_files(path: &Path) -> Result<()> {
+    fs::create_dir_all(path.join("nested"))?;
+    fs::write(path.join("a.txt"), "first file")?;
+    fs::write(path.join("nested").join("b.txt"), "second file")?;
+
+    Ok(())
+}
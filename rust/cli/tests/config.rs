@@ -0,0 +1,123 @@
+use std::thread;
+
+use tempfile::tempdir;
+use vsnap::library::{
+    config::Config,
+    pattern::{expand_template, matches_pattern, matches_template},
+    storage::SnapshotStorage,
+};
+
+#[test]
+fn test_matches_pattern() {
+    assert!(matches_pattern("prod-db", "prod-db"));
+    assert!(matches_pattern("prod-*", "prod-db"));
+    assert!(matches_pattern("*-db", "prod-db"));
+    assert!(matches_pattern("prod-d?", "prod-db"));
+    assert!(matches_pattern("*", "anything"));
+
+    assert!(!matches_pattern("prod-*", "dev-db"));
+    assert!(!matches_pattern("prod", "prod-db"));
+    assert!(!matches_pattern("prod.db", "prod-db"));
+}
+
+#[test]
+fn test_name_templates() {
+    assert_eq!(
+        expand_template("test-db-{i}", 1, 3).unwrap(),
+        vec![
+            "test-db-1".to_string(),
+            "test-db-2".to_string(),
+            "test-db-3".to_string()
+        ]
+    );
+    assert!(expand_template("test-db", 1, 3).is_err());
+    assert!(expand_template("test-db-{i}", 1, 0).is_err());
+
+    assert!(matches_template("test-db-{i}", "test-db-12"));
+    assert!(matches_template("{i}.db", "0.db"));
+    assert!(!matches_template("test-db-{i}", "test-db-"));
+    assert!(!matches_template("test-db-{i}", "test-db-1-old"));
+    assert!(!matches_template("{i}.db", "0-db"));
+    assert!(!matches_template("test-db", "test-db"));
+}
+
+#[test]
+fn test_pin_and_protect() {
+    let mut config = Config::default();
+
+    assert!(config.pin("vsnap-1741900000-a"));
+    assert!(!config.pin("vsnap-1741900000-a"));
+    assert!(config.is_pinned("vsnap-1741900000-a"));
+    assert!(!config.is_pinned("vsnap-1741900000-b"));
+    assert!(config.unpin("vsnap-1741900000-a"));
+    assert!(!config.is_pinned("vsnap-1741900000-a"));
+
+    assert!(config.protect("prod-*"));
+    assert!(!config.protect("prod-*"));
+    assert!(config.is_protected("prod-db"));
+    assert!(!config.is_protected("dev-db"));
+    assert!(config.unprotect("prod-*"));
+    assert!(!config.is_protected("prod-db"));
+}
+
+#[test]
+fn test_concurrent_config_updates() {
+    let root = tempdir().unwrap();
+    let path = root.path().join("config.json");
+
+    thread::scope(|scope| {
+        for thread in 0..8 {
+            let path = &path;
+
+            scope.spawn(move || {
+                for snapshot in 0..10 {
+                    Config::update_at(path, |config| {
+                        config.pin(&format!("vsnap-1741900000-{}-{}", thread, snapshot))
+                    })
+                    .unwrap();
+                }
+            });
+        }
+    });
+
+    // No pin is lost, and only the config and its lock are left behind.
+    assert_eq!(Config::load_from(&path).unwrap().pinned_snapshots.len(), 80);
+    assert_eq!(root.path().read_dir().unwrap().count(), 2);
+}
+
+#[test]
+fn test_snapshot_prefix() {
+    let default = SnapshotStorage::default();
+    let project = SnapshotStorage::new("vsnap-shop-").unwrap();
+
+    assert_eq!(
+        project.snapshot_volume_name(1741900000, "a"),
+        "vsnap-shop-1741900000-a"
+    );
+    assert!(project.is_snapshot_volume("vsnap-shop-1741900000-a"));
+    assert!(!project.is_snapshot_volume("vsnap-1741900000-a"));
+    assert!(!default.is_snapshot_volume("vsnap-shop-1741900000-a"));
+    assert_eq!(project.snapshot_name("vsnap-shop-1741900000-a"), "a");
+    assert_eq!(
+        project
+            .snapshot_timestamp("vsnap-shop-1741900000-a")
+            .unwrap(),
+        1741900000
+    );
+
+    // Names of snapshots, of either prefix, and of locks.
+    assert!(project.is_reserved("vsnap-shop-1741900000-db"));
+    assert!(project.is_reserved("vsnap-1741900000-db"));
+    assert!(project.is_reserved("vsnap-lock-volume-db"));
+    assert!(!project.is_reserved("vsnap-shop-db"));
+    assert!(!project.is_reserved("vsnap-db"));
+    assert!(!project.is_reserved("shop-db"));
+
+    assert_ne!(
+        project.snapshot_lock("a").volume_name(),
+        default.snapshot_lock("a").volume_name()
+    );
+
+    assert!(SnapshotStorage::new("").is_err());
+    assert!(SnapshotStorage::new("shop/").is_err());
+}
@@ -0,0 +1,93 @@
+use std::fs;
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use tempfile::tempdir;
+use vsnap::library::connection::{ConnectionOptions, resolve_endpoint};
+
+#[test]
+fn test_resolve_endpoint() -> Result<()> {
+    let docker_config = tempdir()?;
+    let digest = format!("{:x}", Sha256::digest("build-box"));
+
+    let meta_dir = docker_config
+        .path()
+        .join("contexts")
+        .join("meta")
+        .join(&digest);
+    let tls_dir = docker_config
+        .path()
+        .join("contexts")
+        .join("tls")
+        .join(&digest)
+        .join("docker");
+
+    fs::create_dir_all(&meta_dir)?;
+    fs::create_dir_all(&tls_dir)?;
+    fs::write(
+        meta_dir.join("meta.json"),
+        r#"{"Name":"build-box","Metadata":{},"Endpoints":{"docker":{"Host":"tcp://build-box:2376","SkipTLSVerify":false}}}"#,
+    )?;
+    fs::write(tls_dir.join("ca.pem"), "")?;
+    fs::write(
+        docker_config.path().join("config.json"),
+        r#"{"currentContext":"build-box"}"#,
+    )?;
+
+    let options = ConnectionOptions {
+        docker_config: Some(docker_config.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let endpoint = resolve_endpoint(&options)?;
+
+    assert_eq!(endpoint.host.as_deref(), Some("tcp://build-box:2376"));
+    assert!(!endpoint.is_local());
+    assert_eq!(endpoint.tls.map(|tls| tls.ca), Some(tls_dir.join("ca.pem")));
+    assert!(!endpoint.podman);
+
+    let endpoint = resolve_endpoint(&ConnectionOptions {
+        host: Some("unix:///run/user/1000/podman/podman.sock".to_string()),
+        ..options.clone()
+    })?;
+
+    assert_eq!(
+        endpoint.host.as_deref(),
+        Some("unix:///run/user/1000/podman/podman.sock")
+    );
+    assert!(endpoint.tls.is_none());
+    assert!(endpoint.podman);
+    assert!(endpoint.is_local());
+
+    let endpoint = resolve_endpoint(&ConnectionOptions {
+        context: Some("default".to_string()),
+        ..options.clone()
+    })?;
+
+    assert!(endpoint.host.is_none());
+    assert!(endpoint.is_local());
+
+    for (host, local) in [
+        ("tcp://127.0.0.1:2375", true),
+        ("tcp://localhost:2375", true),
+        ("ssh://user@build-box", false),
+        ("ssh://user@localhost", false),
+    ] {
+        let endpoint = resolve_endpoint(&ConnectionOptions {
+            host: Some(host.to_string()),
+            ..options.clone()
+        })?;
+
+        assert_eq!(endpoint.is_local(), local, "{}", host);
+    }
+
+    assert!(
+        resolve_endpoint(&ConnectionOptions {
+            context: Some("missing".to_string()),
+            ..options
+        })
+        .is_err()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,379 @@
+mod common;
+
+use std::{fs, sync::Arc, thread, time::Duration};
+
+use anyhow::Result;
+use common::{create_client, create_client_with_runner, run, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::Backend,
+    cancel::{CancelReason, Cancellation},
+    client::{
+        CreateOptions, DropOptions, ExistingVolume, InspectOptions, ListOptions, RestoreOptions,
+    },
+    config::Config,
+    error::Error,
+    gc::GarbageKind,
+    journal::Journal,
+    metadata::ChecksumState,
+    storage::SnapshotStorage,
+};
+
+#[tokio::test]
+async fn test_create_list_restore_drop() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    let created = client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            compress: true,
+            ..Default::default()
+        })
+        .await?;
+
+    let snapshots = client.list(ListOptions::default()).await?;
+
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].name, "snap");
+    assert_eq!(snapshots[0].volume_name, created.volume_name);
+    assert_eq!(snapshots[0].source.as_deref(), Some("source"));
+
+    let details = client
+        .inspect("snap", InspectOptions { verify: true })
+        .await?;
+
+    assert_eq!(details.inspection.checksum, ChecksumState::Valid);
+    assert_eq!(
+        details.labels.get("vsnap.source").map(String::as_str),
+        Some("source")
+    );
+    assert!(details.labels.keys().is_sorted());
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "restored".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let restored = backend.volume_path("restored");
+
+    assert_eq!(fs::read_to_string(restored.join("a.txt"))?, "first file");
+    assert_eq!(
+        fs::read_to_string(restored.join("nested").join("b.txt"))?,
+        "second file"
+    );
+
+    // Pinned, each operation says how to get past it.
+    client.pin("snap", true).await?;
+
+    let restore_error = client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "restored-again".to_string(),
+            drop_snapshot: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    let drop_error = client
+        .drop(DropOptions {
+            snapshots: vec!["snap".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+
+    assert!(matches!(restore_error, Error::SnapshotPinned { .. }));
+    assert!(restore_error.to_string().contains("vsnap unpin snap"));
+    assert!(matches!(drop_error, Error::SnapshotPinned { .. }));
+    assert!(drop_error.to_string().contains("--force"));
+
+    let dropped = client
+        .drop(DropOptions {
+            all: true,
+            ..Default::default()
+        })
+        .await?;
+
+    assert!(dropped.dropped.is_empty());
+    assert_eq!(dropped.skipped, vec!["snap".to_string()]);
+
+    client.pin("snap", false).await?;
+
+    let dropped = client
+        .drop(DropOptions {
+            snapshots: vec!["snap".to_string()],
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(dropped.dropped, vec!["snap".to_string()]);
+    assert!(client.list(ListOptions::default()).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conflicts() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    backend.add_container("database", "running", &["source"]);
+
+    let result = client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(matches!(result, Err(Error::VolumeInUse { .. })));
+
+    backend.remove_container("database").await?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let result = client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "source".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(matches!(result, Err(Error::VolumeExists(_))));
+
+    fs::write(backend.volume_path("source").join("a.txt"), "changed")?;
+
+    let restored = client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "source".to_string(),
+            existing_volume: ExistingVolume::Replace,
+            ..Default::default()
+        })
+        .await?;
+
+    assert!(restored.replaced_volume);
+    assert_eq!(
+        fs::read_to_string(backend.volume_path("source").join("a.txt"))?,
+        "first file"
+    );
+
+    // A snapshot that can't fit is refused before the volume it replaces is dropped.
+    let snapshot_volume = client.list(ListOptions::default()).await?[0]
+        .volume_name
+        .clone();
+    let metadata_path = backend.volume_path(&snapshot_volume).join("metadata.json");
+    let mut metadata: serde_json::Value = serde_json::from_slice(&fs::read(&metadata_path)?)?;
+
+    metadata["total_size"] = serde_json::json!(u64::MAX / 2);
+    fs::write(&metadata_path, serde_json::to_vec(&metadata)?)?;
+
+    let result = client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "source".to_string(),
+            existing_volume: ExistingVolume::Replace,
+            ..Default::default()
+        })
+        .await;
+
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Not enough disk space")
+    );
+    assert!(backend.volume_path("source").join("a.txt").exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_force() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+    let options = CreateOptions {
+        source_volume: "source".to_string(),
+        snapshot: "snap".to_string(),
+        ..Default::default()
+    };
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    let first = client.create(options.clone()).await?;
+    client.pin("snap", true).await?;
+
+    fs::write(backend.volume_path("source").join("a.txt"), "replaced")?;
+
+    // Written next to the old snapshot, even within the same second, which is dropped last.
+    let second = client
+        .create(CreateOptions {
+            force: true,
+            ..options.clone()
+        })
+        .await?;
+
+    assert!(second.replaced);
+    assert_ne!(second.volume_name, first.volume_name);
+    assert!(!client.volume_exists(&first.volume_name).await);
+
+    let entries = client.list(ListOptions::default()).await?;
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].volume_name, second.volume_name);
+    assert!(entries[0].pinned);
+
+    // A replacement that is still being written is not picked up, a complete one is.
+    let storage = SnapshotStorage::default();
+    let timestamp = storage.snapshot_timestamp(&second.volume_name)?;
+    let pending = storage.snapshot_volume_name(timestamp + 1, "snap");
+    let journal_path = Journal::path(&root.path().join("config.json"));
+
+    backend.create_volume(&pending, Default::default()).await?;
+
+    let mut journal = Journal::load_from(&journal_path)?;
+    journal.start_create(&pending, "snap");
+    journal.save_to(&journal_path)?;
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "restored".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(
+        fs::read_to_string(backend.volume_path("restored").join("a.txt"))?,
+        "replaced"
+    );
+
+    // Interrupted before the old snapshot was dropped, gc drops it and keeps the pin.
+    backend.remove_volume(&pending).await?;
+    Journal::default().save_to(&journal_path)?;
+
+    let third = client
+        .create(CreateOptions {
+            force: true,
+            ..options
+        })
+        .await?;
+    backend
+        .create_volume(&second.volume_name, Default::default())
+        .await?;
+
+    for entry in fs::read_dir(backend.volume_path(&third.volume_name))? {
+        let entry = entry?;
+        fs::copy(
+            entry.path(),
+            backend
+                .volume_path(&second.volume_name)
+                .join(entry.file_name()),
+        )?;
+    }
+
+    let config_path = root.path().join("config.json");
+    let mut config = Config::load_from(&config_path)?;
+    config.unpin(&third.volume_name);
+    config.pin(&second.volume_name);
+    config.save_to(&config_path)?;
+
+    let mut journal = Journal::load_from(&journal_path)?;
+    journal.start_replace(&second.volume_name, &third.volume_name);
+    journal.save_to(&journal_path)?;
+
+    // The replacement is complete, so the snapshot already resolves to it.
+    let inspected = client
+        .inspect("snap", InspectOptions { verify: false })
+        .await?;
+
+    assert_eq!(inspected.volume_name, third.volume_name);
+
+    let garbage = client.find_garbage().await?;
+
+    assert_eq!(garbage.len(), 1);
+    assert_eq!(garbage[0].kind, GarbageKind::ReplacedSnapshot);
+    assert_eq!(garbage[0].name, second.volume_name);
+
+    client.collect_garbage(garbage).await?;
+
+    let entries = client.list(ListOptions::default()).await?;
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].volume_name, third.volume_name);
+    assert!(entries[0].pinned);
+    assert!(Journal::load_from(&journal_path)?.replaces.is_empty());
+
+    // Duplicates the journal doesn't explain are an error instead of a guess.
+    backend
+        .create_volume(&second.volume_name, Default::default())
+        .await?;
+
+    assert!(
+        client
+            .inspect("snap", InspectOptions { verify: false })
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancelled_create_is_rolled_back() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client_with_runner(
+        &root,
+        Arc::new(|args, input, output| {
+            if args[0] == "snapshot" {
+                thread::sleep(Duration::from_millis(200));
+            }
+
+            run(args, input, output)
+        }),
+    )?;
+
+    let cancellation = Cancellation::default();
+    let client = client.with_cancellation(cancellation.clone());
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancellation.cancel(CancelReason::TimedOut);
+    });
+
+    let result = client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(matches!(result, Err(Error::TimedOut)));
+    assert!(client.list(ListOptions::default()).await?.is_empty());
+
+    Ok(())
+}
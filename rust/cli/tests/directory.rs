@@ -0,0 +1,20 @@
+use std::path::Path;
+
+use anyhow::Result;
+use vsnap::library::directory::SnapshotDirectory;
+
+#[test]
+fn test_home_directory() -> Result<()> {
+    let home = dirs::home_dir().unwrap();
+
+    assert_eq!(
+        SnapshotDirectory::new(Path::new("~/.local/share/vsnap"))?.root(),
+        home.join(".local/share/vsnap")
+    );
+    assert_eq!(
+        SnapshotDirectory::new(Path::new("/srv/snapshots/~"))?.root(),
+        Path::new("/srv/snapshots/~")
+    );
+
+    Ok(())
+}
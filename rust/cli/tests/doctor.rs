@@ -0,0 +1,36 @@
+mod common;
+
+use anyhow::Result;
+use common::create_client;
+use tempfile::tempdir;
+use vsnap::library::{backend::Backend, doctor::CheckStatus};
+
+#[tokio::test]
+async fn test_doctor() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend
+        .create_volume("vsnap-1741900000-broken", Default::default())
+        .await?;
+
+    let report = client.doctor().await;
+    let status = |name: &str| {
+        report
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .map(|check| check.status)
+    };
+
+    assert_eq!(status("engine"), Some(CheckStatus::Pass));
+    assert_eq!(status("runner_version"), Some(CheckStatus::Pass));
+    assert!(status("disk_space").is_some());
+    assert_eq!(status("snapshots"), Some(CheckStatus::Warn));
+    assert_eq!(status("orphaned_containers"), Some(CheckStatus::Pass));
+
+    // The throwaway volume for measuring free space is removed again.
+    assert_eq!(backend.list_volumes().await?.len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use vsnap::library::error::Error;
+
+#[test]
+fn test_error_exit_codes() {
+    let error = Error::from(anyhow::Error::from(Error::SnapshotNotFound(
+        "a".to_string(),
+    )));
+
+    assert!(matches!(error, Error::SnapshotNotFound(_)));
+    assert_eq!(error.exit_code(), 3);
+    assert_eq!(error.kind(), "snapshot_not_found");
+
+    let error = Error::from(anyhow::Error::from(
+        bollard::errors::Error::SocketNotFoundError("/var/run/docker.sock".to_string()),
+    ));
+
+    assert!(matches!(error, Error::DockerUnavailable(_)));
+    assert_eq!(error.exit_code(), 9);
+
+    let error = Error::from(anyhow::anyhow!("Something else"));
+
+    assert!(matches!(error, Error::Other(_)));
+    assert_eq!(error.exit_code(), 1);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use anyhow::Result;
+use common::{create_client, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::Backend,
+    client::{CreateOptions, ListOptions},
+    constant::LABEL_RUNNER,
+    gc::GarbageKind,
+    journal::Journal,
+    lock::LockOwner,
+};
+
+#[tokio::test]
+async fn test_gc() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "good".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    backend
+        .create_volume("vsnap-1741900000-broken", Default::default())
+        .await?;
+    backend.add_labeled_container(
+        "vsnap-1741900001",
+        "exited",
+        &[],
+        &[(LABEL_RUNNER, "0.6.0")],
+    );
+    backend.add_labeled_container(
+        "vsnap-1741900002",
+        "running",
+        &[],
+        &[(LABEL_RUNNER, "0.6.0")],
+    );
+
+    // Created by this process and about to start.
+    let mut owned = LockOwner::current("runner").labels();
+    owned.insert(LABEL_RUNNER.to_string(), "0.6.0".to_string());
+
+    backend.add_labeled_container(
+        "vsnap-runner-1741900003",
+        "created",
+        &[],
+        &owned
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>(),
+    );
+
+    // Runners from before they were labeled, and a container that only looks like one.
+    backend.add_image_container(
+        "vsnap-1741900004",
+        "fominv/vsnap:0.5.0",
+        "created",
+        &[],
+        &[],
+    );
+    backend.add_image_container("vsnap-1741900005", "postgres:17", "exited", &[], &[]);
+
+    // A restore that was killed before it could finish or roll back.
+    backend.create_volume("half", Default::default()).await?;
+
+    // A create that was killed, even if its runner got to write the whole snapshot.
+    backend
+        .create_volume("vsnap-1741900006-killed", Default::default())
+        .await?;
+
+    let mut journal = Journal::default();
+    journal.start_restore("half", "good");
+    journal.start_create("vsnap-1741900006-killed", "killed");
+    journal.save_to(&Journal::path(&root.path().join("config.json")))?;
+
+    let garbage = client.find_garbage().await?;
+    let mut found = garbage
+        .iter()
+        .map(|item| (item.kind, item.name.as_str()))
+        .collect::<Vec<_>>();
+    found.sort_by_key(|(_, name)| name.to_string());
+
+    assert_eq!(
+        found,
+        vec![
+            (GarbageKind::RestoredVolume, "half"),
+            (GarbageKind::Snapshot, "vsnap-1741900000-broken"),
+            (GarbageKind::Container, "vsnap-1741900001"),
+            (GarbageKind::Container, "vsnap-1741900004"),
+            (GarbageKind::Snapshot, "vsnap-1741900006-killed"),
+        ]
+    );
+
+    let result = client.collect_garbage(garbage).await?;
+
+    assert_eq!(result.removed.len(), 5);
+    assert!(client.find_garbage().await?.is_empty());
+    assert!(!client.volume_exists("half").await);
+    assert_eq!(client.list(ListOptions::default()).await?.len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use vsnap::library::listing::{
+    ListFilter, ListFormat, SnapshotEntry, SortKey, format_age, parse_duration, parse_since,
+    render_entries, sort_entries,
+};
+
+fn entry(name: &str, created_at: i64, size: Option<u64>) -> SnapshotEntry {
+    SnapshotEntry {
+        name: name.to_string(),
+        volume_name: format!("vsnap-{}-{}", created_at, name),
+        created_at,
+        source: Some("source-volume".to_string()),
+        size,
+        pinned: false,
+    }
+}
+
+#[test]
+fn test_render_machine_formats() -> anyhow::Result<()> {
+    let entries = vec![
+        entry("a", 1741900000, Some(1536)),
+        entry("b,c", 1741900001, None),
+    ];
+
+    assert_eq!(
+        render_entries(&entries, &"names".parse()?, false)?,
+        "a\nb,c"
+    );
+
+    let csv = render_entries(&entries, &ListFormat::Csv, false)?;
+    let lines = csv.lines().collect::<Vec<_>>();
+
+    assert_eq!(
+        lines[0],
+        "name,volume_name,created_at,age,source,size,pinned"
+    );
+    assert!(lines[1].starts_with("a,vsnap-1741900000-a,1741900000,"));
+    assert!(lines[1].ends_with(",source-volume,1536,false"));
+    assert!(lines[2].starts_with("\"b,c\",\"vsnap-1741900001-b,c\","));
+
+    let json: serde_json::Value =
+        serde_json::from_str(&render_entries(&entries, &ListFormat::Json, false)?)?;
+
+    assert_eq!(json[0]["size"], 1536);
+    assert_eq!(json[1]["size"], serde_json::Value::Null);
+
+    Ok(())
+}
+
+#[test]
+fn test_render_table() -> anyhow::Result<()> {
+    let entries = vec![
+        entry("a", 1741900000, Some(1536)),
+        entry("b", 1741900001, None),
+    ];
+
+    let table = render_entries(&entries, &"table".parse()?, true)?;
+
+    // The header, its separator and a row per snapshot.
+    assert_eq!(table.lines().count(), 4);
+    assert!(table.contains("1.50 KiB"));
+    assert!(table.contains("Unavailable"));
+    assert!(table.contains("vsnap-1741900001-b"));
+    assert!(!render_entries(&entries, &ListFormat::Table, false)?.contains("Unavailable"));
+    assert_eq!(
+        render_entries(&[], &ListFormat::Table, false)?,
+        "No snapshots found."
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_render_template() -> anyhow::Result<()> {
+    let entries = vec![entry("a", 1741900000, Some(1536))];
+
+    let format: ListFormat = "{{.Name}}\\t{{ .Size }}".parse()?;
+    assert_eq!(render_entries(&entries, &format, false)?, "a\t1536");
+
+    let format: ListFormat = "{{.Unknown}}".parse()?;
+    assert!(render_entries(&entries, &format, false).is_err());
+
+    assert!("yaml".parse::<ListFormat>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_filter_and_sort() -> anyhow::Result<()> {
+    let mut entries = vec![
+        entry("b", 1741900002, Some(10)),
+        entry("a", 1741900003, Some(30)),
+        entry("c", 1741900001, Some(20)),
+    ];
+
+    sort_entries(&mut entries, SortKey::Name);
+    assert_eq!(
+        render_entries(&entries, &ListFormat::Names, false)?,
+        "a\nb\nc"
+    );
+
+    sort_entries(&mut entries, SortKey::Date);
+    assert_eq!(
+        render_entries(&entries, &ListFormat::Names, false)?,
+        "c\nb\na"
+    );
+
+    sort_entries(&mut entries, SortKey::Size);
+    assert_eq!(
+        render_entries(&entries, &ListFormat::Names, false)?,
+        "b\nc\na"
+    );
+
+    let filter = ListFilter {
+        since: Some(1741900002),
+        pattern: Some("[ab]*".to_string()),
+        ..Default::default()
+    };
+
+    assert!(!filter.matches(&entries[0]));
+    assert!(!filter.matches(&entries[1]));
+    assert!(!filter.matches(&entries[2]));
+
+    let filter = ListFilter {
+        source: Some("source-volume".to_string()),
+        since: Some(1741900002),
+        pattern: Some("?".to_string()),
+    };
+
+    assert!(filter.matches(&entries[0]));
+    assert!(!filter.matches(&entries[1]));
+
+    Ok(())
+}
+
+#[test]
+fn test_since_and_age() -> anyhow::Result<()> {
+    let now = chrono::Utc::now().timestamp();
+
+    assert!((parse_since("2h")? - (now - 7200)).abs() <= 1);
+    assert_eq!(parse_since("2025-03-14T00:00:00Z")?, 1741910400);
+    assert!(parse_since("yesterday").is_err());
+
+    assert_eq!(parse_duration("10m")?.as_secs(), 600);
+    assert_eq!(parse_duration("45")?.as_secs(), 45);
+    assert!(parse_duration("soon").is_err());
+
+    assert_eq!(format_age(30), "30 seconds ago");
+    assert_eq!(format_age(60), "1 minute ago");
+    assert_eq!(format_age(60 * 60 * 50), "2 days ago");
+
+    Ok(())
+}
@@ -0,0 +1,277 @@
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use common::{create_client, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::Backend,
+    cancel::Cancellation,
+    client::{CreateOptions, ListOptions},
+    error::Error,
+    gc::GarbageKind,
+    lock::{LockMode, LockOwner, acquire_locks, find_live_lock, take_over},
+    progress::no_progress,
+    storage::SnapshotStorage,
+};
+
+#[tokio::test]
+async fn test_locks() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+    let client = client.with_lock_wait(false);
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    let options = CreateOptions {
+        source_volume: "source".to_string(),
+        snapshot: "snap".to_string(),
+        ..Default::default()
+    };
+    let lock_volume_name = SnapshotStorage::default()
+        .snapshot_lock("snap")
+        .volume_name();
+
+    // Held by a live process, this one.
+    backend
+        .create_volume(
+            &lock_volume_name,
+            LockOwner::current("restore").labels().into(),
+        )
+        .await?;
+
+    assert!(matches!(
+        client.create(options.clone()).await,
+        Err(Error::Locked(_))
+    ));
+    assert!(client.list(ListOptions::default()).await?.is_empty());
+
+    // Held by a process on this host that no longer exists.
+    let stale = LockOwner {
+        pid: i32::MAX as u32,
+        ..LockOwner::current("restore")
+    };
+
+    backend.remove_volume(&lock_volume_name).await?;
+    backend
+        .create_volume(&lock_volume_name, stale.labels().into())
+        .await?;
+
+    let garbage = client.find_garbage().await?;
+
+    assert_eq!(garbage.len(), 1);
+    assert_eq!(garbage[0].kind, GarbageKind::Lock);
+
+    client.create(options.clone()).await?;
+
+    assert!(!client.volume_exists(&lock_volume_name).await);
+
+    // Waits until the holder releases the lock.
+    let client = client.with_lock_wait(true);
+
+    backend
+        .create_volume(
+            &lock_volume_name,
+            LockOwner::current("create").labels().into(),
+        )
+        .await?;
+
+    let release = {
+        let backend = backend.clone();
+        let lock_volume_name = lock_volume_name.clone();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            backend.remove_volume(&lock_volume_name).await
+        })
+    };
+
+    client
+        .create(CreateOptions {
+            force: true,
+            ..options
+        })
+        .await?;
+    release.await??;
+
+    assert_eq!(client.list(ListOptions::default()).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stale_lock_takeover() -> Result<()> {
+    let root = tempdir()?;
+    let (_, backend) = create_client(&root)?;
+    let target = SnapshotStorage::default().snapshot_lock("snap");
+    let stale = LockOwner {
+        pid: i32::MAX as u32,
+        ..LockOwner::current("restore")
+    };
+
+    // Another process found the lock stale too, but took it over first.
+    let winner = LockOwner::current("create");
+    let loser = LockOwner::current("restore");
+
+    backend
+        .create_volume(&target.volume_name(), winner.labels().into())
+        .await?;
+
+    assert!(take_over(backend.as_ref(), &loser, &target.volume_name(), &stale).await?);
+    assert_eq!(
+        find_live_lock(backend.as_ref(), &target).await?,
+        Some(winner.clone())
+    );
+
+    // Only one process takes over at a time.
+    let takeover_volume_name = format!("{}-takeover", target.volume_name());
+
+    backend.remove_volume(&target.volume_name()).await?;
+    backend
+        .create_volume(&target.volume_name(), stale.labels().into())
+        .await?;
+    backend
+        .create_volume(&takeover_volume_name, winner.labels().into())
+        .await?;
+
+    assert!(!take_over(backend.as_ref(), &loser, &target.volume_name(), &stale).await?);
+    assert!(
+        backend
+            .inspect_volume(&target.volume_name())
+            .await?
+            .is_some()
+    );
+
+    backend.remove_volume(&takeover_volume_name).await?;
+
+    // Processes that find the same stale lock at the same time never both end up holding it.
+    let acquire = || {
+        let backend = backend.clone();
+        let target = target.clone();
+
+        tokio::spawn(async move {
+            acquire_locks(
+                backend.as_ref(),
+                "create",
+                vec![(target, LockMode::Exclusive)],
+                false,
+                &Cancellation::default(),
+                &no_progress(),
+            )
+            .await
+        })
+    };
+
+    let (first, second) = tokio::join!(acquire(), acquire());
+    let guards = [first?, second?]
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+
+    assert_eq!(guards.len(), 1);
+
+    for guard in guards {
+        guard.release(backend.as_ref()).await;
+    }
+
+    // A takeover interrupted by a crash doesn't block later ones.
+    backend
+        .create_volume(&target.volume_name(), stale.labels().into())
+        .await?;
+    backend
+        .create_volume(&takeover_volume_name, stale.labels().into())
+        .await?;
+
+    acquire_locks(
+        backend.as_ref(),
+        "create",
+        vec![(target.clone(), LockMode::Exclusive)],
+        false,
+        &Cancellation::default(),
+        &no_progress(),
+    )
+    .await?
+    .release(backend.as_ref())
+    .await;
+
+    assert!(
+        backend
+            .inspect_volume(&takeover_volume_name)
+            .await?
+            .is_none()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shared_locks() -> Result<()> {
+    let root = tempdir()?;
+    let (_, backend) = create_client(&root)?;
+    let target = SnapshotStorage::default().snapshot_lock("snap");
+
+    let acquire = |mode: LockMode, wait: bool| {
+        let backend = backend.clone();
+        let target = target.clone();
+
+        async move {
+            acquire_locks(
+                backend.as_ref(),
+                "restore",
+                vec![(target, mode)],
+                wait,
+                &Cancellation::default(),
+                &no_progress(),
+            )
+            .await
+        }
+    };
+
+    // Readers don't wait for each other, writers wait for all of them.
+    let first = acquire(LockMode::Shared, false).await?;
+    let second = acquire(LockMode::Shared, false).await?;
+
+    assert!(find_live_lock(backend.as_ref(), &target).await?.is_some());
+    assert!(matches!(
+        acquire(LockMode::Exclusive, false).await,
+        Err(Error::Locked(_))
+    ));
+    assert!(
+        backend
+            .inspect_volume(&target.volume_name())
+            .await?
+            .is_none()
+    );
+
+    let writer = tokio::spawn(acquire(LockMode::Exclusive, true));
+
+    first.release(backend.as_ref()).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // New readers back off while the writer waits for the last one.
+    assert!(matches!(
+        acquire(LockMode::Shared, false).await,
+        Err(Error::Locked(_))
+    ));
+
+    second.release(backend.as_ref()).await;
+    let writer = writer.await??;
+
+    assert!(matches!(
+        acquire(LockMode::Shared, false).await,
+        Err(Error::Locked(_))
+    ));
+
+    writer.release(backend.as_ref()).await;
+    acquire(LockMode::Shared, false)
+        .await?
+        .release(backend.as_ref())
+        .await;
+
+    assert!(find_live_lock(backend.as_ref(), &target).await?.is_none());
+    assert!(backend.list_volumes().await?.is_empty());
+
+    Ok(())
+}
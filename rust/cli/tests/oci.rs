@@ -0,0 +1,393 @@
+mod common;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use common::{
+    http::{Request, Response, serve},
+    upload,
+};
+use futures::TryStreamExt;
+use tempfile::tempdir;
+use vsnap::library::{
+    error::Error,
+    metadata::{SNAPSHOT_TAR, SnapshotMetadata},
+    remote::{
+        RemoteStore,
+        oci::{OciReference, OciStore, parse_challenge},
+        s3::sha256_hex,
+    },
+};
+
+const TOKEN: &str = "fake-token";
+
+/// Just enough of the OCI distribution API for vsnap, behind token authentication with the
+/// credentials `user:secret`. Tags are listed one per page.
+#[derive(Default)]
+struct FakeRegistry {
+    address: String,
+    blobs: HashMap<String, Vec<u8>>,
+    uploads: HashMap<String, Vec<u8>>,
+    manifests: HashMap<String, Vec<u8>>,
+    /// Digests by repository and tag.
+    tags: BTreeMap<String, BTreeMap<String, String>>,
+    token_requests: usize,
+    tag_list_requests: usize,
+}
+
+fn digest(content: &[u8]) -> String {
+    format!("sha256:{}", sha256_hex(content))
+}
+
+fn registry_error(status: u16, code: &str) -> Response {
+    Response::new(
+        status,
+        format!(
+            r#"{{"errors":[{{"code":"{}","message":"{}"}}]}}"#,
+            code,
+            code.to_lowercase()
+        ),
+    )
+    .header("content-type", "application/json")
+}
+
+impl FakeRegistry {
+    fn handle(&mut self, request: &Request) -> Response {
+        if request.path == "/token" {
+            self.token_requests += 1;
+
+            let expected = format!("Basic {}", STANDARD.encode("user:secret"));
+
+            return match request.header("authorization") == Some(expected.as_str()) {
+                true => Response::new(200, format!(r#"{{"token":"{}"}}"#, TOKEN)),
+                false => registry_error(401, "UNAUTHORIZED"),
+            };
+        }
+
+        if request.header("authorization") != Some(format!("Bearer {}", TOKEN).as_str()) {
+            return registry_error(401, "UNAUTHORIZED").header(
+                "www-authenticate",
+                format!(r#"Bearer realm="{}/token",service="fake""#, self.address),
+            );
+        }
+
+        let Some(path) = request.path.strip_prefix("/v2/") else {
+            return registry_error(404, "NOT_FOUND");
+        };
+
+        if let Some((repository, upload)) = path.split_once("/blobs/uploads/") {
+            return self.upload(request, repository, upload);
+        }
+
+        if let Some((_, blob)) = path.split_once("/blobs/") {
+            return match self.blobs.get(blob) {
+                Some(content) => Response::new(200, content.clone()),
+                None => registry_error(404, "BLOB_UNKNOWN"),
+            };
+        }
+
+        if let Some((repository, reference)) = path.split_once("/manifests/") {
+            return self.manifest(request, repository, reference);
+        }
+
+        if let Some(repository) = path.strip_suffix("/tags/list") {
+            return self.tag_list(request, repository);
+        }
+
+        registry_error(404, "NOT_FOUND")
+    }
+
+    fn upload(&mut self, request: &Request, repository: &str, upload: &str) -> Response {
+        let location = |upload: &str| format!("/v2/{}/blobs/uploads/{}", repository, upload);
+
+        match (request.method.as_str(), request.query("digest")) {
+            ("POST", _) => {
+                let upload = format!("upload-{}", self.uploads.len() + self.blobs.len());
+
+                self.uploads.insert(upload.clone(), vec![]);
+
+                Response::new(202, "").header("location", location(&upload))
+            }
+            ("PATCH", _) => {
+                let Some(content) = self.uploads.get_mut(upload) else {
+                    return registry_error(404, "BLOB_UPLOAD_UNKNOWN");
+                };
+
+                let start = request
+                    .header("content-range")
+                    .and_then(|range| range.split_once('-'))
+                    .and_then(|(start, _)| start.parse::<usize>().ok());
+
+                if start != Some(content.len()) {
+                    return registry_error(416, "BLOB_UPLOAD_INVALID");
+                }
+
+                content.extend(&request.body);
+
+                Response::new(202, "").header("location", location(upload))
+            }
+            ("PUT", Some(expected)) => {
+                let Some(mut content) = self.uploads.remove(upload) else {
+                    return registry_error(404, "BLOB_UPLOAD_UNKNOWN");
+                };
+
+                content.extend(&request.body);
+
+                if digest(&content) != expected {
+                    return registry_error(400, "DIGEST_INVALID");
+                }
+
+                self.blobs.insert(expected.to_string(), content);
+
+                Response::new(201, "")
+            }
+            _ => registry_error(405, "UNSUPPORTED"),
+        }
+    }
+
+    fn manifest(&mut self, request: &Request, repository: &str, reference: &str) -> Response {
+        let resolved = match reference.starts_with("sha256:") {
+            true => Some(reference.to_string()),
+            false => self
+                .tags
+                .get(repository)
+                .and_then(|tags| tags.get(reference))
+                .cloned(),
+        };
+
+        match request.method.as_str() {
+            "PUT" => {
+                let manifest_digest = digest(&request.body);
+
+                self.manifests
+                    .insert(manifest_digest.clone(), request.body.clone());
+                self.tags
+                    .entry(repository.to_string())
+                    .or_default()
+                    .insert(reference.to_string(), manifest_digest.clone());
+
+                Response::new(201, "").header("docker-content-digest", manifest_digest)
+            }
+            "GET" => match resolved.and_then(|resolved| {
+                self.manifests
+                    .get(&resolved)
+                    .map(|manifest| (resolved, manifest))
+            }) {
+                Some((resolved, manifest)) => Response::new(200, manifest.clone())
+                    .header("content-type", "application/vnd.oci.image.manifest.v1+json")
+                    .header("docker-content-digest", resolved),
+                None => registry_error(404, "MANIFEST_UNKNOWN"),
+            },
+            "DELETE" if reference.starts_with("sha256:") => {
+                if self.manifests.remove(reference).is_none() {
+                    return registry_error(404, "MANIFEST_UNKNOWN");
+                }
+
+                if let Some(tags) = self.tags.get_mut(repository) {
+                    tags.retain(|_, tagged| tagged != reference);
+                }
+
+                Response::new(202, "")
+            }
+            _ => registry_error(405, "UNSUPPORTED"),
+        }
+    }
+
+    fn tag_list(&mut self, request: &Request, repository: &str) -> Response {
+        self.tag_list_requests += 1;
+
+        let Some(tags) = self.tags.get(repository) else {
+            return registry_error(404, "NAME_UNKNOWN");
+        };
+
+        let mut remaining = tags
+            .keys()
+            .filter(|tag| request.query("last").is_none_or(|last| tag.as_str() > last));
+
+        let page = remaining.next().cloned().into_iter().collect::<Vec<_>>();
+        let body = serde_json::json!({ "name": repository, "tags": page }).to_string();
+
+        match (remaining.next(), page.last()) {
+            (Some(_), Some(last)) => Response::new(200, body).header(
+                "link",
+                format!(
+                    r#"</v2/{}/tags/list?n=1&last={}>; rel="next""#,
+                    repository, last
+                ),
+            ),
+            _ => Response::new(200, body),
+        }
+    }
+}
+
+async fn fake_registry() -> Result<(String, Arc<Mutex<FakeRegistry>>)> {
+    let fake = Arc::new(Mutex::new(FakeRegistry::default()));
+    let state = fake.clone();
+    let address = serve(move |request| state.lock().unwrap().handle(&request)).await?;
+
+    fake.lock().unwrap().address = address.clone();
+
+    Ok((address.trim_start_matches("http://").to_string(), fake))
+}
+
+#[test]
+fn test_parse_reference() -> anyhow::Result<()> {
+    let reference = OciReference::parse("localhost:5000/snapshots/db:seeded")?;
+
+    assert_eq!(reference.registry, "localhost:5000");
+    assert_eq!(reference.repository, "snapshots/db");
+    assert_eq!(reference.reference, "seeded");
+    assert_eq!(reference.to_string(), "localhost:5000/snapshots/db:seeded");
+
+    let reference = OciReference::parse("ghcr.io/team/snapshots")?;
+
+    assert_eq!(reference.registry, "ghcr.io");
+    assert_eq!(reference.reference, "latest");
+
+    let reference = OciReference::parse("snapshots@sha256:abc")?;
+
+    assert_eq!(reference.registry, "docker.io");
+    assert_eq!(reference.repository, "library/snapshots");
+    assert_eq!(reference.reference, "sha256:abc");
+    assert_eq!(
+        reference.to_string(),
+        "docker.io/library/snapshots@sha256:abc"
+    );
+
+    assert!(OciReference::parse("registry.local/").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_challenge() {
+    let challenge = parse_challenge(
+        r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:team/db:pull,push""#,
+    );
+
+    assert_eq!(challenge["scheme"], "bearer");
+    assert_eq!(challenge["realm"], "https://auth.docker.io/token");
+    assert_eq!(challenge["service"], "registry.docker.io");
+    assert_eq!(challenge["scope"], "repository:team/db:pull,push");
+
+    let challenge = parse_challenge(r#"Basic realm="Registry Realm""#);
+
+    assert_eq!(challenge["scheme"], "basic");
+    assert_eq!(challenge["realm"], "Registry Realm");
+}
+
+#[test]
+fn test_insecure_registry() -> anyhow::Result<()> {
+    let mut reference = OciReference::parse("registry:5000/snapshots/db:seeded")?;
+
+    assert_eq!(reference.base_url(), "https://registry:5000");
+
+    reference.insecure = true;
+
+    assert_eq!(reference.base_url(), "http://registry:5000");
+    assert_eq!(
+        OciReference::parse("localhost:5000/snapshots")?.base_url(),
+        "http://localhost:5000"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fake_registry() -> Result<()> {
+    let (registry, fake) = fake_registry().await?;
+    let docker_config = tempdir()?;
+
+    fs::write(
+        docker_config.path().join("config.json"),
+        serde_json::json!({
+            "auths": { registry.clone(): { "auth": STANDARD.encode("user:secret") } }
+        })
+        .to_string(),
+    )?;
+
+    let store = OciStore::new(Some(docker_config.path().to_path_buf()));
+    let repository = format!("{}/snapshots/db", registry);
+    let location = |tag: &str| format!("{}:{}", repository, tag);
+
+    // Bigger than a chunk, so that it goes up in several.
+    let large = (0..20 * 1024 * 1024)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let small = b"small archive".to_vec();
+
+    store.put(&location("a"), upload(&large, false)).await?;
+    store.put(&location("b"), upload(&small, false)).await?;
+
+    let download = store.get(&location("a")).await?;
+    let metadata: SnapshotMetadata = serde_json::from_slice(&download.metadata)?;
+
+    assert_eq!(download.archive_name, SNAPSHOT_TAR);
+    assert_eq!(metadata.archive_size, Some(large.len() as u64));
+    assert_eq!(download.archive.try_concat().await?, large);
+
+    // A plain image in the same repository is no snapshot.
+    {
+        let mut fake = fake.lock().unwrap();
+        let image = br#"{"schemaVersion":2,"config":{"mediaType":"application/vnd.oci.image.config.v1+json","digest":"sha256:0","size":0},"layers":[]}"#;
+
+        fake.manifests.insert(digest(image), image.to_vec());
+        fake.tags
+            .entry("snapshots/db".to_string())
+            .or_default()
+            .insert("image".to_string(), digest(image));
+    }
+
+    assert!(store.get(&location("image")).await.is_err());
+
+    let snapshots = store.list(&repository).await?;
+
+    assert_eq!(
+        snapshots
+            .iter()
+            .map(|snapshot| (snapshot.remote.as_str(), snapshot.size))
+            .collect::<Vec<_>>(),
+        [
+            (location("a").as_str(), Some(large.len() as u64)),
+            (location("b").as_str(), Some(small.len() as u64)),
+        ]
+    );
+    assert!(
+        snapshots
+            .iter()
+            .all(|snapshot| snapshot.modified_at.is_some())
+    );
+    assert!(fake.lock().unwrap().tag_list_requests > 1);
+    assert!(
+        store
+            .list(&format!("{}/snapshots/other", registry))
+            .await?
+            .is_empty()
+    );
+
+    store.delete(&location("a")).await?;
+
+    for result in [
+        store.get(&location("a")).await.map(|_| ()),
+        store.delete(&location("a")).await,
+    ] {
+        assert!(matches!(
+            result.unwrap_err().downcast_ref(),
+            Some(Error::SnapshotNotFound(_))
+        ));
+    }
+
+    assert_eq!(store.list(&repository).await?.len(), 1);
+
+    // A token per repository and access, pushing and pulling db and pulling other, reused for
+    // every request. Nothing works without credentials.
+    assert_eq!(fake.lock().unwrap().token_requests, 3);
+    assert!(OciStore::new(None).get(&location("b")).await.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use anyhow::Result;
+use common::upload;
+use futures::TryStreamExt;
+use tempfile::tempdir;
+use vsnap::library::{
+    error::Error,
+    metadata::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SnapshotMetadata},
+    remote::{
+        RemoteStore,
+        plugin::{PluginStore, find_plugin},
+    },
+};
+
+fn directory_plugin() -> PluginStore {
+    PluginStore::new(
+        "dir",
+        PathBuf::from(env!("CARGO_BIN_EXE_vsnap-backend-dir")),
+    )
+}
+
+#[test]
+fn test_find_plugin() -> Result<()> {
+    let empty = tempdir()?;
+    let plugins = tempdir()?;
+    let program = plugins
+        .path()
+        .join(format!("vsnap-backend-team{}", env::consts::EXE_SUFFIX));
+
+    fs::write(&program, "")?;
+
+    let path = env::join_paths([empty.path(), plugins.path()])?;
+
+    assert_eq!(find_plugin("team", &path), Some(program));
+    assert_eq!(find_plugin("other", &path), None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_directory_plugin() -> Result<()> {
+    let root = tempdir()?;
+    let store = directory_plugin();
+    let location = root.path().join("dev/snapshot-a").display().to_string();
+    let archive = (0..5000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    store.put(&location, upload(&archive, false)).await?;
+
+    let download = store.get(&location).await?;
+    let metadata: SnapshotMetadata = serde_json::from_slice(&download.metadata)?;
+
+    assert_eq!(download.archive_name, SNAPSHOT_TAR);
+    assert_eq!(metadata.archive_size, Some(5000));
+    assert_eq!(download.archive.try_concat().await?, archive);
+
+    let snapshots = store.list(&root.path().display().to_string()).await?;
+
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].remote, location);
+    assert_eq!(snapshots[0].size, Some(5000));
+
+    // A failing archive never leaves a complete looking snapshot behind.
+    let failed = root.path().join("dev/snapshot-b");
+
+    assert!(
+        store
+            .put(&failed.display().to_string(), upload(&archive, true))
+            .await
+            .is_err()
+    );
+    assert!(!failed.join(SNAPSHOT_METADATA).exists());
+
+    store.delete(&location).await?;
+
+    for result in [
+        store.get(&location).await.map(|_| ()),
+        store.delete(&location).await,
+    ] {
+        assert!(matches!(
+            result.unwrap_err().downcast_ref(),
+            Some(Error::SnapshotNotFound(_))
+        ));
+    }
+
+    assert!(store.list(&location).await?.is_empty());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_chatty_plugin() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let plugins = tempdir()?;
+    let program = plugins.path().join("vsnap-backend-chatty");
+
+    // Fills the stderr pipe before it reads the archive that fills its stdin.
+    fs::write(
+        &program,
+        "#!/bin/sh\nhead -c 1000000 /dev/zero | tr '\\0' x >&2\ncat > /dev/null\necho '{}'\n",
+    )?;
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755))?;
+
+    let store = PluginStore::new("chatty", program);
+    let archive = vec![0; 1_000_000];
+
+    tokio::time::timeout(
+        Duration::from_secs(30),
+        store.put("snapshot-a", upload(&archive, false)),
+    )
+    .await??;
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use std::fs;
+
+use anyhow::Result;
+use tempfile::tempdir;
+use vsnap::library::registry::{image_registry, registry_credentials};
+
+#[test]
+fn test_image_registry() {
+    assert_eq!(image_registry("fominv/vsnap:0.6.0"), "docker.io");
+    assert_eq!(image_registry("alpine"), "docker.io");
+    assert_eq!(
+        image_registry("mirror.local:5000/fominv/vsnap"),
+        "mirror.local:5000"
+    );
+    assert_eq!(image_registry("localhost/vsnap"), "localhost");
+}
+
+#[test]
+fn test_registry_credentials() -> Result<()> {
+    let docker_config = tempdir()?;
+
+    fs::write(
+        docker_config.path().join("config.json"),
+        // "user:secret" and "mirror:token"
+        r#"{
+            "auths": {
+                "https://index.docker.io/v1/": { "auth": "dXNlcjpzZWNyZXQ=" },
+                "mirror.local:5000": { "auth": "bWlycm9yOnRva2Vu" }
+            }
+        }"#,
+    )?;
+
+    let credentials = registry_credentials(docker_config.path(), "fominv/vsnap:0.6.0")?.unwrap();
+
+    assert_eq!(credentials.username.as_deref(), Some("user"));
+    assert_eq!(credentials.password.as_deref(), Some("secret"));
+
+    let credentials =
+        registry_credentials(docker_config.path(), "mirror.local:5000/fominv/vsnap")?.unwrap();
+
+    assert_eq!(credentials.username.as_deref(), Some("mirror"));
+    assert_eq!(credentials.password.as_deref(), Some("token"));
+
+    assert!(registry_credentials(docker_config.path(), "ghcr.io/fominv/vsnap")?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use common::{create_client, write_files};
+use futures::{StreamExt, TryStreamExt};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::Backend,
+    client::{
+        CreateOptions, InspectOptions, ListOptions, PullOptions, PushOptions, RestoreOptions,
+    },
+    error::Error,
+    metadata::ChecksumState,
+    remote::{RemoteDownload, RemoteSnapshot, RemoteStore, RemoteUpload},
+};
+
+#[derive(Clone)]
+struct StoredSnapshot {
+    metadata: Vec<u8>,
+    archive_name: String,
+    archive: Vec<u8>,
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    snapshots: Mutex<HashMap<String, StoredSnapshot>>,
+}
+
+#[async_trait]
+impl RemoteStore for MemoryStore {
+    async fn put(&self, location: &str, upload: RemoteUpload) -> Result<()> {
+        let archive = upload.archive.try_concat().await?;
+
+        self.snapshots.lock().unwrap().insert(
+            location.to_string(),
+            StoredSnapshot {
+                metadata: upload.metadata,
+                archive_name: upload.archive_name,
+                archive,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn get(&self, location: &str) -> Result<RemoteDownload> {
+        let snapshot = self
+            .snapshots
+            .lock()
+            .unwrap()
+            .get(location)
+            .cloned()
+            .ok_or(Error::SnapshotNotFound(location.to_string()))?;
+
+        let chunks = snapshot
+            .archive
+            .chunks(7)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect::<Vec<_>>();
+
+        Ok(RemoteDownload {
+            metadata: snapshot.metadata,
+            archive_name: snapshot.archive_name,
+            archive: futures::stream::iter(chunks).boxed(),
+        })
+    }
+
+    async fn list(&self, location: &str) -> Result<Vec<RemoteSnapshot>> {
+        Ok(self
+            .snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(remote, _)| remote.starts_with(location))
+            .map(|(remote, snapshot)| RemoteSnapshot {
+                remote: remote.clone(),
+                size: Some(snapshot.archive.len() as u64),
+                modified_at: None,
+            })
+            .collect())
+    }
+
+    async fn delete(&self, location: &str) -> Result<()> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .remove(location)
+            .map(|_| ())
+            .ok_or(anyhow!("{} not found", location))
+    }
+}
+
+#[tokio::test]
+async fn test_push_pull() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+    let store = Arc::new(MemoryStore::default());
+    let client = client.with_remote_store("mem", store.clone());
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            compress: true,
+            ..Default::default()
+        })
+        .await?;
+
+    let pushed = client
+        .push(PushOptions {
+            snapshot: "snap".to_string(),
+            remote: "mem://team/".to_string(),
+        })
+        .await?;
+
+    assert_eq!(pushed.remote, "mem://team/snap");
+
+    let remote_snapshots = client.list_remote("mem://team/").await?;
+
+    assert_eq!(remote_snapshots.len(), 1);
+    assert_eq!(remote_snapshots[0].remote, "mem://team/snap");
+    assert_eq!(remote_snapshots[0].size, Some(pushed.size));
+
+    client
+        .pull(PullOptions {
+            remote: "mem://team/snap".to_string(),
+            snapshot: "pulled".to_string(),
+        })
+        .await?;
+
+    let details = client
+        .inspect("pulled", InspectOptions { verify: true })
+        .await?;
+
+    assert_eq!(details.inspection.checksum, ChecksumState::Valid);
+    assert_eq!(
+        details
+            .inspection
+            .metadata
+            .unwrap()
+            .source_volume
+            .as_deref(),
+        Some("source")
+    );
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "pulled".to_string(),
+            volume: "restored".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(
+        fs::read_to_string(backend.volume_path("restored").join("a.txt"))?,
+        "first file"
+    );
+
+    // A corrupted archive is rejected and leaves no snapshot behind.
+    store
+        .snapshots
+        .lock()
+        .unwrap()
+        .get_mut("team/snap")
+        .unwrap()
+        .archive[0] ^= 0xff;
+
+    assert!(
+        client
+            .pull(PullOptions {
+                remote: "mem://team/snap".to_string(),
+                snapshot: "corrupt".to_string(),
+            })
+            .await
+            .is_err()
+    );
+    assert_eq!(client.list(ListOptions::default()).await?.len(), 2);
+
+    client.delete_remote("mem://team/snap").await?;
+
+    assert!(client.list_remote("mem://team/").await?.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,381 @@
+mod common;
+
+use std::{collections::HashMap, fs};
+
+use anyhow::Result;
+use common::{create_client, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::{Backend, VolumeSpec},
+    client::{
+        CloneOptions, CreateOptions, ExistingVolume, ListOptions, RestoreCopiesOptions,
+        RestoreOptions,
+    },
+    config::Config,
+    error::Error,
+    journal::Journal,
+    volume::VolumeOverrides,
+};
+
+#[tokio::test]
+async fn test_restore_targets() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+    let restore = |volume: &str| RestoreOptions {
+        snapshot: "snap".to_string(),
+        volume: volume.to_string(),
+        ..Default::default()
+    };
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    let created = client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let config_path = root.path().join("config.json");
+    let mut config = Config::load_from(&config_path)?;
+    config.protect("prod-*");
+    config.save_to(&config_path)?;
+
+    backend.create_volume("prod-db", Default::default()).await?;
+
+    // Snapshot volumes and protected volumes are never restored into.
+    assert!(matches!(
+        client.restore(restore(&created.volume_name)).await,
+        Err(Error::VolumeProtected(_))
+    ));
+    assert!(matches!(
+        client
+            .restore(RestoreOptions {
+                existing_volume: ExistingVolume::Replace,
+                ..restore("prod-db")
+            })
+            .await,
+        Err(Error::VolumeProtected(_))
+    ));
+
+    // Other names sharing the snapshot prefix are ordinary volumes.
+    client.restore(restore("vsnap-db")).await?;
+
+    assert_eq!(
+        fs::read_to_string(backend.volume_path("vsnap-db").join("a.txt"))?,
+        "first file"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_copies() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "seed".to_string(),
+            compress: true,
+            ..Default::default()
+        })
+        .await?;
+
+    backend
+        .create_volume("test-db-2", Default::default())
+        .await?;
+    backend
+        .create_volume("test-db-other", Default::default())
+        .await?;
+
+    let options = RestoreCopiesOptions {
+        snapshot: "seed".to_string(),
+        name_template: "test-db-{i}".to_string(),
+        count: 3,
+        start: 1,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        client.restore_copies(options.clone()).await,
+        Err(Error::VolumeExists(_))
+    ));
+    assert!(!client.volume_exists("test-db-1").await);
+
+    let restored = client
+        .restore_copies(RestoreCopiesOptions {
+            existing_volume: ExistingVolume::Replace,
+            ..options
+        })
+        .await?;
+
+    assert_eq!(
+        restored.volume_names,
+        vec!["test-db-1", "test-db-2", "test-db-3"]
+    );
+    assert_eq!(restored.replaced_volumes, vec!["test-db-2"]);
+
+    for volume_name in &restored.volume_names {
+        assert_eq!(
+            fs::read_to_string(
+                backend
+                    .volume_path(volume_name)
+                    .join("nested")
+                    .join("b.txt")
+            )?,
+            "second file"
+        );
+    }
+
+    let dropped = client.drop_copies("test-db-{i}").await?;
+
+    assert_eq!(dropped, restored.volume_names);
+    assert!(client.volume_exists("test-db-other").await);
+    assert!(client.find_copies("test-db-{i}").await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clone() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    let cloned = client
+        .clone_volume(CloneOptions {
+            source_volume: "source".to_string(),
+            volume: "experiment".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(cloned.volume_name, "experiment");
+    assert_eq!(
+        fs::read_to_string(
+            backend
+                .volume_path("experiment")
+                .join("nested")
+                .join("b.txt")
+        )?,
+        "second file"
+    );
+    assert!(client.list(ListOptions::default()).await?.is_empty());
+    assert!(
+        Journal::load_from(&Journal::path(&root.path().join("config.json")))?
+            .restores
+            .is_empty()
+    );
+
+    assert!(matches!(
+        client
+            .clone_volume(CloneOptions {
+                source_volume: "source".to_string(),
+                volume: "experiment".to_string(),
+                ..Default::default()
+            })
+            .await,
+        Err(Error::VolumeExists(_))
+    ));
+    assert!(matches!(
+        client
+            .clone_volume(CloneOptions {
+                source_volume: "missing".to_string(),
+                volume: "other".to_string(),
+                ..Default::default()
+            })
+            .await,
+        Err(Error::VolumeNotFound(_))
+    ));
+    assert!(!client.volume_exists("other").await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_inherits_volume_spec() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    let labels = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>()
+    };
+
+    backend
+        .create_volume(
+            "app_db",
+            VolumeSpec {
+                driver: Some("local".to_string()),
+                driver_opts: labels(&[("type", "tmpfs"), ("device", "tmpfs")]),
+                labels: labels(&[
+                    ("com.docker.compose.project", "app"),
+                    ("com.docker.compose.volume", "db"),
+                    ("team", "backend"),
+                ]),
+            },
+        )
+        .await?;
+    write_files(&backend.volume_path("app_db"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "app_db".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "restored".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    let restored = backend.inspect_volume("restored").await?.unwrap();
+
+    assert_eq!(restored.driver, "local");
+    assert_eq!(
+        restored.driver_opts.get("type").map(String::as_str),
+        Some("tmpfs")
+    );
+    assert_eq!(
+        restored.labels,
+        labels(&[
+            ("com.docker.compose.project", "app"),
+            ("com.docker.compose.volume", "db"),
+            ("team", "backend"),
+        ])
+    );
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "experiment".to_string(),
+            overrides: VolumeOverrides {
+                strip_driver_opts: vec!["*".to_string()],
+                labels: labels(&[("team", "data")]),
+                strip_labels: vec!["com.docker.compose.*".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await?;
+
+    let experiment = backend.inspect_volume("experiment").await?.unwrap();
+
+    assert!(experiment.driver_opts.is_empty());
+    assert_eq!(experiment.labels, labels(&[("team", "data")]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_refuses_bind_device() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+
+    backend
+        .create_volume(
+            "data",
+            VolumeSpec {
+                driver: Some("local".to_string()),
+                driver_opts: HashMap::from([
+                    ("type".to_string(), "none".to_string()),
+                    ("o".to_string(), "bind".to_string()),
+                    ("device".to_string(), "/srv/data".to_string()),
+                ]),
+                labels: HashMap::new(),
+            },
+        )
+        .await?;
+    write_files(&backend.volume_path("data"))?;
+
+    client
+        .create(CreateOptions {
+            source_volume: "data".to_string(),
+            snapshot: "snap".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    // Both would write into /srv/data, the directory of the source that still exists.
+    let restore = client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "copy".to_string(),
+            ..Default::default()
+        })
+        .await;
+    let copies = client
+        .restore_copies(RestoreCopiesOptions {
+            snapshot: "snap".to_string(),
+            name_template: "copy-{i}".to_string(),
+            count: 2,
+            ..Default::default()
+        })
+        .await;
+
+    for result in [restore.map(|_| ()), copies.map(|_| ())] {
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+
+    assert!(backend.inspect_volume("copy").await?.is_none());
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "copy".to_string(),
+            overrides: VolumeOverrides {
+                strip_driver_opts: vec!["*".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await?;
+
+    let copy = backend.inspect_volume("copy").await?.unwrap();
+
+    assert!(copy.driver_opts.is_empty());
+    assert!(backend.volume_path("copy").join("a.txt").exists());
+
+    // Resetting the source itself keeps it on its device.
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "data".to_string(),
+            existing_volume: ExistingVolume::Replace,
+            ..Default::default()
+        })
+        .await?;
+
+    let data = backend.inspect_volume("data").await?.unwrap();
+
+    assert_eq!(data.driver_opts.get("device").unwrap(), "/srv/data");
+
+    // Once the source is gone, nothing else writes there.
+    backend.remove_volume("data").await?;
+
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "data-restored".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use std::{collections::HashMap, fs, sync::Arc};
+
+use anyhow::Result;
+use common::{create_client, write_files};
+use tempfile::tempdir;
+use vsnap::library::{
+    backend::{Backend, fake::FakeBackend},
+    client::{
+        CreateOptions, DropOptions, InspectOptions, ListOptions, RestoreOptions, VsnapClient,
+    },
+    error::Error,
+    metadata::ChecksumState,
+};
+
+#[tokio::test]
+async fn test_snapshot_storage() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+    let shop = client
+        .clone()
+        .with_snapshot_prefix("shop-snap-".to_string())
+        .with_snapshot_driver(
+            Some("local".to_string()),
+            HashMap::from([("device".to_string(), "/mnt/big/{volume}".to_string())]),
+        );
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    for client in [&client, &shop] {
+        client
+            .create(CreateOptions {
+                source_volume: "source".to_string(),
+                snapshot: "snap".to_string(),
+                ..Default::default()
+            })
+            .await?;
+    }
+
+    let snapshots = shop.list(ListOptions::default()).await?;
+
+    assert_eq!(snapshots.len(), 1);
+    assert!(snapshots[0].volume_name.starts_with("shop-snap-"));
+    assert_eq!(
+        backend
+            .inspect_volume(&snapshots[0].volume_name)
+            .await?
+            .unwrap()
+            .driver_opts
+            .get("device"),
+        Some(&format!("/mnt/big/{}", snapshots[0].volume_name))
+    );
+
+    shop.drop(DropOptions {
+        all: true,
+        ..Default::default()
+    })
+    .await?;
+
+    assert_eq!(client.list(ListOptions::default()).await?.len(), 1);
+    assert!(matches!(
+        shop.restore(RestoreOptions {
+            snapshot: "snap".to_string(),
+            volume: "shop-snap-1741900000-db".to_string(),
+            ..Default::default()
+        })
+        .await,
+        Err(Error::VolumeProtected(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_dir() -> Result<()> {
+    let root = tempdir()?;
+    let (client, backend) = create_client(&root)?;
+    let snapshot_dir = root.path().join("snapshots");
+    let client = client.with_snapshot_dir(snapshot_dir.clone());
+
+    backend.create_volume("source", Default::default()).await?;
+    write_files(&backend.volume_path("source"))?;
+
+    let created = client
+        .create(CreateOptions {
+            source_volume: "source".to_string(),
+            snapshot: "snap".to_string(),
+            compress: true,
+            ..Default::default()
+        })
+        .await?;
+
+    // The snapshot lives on the host, not in a volume.
+    assert!(snapshot_dir.join(&created.volume_name).is_dir());
+    assert!(!client.volume_exists(&created.volume_name).await);
+
+    let snapshots = client
+        .list(ListOptions {
+            include_size: true,
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].source.as_deref(), Some("source"));
+    assert!(snapshots[0].size.is_some_and(|size| size > 0));
+
+    let details = client
+        .inspect("snap", InspectOptions { verify: true })
+        .await?;
+
+    assert_eq!(details.inspection.checksum, ChecksumState::Valid);
+
+    client.copy("snap", "snap-copy").await?;
+    client
+        .restore(RestoreOptions {
+            snapshot: "snap-copy".to_string(),
+            volume: "restored".to_string(),
+            ..Default::default()
+        })
+        .await?;
+
+    assert_eq!(
+        fs::read_to_string(backend.volume_path("restored").join("a.txt"))?,
+        "first file"
+    );
+
+    client
+        .drop(DropOptions {
+            all: true,
+            ..Default::default()
+        })
+        .await?;
+
+    assert!(client.list(ListOptions::default()).await?.is_empty());
+    assert_eq!(fs::read_dir(&snapshot_dir)?.count(), 0);
+
+    // The directory would be read here but mounted on another machine.
+    let remote = VsnapClient::with_backend(Arc::new(
+        FakeBackend::new(&root.path().join("volumes"), Arc::new(|_, _, _| Ok(())))
+            .with_remote_engine(),
+    ))?
+    .with_config_path(root.path().join("config.json"))
+    .with_snapshot_dir(snapshot_dir);
+
+    assert!(matches!(
+        remote.list(ListOptions::default()).await,
+        Err(Error::InvalidArgument(_))
+    ));
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
+pub mod capabilities;
+pub mod checksum;
 pub mod cli;
 pub mod constant;
-pub mod metadata;
 pub mod progress;
+pub mod signal;
 pub mod snapshot;
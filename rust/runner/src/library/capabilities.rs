@@ -0,0 +1,38 @@
+use vsnap::library::{
+    capabilities::{
+        Capabilities, FEATURE_CHECKSUM, FEATURE_FREE_SPACE, FEATURE_MULTI_RESTORE,
+        FEATURE_SOURCE_NAME, FEATURE_SPACE_CHECK, PROTOCOL_VERSION,
+    },
+    metadata::METADATA_VERSION,
+};
+
+use crate::library::constant::VERSION;
+
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        protocol_version: PROTOCOL_VERSION,
+        version: Some(VERSION.to_string()),
+        commands: [
+            "snapshot",
+            "restore",
+            "clone",
+            "copy",
+            "inspect",
+            "export",
+            "import",
+            "free-space",
+            "capabilities",
+        ]
+        .map(String::from)
+        .to_vec(),
+        compression: vec!["zstd".to_string()],
+        metadata_versions: (1..=METADATA_VERSION).collect(),
+        features: vec![
+            FEATURE_SOURCE_NAME.to_string(),
+            FEATURE_CHECKSUM.to_string(),
+            FEATURE_FREE_SPACE.to_string(),
+            FEATURE_SPACE_CHECK.to_string(),
+            FEATURE_MULTI_RESTORE.to_string(),
+        ],
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Write},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+use vsnap::library::metadata::format_checksum;
+
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    pub fn finalize(mut self) -> io::Result<String> {
+        self.inner.flush()?;
+
+        Ok(format_checksum(self.hasher.finalize().as_slice()))
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes_written = self.inner.write(buf)?;
+
+        self.hasher.update(&buf[..bytes_written]);
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn file_checksum(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut reader, &mut hasher)?;
+
+    Ok(format_checksum(hasher.finalize().as_slice()))
+}
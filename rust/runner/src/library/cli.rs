@@ -1,8 +1,18 @@
-use std::path::PathBuf;
+use std::{
+    io::{Read, Write, stdin, stdout},
+    path::PathBuf,
+};
 
 use clap::{Parser, Subcommand};
 
-use crate::library::snapshot::{restore, snapshot};
+use crate::library::{
+    capabilities::capabilities,
+    signal::handle_termination,
+    snapshot::{
+        SnapshotOptions, clone_tree, copy, export, free_space, import, inspect, restore,
+        restore_many, snapshot,
+    },
+};
 
 #[derive(Parser)]
 pub struct Cli {
@@ -16,12 +26,59 @@ pub enum Commands {
         #[arg(long, short, default_value_t = false)]
         compress: bool,
 
+        #[arg(long)]
+        source_name: Option<String>,
+
+        #[arg(long, visible_alias = "ignore-space", default_value_t = false)]
+        force: bool,
+
         source_path: PathBuf,
         snapshot_path: PathBuf,
     },
     Restore {
+        #[arg(long, visible_alias = "ignore-space", default_value_t = false)]
+        force: bool,
+
+        snapshot_path: PathBuf,
+
+        /// Every directory to restore to, the archive is read once for all of them.
+        #[arg(required = true)]
+        restore_paths: Vec<PathBuf>,
+    },
+    /// Copies a directory tree straight into another directory.
+    Clone {
+        #[arg(long, visible_alias = "ignore-space", default_value_t = false)]
+        force: bool,
+
+        source_path: PathBuf,
+        destination_path: PathBuf,
+    },
+    Copy {
         snapshot_path: PathBuf,
-        restore_path: PathBuf,
+        destination_path: PathBuf,
+    },
+    Inspect {
+        #[arg(long, default_value_t = false)]
+        verify: bool,
+
+        snapshot_path: PathBuf,
+    },
+    /// Writes the archive or metadata of a snapshot to stdout.
+    Export {
+        snapshot_path: PathBuf,
+        file_name: String,
+    },
+    /// Writes stdin to the archive or metadata of a snapshot.
+    Import {
+        snapshot_path: PathBuf,
+        file_name: String,
+    },
+    /// Reports the available and total bytes of the filesystem holding `path`.
+    FreeSpace { path: PathBuf },
+    /// Reports the protocol version and features this runner supports.
+    Capabilities {
+        #[arg(long, default_value_t = false)]
+        json: bool,
     },
 }
 
@@ -34,16 +91,102 @@ pub fn run() -> anyhow::Result<()> {
 
     let args = Cli::parse();
 
-    match args.command {
+    handle_termination()?;
+
+    execute(args.command, &mut stdin(), &mut stdout())
+}
+
+/// Runs a command, reading data from `input` and printing results to `output`. Progress is
+/// always printed to stdout.
+pub fn execute(
+    command: Commands,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> anyhow::Result<()> {
+    match command {
         Commands::Snapshot {
             compress,
+            source_name,
+            force,
             source_path,
             snapshot_path,
-        } => snapshot(&source_path, &snapshot_path, compress)?,
+        } => snapshot(
+            &source_path,
+            &snapshot_path,
+            &SnapshotOptions {
+                compress,
+                source_name,
+                force,
+            },
+        )?,
         Commands::Restore {
+            force,
             snapshot_path,
-            restore_path,
-        } => restore(&snapshot_path, &restore_path)?,
+            restore_paths,
+        } => match restore_paths.as_slice() {
+            [restore_path] => restore(&snapshot_path, restore_path, force)?,
+            restore_paths => restore_many(
+                &snapshot_path,
+                &restore_paths
+                    .iter()
+                    .map(|path| path.as_path())
+                    .collect::<Vec<_>>(),
+                force,
+            )?,
+        },
+        Commands::Clone {
+            force,
+            source_path,
+            destination_path,
+        } => clone_tree(&source_path, &destination_path, force)?,
+        Commands::Copy {
+            snapshot_path,
+            destination_path,
+        } => copy(&snapshot_path, &destination_path)?,
+        Commands::Inspect {
+            verify,
+            snapshot_path,
+        } => writeln!(
+            output,
+            "{}",
+            serde_json::to_string(&inspect(&snapshot_path, verify)?)?
+        )?,
+        Commands::Export {
+            snapshot_path,
+            file_name,
+        } => export(&snapshot_path, &file_name, output)?,
+        Commands::Import {
+            snapshot_path,
+            file_name,
+        } => import(&snapshot_path, &file_name, input)?,
+        Commands::FreeSpace { path } => {
+            writeln!(output, "{}", serde_json::to_string(&free_space(&path)?)?)?
+        }
+        Commands::Capabilities { json: true } => {
+            writeln!(output, "{}", serde_json::to_string(&capabilities())?)?
+        }
+        Commands::Capabilities { json: false } => {
+            let capabilities = capabilities();
+
+            writeln!(output, "protocol: {}", capabilities.protocol_version)?;
+            writeln!(output, "commands: {}", capabilities.commands.join(", "))?;
+            writeln!(
+                output,
+                "compression: {}",
+                capabilities.compression.join(", ")
+            )?;
+            writeln!(
+                output,
+                "metadata versions: {}",
+                capabilities
+                    .metadata_versions
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+            writeln!(output, "features: {}", capabilities.features.join(", "))?;
+        }
     }
 
     Ok(())
@@ -1,4 +1,3 @@
-pub static SNAPSHOT_TAR_ZST: &str = "snapshot.tar.zst";
-pub static SNAPSHOT_TAR: &str = "snapshot.tar";
+pub use vsnap::library::metadata::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST};
 
-pub static SNAPSHOT_METADATA: &str = "metadata.json";
+pub static VERSION: &str = env!("CARGO_PKG_VERSION");
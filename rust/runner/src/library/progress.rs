@@ -7,6 +7,8 @@ use std::{
 
 use vsnap::library::Progress;
 
+use crate::library::signal::check_terminated;
+
 pub struct ProgressReporterWriter<W: Write> {
     inner: W,
     sender: Sender<u64>,
@@ -20,6 +22,8 @@ impl<W: Write> ProgressReporterWriter<W> {
 
 impl<W: Write> Write for ProgressReporterWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        check_terminated()?;
+
         let bytes_written = self.inner.write(buf)?;
 
         self.sender.send(bytes_written as u64).ok();
@@ -45,6 +49,8 @@ impl<R: Read> ProgressReporterReader<R> {
 
 impl<R: Read> Read for ProgressReporterReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        check_terminated()?;
+
         let bytes_read = self.inner.read(buf)?;
 
         self.sender.send(bytes_read as u64).ok();
@@ -72,20 +78,15 @@ impl ProgressListener {
 
     pub fn listen(mut self) -> thread::JoinHandle<()> {
         thread::spawn(move || {
-            loop {
-                match self.receiver.recv() {
-                    Ok(bytes_read) => {
-                        self.progress = min(self.progress + bytes_read, self.total_size);
-
-                        serde_json::to_string(&Progress {
-                            progress: self.progress,
-                            total: self.total_size,
-                        })
-                        .ok()
-                        .map(|x| writeln!(self.stdout, "{}", x).ok());
-                    }
-                    Err(_) => break,
-                }
+            while let Ok(bytes_read) = self.receiver.recv() {
+                self.progress = min(self.progress + bytes_read, self.total_size);
+
+                serde_json::to_string(&Progress {
+                    progress: self.progress,
+                    total: self.total_size,
+                })
+                .ok()
+                .map(|x| writeln!(self.stdout, "{}", x).ok());
             }
         })
     }
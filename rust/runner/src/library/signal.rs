@@ -0,0 +1,27 @@
+use std::{
+    io,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+static TERMINATED: LazyLock<Arc<AtomicBool>> = LazyLock::new(|| Arc::new(AtomicBool::new(false)));
+
+/// Sets a flag on SIGTERM and SIGINT instead of dying mid-write. As the container's PID 1 the
+/// runner would otherwise ignore `docker stop` until it is killed.
+pub fn handle_termination() -> anyhow::Result<()> {
+    for signal in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+        signal_hook::flag::register(signal, TERMINATED.clone())?;
+    }
+
+    Ok(())
+}
+
+/// Fails reads and writes once the runner was asked to stop, unwinding the current command.
+pub fn check_terminated() -> io::Result<()> {
+    match TERMINATED.load(Ordering::Relaxed) {
+        true => Err(io::Error::other("Terminated")),
+        false => Ok(()),
+    }
+}
@@ -1,106 +1,398 @@
-use std::{fs::File, path::Path, sync};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write, stdout},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{self, Arc},
+    thread,
+};
 
 use anyhow::Result;
 use tar::{Archive, Builder};
+use vsnap::library::{
+    DiskSpace, RunnerWarning,
+    channel::{ChannelReader, ChannelWriter},
+    metadata::{ChecksumState, SnapshotInspection, SnapshotMetadata},
+};
 use zstd::Encoder;
 
 use crate::library::{
-    constant::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST},
-    metadata::SnapshotMetadata,
+    checksum::{ChecksumWriter, file_checksum},
+    constant::{SNAPSHOT_METADATA, SNAPSHOT_TAR, SNAPSHOT_TAR_ZST, VERSION},
     progress::{ProgressListener, ProgressReporterReader, ProgressReporterWriter},
 };
 
-pub fn snapshot(source_path: &Path, snapshot_path: &Path, compress: bool) -> Result<()> {
+#[derive(Default)]
+pub struct SnapshotOptions {
+    pub compress: bool,
+    pub source_name: Option<String>,
+    /// Only warn when the snapshot volume looks too small.
+    pub force: bool,
+}
+
+struct TreeStats {
+    total_size: u64,
+    file_count: u64,
+    dir_count: u64,
+}
+
+pub fn snapshot(source_path: &Path, snapshot_path: &Path, options: &SnapshotOptions) -> Result<()> {
     let (sender, receiver) = sync::mpsc::channel::<u64>();
-    let total_size = calculate_total_size(source_path)?;
+    let stats = calculate_tree_stats(source_path)?;
 
-    SnapshotMetadata::new(total_size).write(&snapshot_path.join(SNAPSHOT_METADATA))?;
-    ProgressListener::new(total_size, receiver).listen();
+    // The uncompressed size is an upper bound, compression usually needs less.
+    check_free_space(snapshot_path, stats.total_size, options.force)?;
 
-    match compress {
-        true => {
-            let tar_file = File::create(&snapshot_path.join(SNAPSHOT_TAR_ZST))?;
-            compress_dir(source_path, tar_file, sender)?;
+    ProgressListener::new(stats.total_size, receiver).listen();
+
+    let archive_path = match options.compress {
+        true => snapshot_path.join(SNAPSHOT_TAR_ZST),
+        false => snapshot_path.join(SNAPSHOT_TAR),
+    };
+
+    let write_archive = || -> Result<String> {
+        let mut writer = ChecksumWriter::new(BufWriter::new(File::create(&archive_path)?));
+
+        match options.compress {
+            true => compress_dir(source_path, &mut writer, sender)?,
+            false => tar_dir(source_path, &mut writer, sender)?,
         }
-        false => {
-            let tar_file = File::create(snapshot_path.join(SNAPSHOT_TAR))?;
-            tar_dir(source_path, tar_file, sender)?;
+
+        Ok(writer.finalize()?)
+    };
+
+    // A truncated archive is removed rather than left behind looking like a snapshot.
+    let checksum = match write_archive() {
+        Ok(checksum) => checksum,
+        Err(e) => {
+            fs::remove_file(&archive_path).ok();
+            return Err(e);
         }
+    };
+
+    // The metadata is written last so that its presence marks a complete snapshot.
+    SnapshotMetadata {
+        source_volume: options.source_name.clone(),
+        compression: options.compress.then(|| "zstd".to_string()),
+        archive_size: Some(fs::metadata(&archive_path)?.len()),
+        file_count: Some(stats.file_count),
+        dir_count: Some(stats.dir_count),
+        checksum: Some(checksum),
+        tool_version: Some(VERSION.to_string()),
+        created_at: Some(unix_timestamp()),
+        ..SnapshotMetadata::new(stats.total_size)
     }
+    .write(&snapshot_path.join(SNAPSHOT_METADATA))?;
 
     Ok(())
 }
 
-pub fn restore(snapshot_path: &Path, restore_path: &Path) -> Result<()> {
+pub fn restore(snapshot_path: &Path, restore_path: &Path, force: bool) -> Result<()> {
+    restore_many(snapshot_path, &[restore_path], force)
+}
+
+/// Restores a snapshot into several directories at once, reading and decompressing the archive
+/// only once.
+pub fn restore_many(snapshot_path: &Path, restore_paths: &[&Path], force: bool) -> Result<()> {
     let (sender, receiver) = sync::mpsc::channel::<u64>();
     let total_size = SnapshotMetadata::read(&snapshot_path.join(SNAPSHOT_METADATA))?.total_size;
 
+    // Directories on the same filesystem share its free space.
+    let mut filesystems = HashMap::<u64, (&Path, u64)>::new();
+
+    for restore_path in restore_paths {
+        filesystems
+            .entry(fs::metadata(restore_path)?.dev())
+            .or_insert((restore_path, 0))
+            .1 += 1;
+    }
+
+    for (restore_path, count) in filesystems.into_values() {
+        check_free_space(restore_path, total_size * count, force)?;
+    }
+
     ProgressListener::new(total_size, receiver).listen();
 
     let is_compressed = snapshot_path.join(SNAPSHOT_TAR_ZST).exists();
 
-    match is_compressed {
-        true => {
-            let tar_file = File::open(snapshot_path.join(SNAPSHOT_TAR_ZST))?;
-            decompress_tar(tar_file, restore_path, sender)?;
+    let reader: Box<dyn Read> = match is_compressed {
+        true => Box::new(zstd::Decoder::new(BufReader::new(File::open(
+            snapshot_path.join(SNAPSHOT_TAR_ZST),
+        )?))?),
+        false => Box::new(BufReader::new(File::open(
+            snapshot_path.join(SNAPSHOT_TAR),
+        )?)),
+    };
+    let reader = ProgressReporterReader::new(reader, sender);
+
+    match restore_paths {
+        [restore_path] => Archive::new(reader).unpack(restore_path)?,
+        restore_paths => unpack_to_all(reader, restore_paths)?,
+    }
+
+    Ok(())
+}
+
+/// Copies a directory tree into another directory, keeping ownership, permissions, timestamps and
+/// symlinks, without writing an archive in between.
+pub fn clone_tree(source_path: &Path, destination_path: &Path, force: bool) -> Result<()> {
+    let (sender, receiver) = sync::mpsc::channel::<u64>();
+    let total_size = calculate_tree_stats(source_path)?.total_size;
+
+    check_free_space(destination_path, total_size, force)?;
+
+    ProgressListener::new(total_size, receiver).listen();
+
+    thread::scope(|scope| {
+        let (chunk_sender, chunk_receiver) = sync::mpsc::sync_channel::<Arc<[u8]>>(16);
+
+        let unpacker = scope.spawn(move || {
+            let mut archive = Archive::new(ChannelReader::new(chunk_receiver));
+
+            archive.set_preserve_permissions(true);
+            archive.set_preserve_ownerships(true);
+            archive.set_preserve_mtime(true);
+            archive.unpack(destination_path)
+        });
+
+        let pack = || -> Result<()> {
+            let mut archive = Builder::new(ProgressReporterWriter::new(
+                ChannelWriter::new(chunk_sender),
+                sender,
+            ));
+
+            archive.follow_symlinks(false);
+            archive.append_dir_all("./", source_path)?;
+            archive.finish()?;
+
+            Ok(())
+        };
+
+        // The builder hangs up when it is dropped, which lets the unpacker finish.
+        let pack_result = pack();
+        let unpack_result = unpacker
+            .join()
+            .map_err(|_| anyhow::anyhow!("Unpacking panicked"))?;
+
+        // A failed unpacker hangs up, so its error explains why the builder couldn't write.
+        match (pack_result, unpack_result) {
+            (Ok(()), unpack_result) => Ok(unpack_result?),
+            (Err(e), Err(unpack_error)) if is_broken_pipe(&e) => Err(unpack_error.into()),
+            (Err(e), _) => Err(e),
+        }
+    })
+}
+
+pub fn copy(snapshot_path: &Path, destination_path: &Path) -> Result<()> {
+    let (sender, receiver) = sync::mpsc::channel::<u64>();
+    let total_size = calculate_tree_stats(snapshot_path)?.total_size;
+
+    ProgressListener::new(total_size, receiver).listen();
+
+    let file_names = [SNAPSHOT_TAR, SNAPSHOT_TAR_ZST, SNAPSHOT_METADATA];
+
+    // The metadata is copied last so that an interrupted copy is recognisably incomplete.
+    let copy_files = || -> Result<()> {
+        for file_name in file_names {
+            let source_file = snapshot_path.join(file_name);
+
+            if !source_file.exists() {
+                continue;
+            }
+
+            let mut reader = std::io::BufReader::new(File::open(source_file)?);
+            let mut writer = ProgressReporterWriter::new(
+                BufWriter::new(File::create(destination_path.join(file_name))?),
+                sender.clone(),
+            );
+
+            std::io::copy(&mut reader, &mut writer)?;
+            writer.flush()?;
         }
-        false => {
-            let tar_file = File::open(snapshot_path.join(SNAPSHOT_TAR))?;
-            untar_dir(tar_file, restore_path, sender)?;
+
+        Ok(())
+    };
+
+    copy_files().inspect_err(|_| {
+        for file_name in file_names {
+            fs::remove_file(destination_path.join(file_name)).ok();
         }
+    })
+}
+
+/// Writes one file of a snapshot to `output`, e.g. to upload it elsewhere.
+pub fn export(snapshot_path: &Path, file_name: &str, output: &mut dyn Write) -> Result<()> {
+    let mut reader = BufReader::new(File::open(snapshot_file(snapshot_path, file_name)?)?);
+
+    io::copy(&mut reader, output)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+/// Writes `input` to one file of a snapshot, e.g. downloaded from elsewhere. A partially
+/// written file is removed.
+pub fn import(snapshot_path: &Path, file_name: &str, input: &mut dyn Read) -> Result<()> {
+    let path = snapshot_file(snapshot_path, file_name)?;
+
+    let mut write_file = || -> Result<()> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+
+        io::copy(input, &mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        Ok(())
+    };
+
+    write_file().inspect_err(|_| {
+        fs::remove_file(&path).ok();
+    })
+}
+
+/// Only the archive and the metadata can be exported or imported.
+fn snapshot_file(snapshot_path: &Path, file_name: &str) -> Result<PathBuf> {
+    match [SNAPSHOT_TAR, SNAPSHOT_TAR_ZST, SNAPSHOT_METADATA].contains(&file_name) {
+        true => Ok(snapshot_path.join(file_name)),
+        false => Err(anyhow::anyhow!("Not a snapshot file: {}", file_name)),
+    }
+}
+
+pub fn inspect(snapshot_path: &Path, verify: bool) -> Result<SnapshotInspection> {
+    let metadata = SnapshotMetadata::read(&snapshot_path.join(SNAPSHOT_METADATA)).ok();
+
+    let archive = [SNAPSHOT_TAR_ZST, SNAPSHOT_TAR]
+        .into_iter()
+        .find(|file_name| snapshot_path.join(file_name).exists());
+
+    let archive_size = archive
+        .map(|file_name| fs::metadata(snapshot_path.join(file_name)))
+        .transpose()?
+        .map(|m| m.len());
+
+    let expected_checksum = metadata.as_ref().and_then(|m| m.checksum.as_ref());
+
+    let checksum = match (expected_checksum, archive, verify) {
+        (None, _, _) => ChecksumState::Unavailable,
+        (Some(_), None, _) => ChecksumState::Invalid,
+        (Some(_), Some(_), false) => ChecksumState::Unverified,
+        (Some(expected), Some(file_name), true) => {
+            match file_checksum(&snapshot_path.join(file_name))? == *expected {
+                true => ChecksumState::Valid,
+                false => ChecksumState::Invalid,
+            }
+        }
+    };
+
+    Ok(SnapshotInspection {
+        metadata,
+        archive: archive.map(|file_name| file_name.to_string()),
+        archive_size,
+        checksum,
+    })
+}
+
+pub fn free_space(path: &Path) -> Result<DiskSpace> {
+    let stats = nix::sys::statvfs::statvfs(path)?;
+    let fragment_size = stats.fragment_size() as u64;
+
+    Ok(DiskSpace {
+        available: stats.blocks_available() as u64 * fragment_size,
+        total: stats.blocks() as u64 * fragment_size,
+    })
+}
+
+/// Refuses to start writing `required` bytes to `path` when they won't fit, or only warns when
+/// `force` is set.
+fn check_free_space(path: &Path, required: u64, force: bool) -> Result<()> {
+    let Err(message) = free_space(path)?.check_fits(required) else {
+        return Ok(());
+    };
+
+    if !force {
+        return Err(anyhow::anyhow!(
+            "Not enough disk space: {}, free up space or use --ignore-space to try anyway",
+            message
+        ));
     }
 
+    let mut stdout = stdout();
+
+    writeln!(
+        stdout,
+        "{}",
+        serde_json::to_string(&RunnerWarning {
+            warning: format!("Disk may run full: {}", message),
+        })?
+    )?;
+    stdout.flush()?;
+
     Ok(())
 }
 
-fn calculate_total_size(path: &Path) -> Result<u64> {
-    let total_size = walkdir::WalkDir::new(path)
+fn calculate_tree_stats(path: &Path) -> Result<TreeStats> {
+    let mut stats = TreeStats {
+        total_size: 0,
+        file_count: 0,
+        dir_count: 0,
+    };
+
+    walkdir::WalkDir::new(path)
+        .min_depth(1)
         .follow_links(false)
         .into_iter()
         .filter_map(|e| e.ok())
         .filter_map(|e| e.metadata().ok())
-        .filter_map(|m| m.is_file().then(|| m.len()))
-        .sum();
+        .for_each(|m| {
+            if m.is_file() {
+                stats.total_size += m.len();
+                stats.file_count += 1;
+            } else if m.is_dir() {
+                stats.dir_count += 1;
+            }
+        });
 
-    Ok(total_size)
+    Ok(stats)
 }
 
-fn compress_dir(
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+fn unix_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn compress_dir<W: Write>(
     dir_to_compress: &Path,
-    tar_file: File,
+    writer: W,
     sender: sync::mpsc::Sender<u64>,
 ) -> anyhow::Result<()> {
-    let buffered_writer = std::io::BufWriter::new(tar_file);
-    let mut encoder = Encoder::new(buffered_writer, 0)?.auto_finish();
+    let mut encoder = Encoder::new(writer, 0)?;
     let mut archive = Builder::new(ProgressReporterWriter::new(&mut encoder, sender));
 
     archive.append_dir_all("./", dir_to_compress)?;
-    archive.finish()?;
-
-    Ok(())
-}
-
-fn decompress_tar(
-    tar_file: File,
-    destination_dir: &Path,
-    sender: sync::mpsc::Sender<u64>,
-) -> anyhow::Result<()> {
-    let buffered_reader = std::io::BufReader::new(tar_file);
-    let mut decoder = zstd::Decoder::new(buffered_reader)?;
-    let mut archive = Archive::new(ProgressReporterReader::new(&mut decoder, sender));
+    archive.into_inner()?;
 
-    archive.unpack(destination_dir)?;
+    encoder.finish()?;
 
     Ok(())
 }
 
-fn tar_dir(
+fn tar_dir<W: Write>(
     dir_to_tar: &Path,
-    tar_file: File,
+    writer: W,
     sender: sync::mpsc::Sender<u64>,
 ) -> anyhow::Result<()> {
-    let buffered_writer = std::io::BufWriter::new(tar_file);
-    let mut archive = Builder::new(ProgressReporterWriter::new(buffered_writer, sender));
+    let mut archive = Builder::new(ProgressReporterWriter::new(writer, sender));
 
     archive.append_dir_all("./", dir_to_tar)?;
     archive.finish()?;
@@ -108,15 +400,56 @@ fn tar_dir(
     Ok(())
 }
 
-fn untar_dir(
-    tar_file: File,
-    destination_dir: &Path,
-    sender: sync::mpsc::Sender<u64>,
-) -> anyhow::Result<()> {
-    let buffered_reader = std::io::BufReader::new(tar_file);
-    let mut archive = Archive::new(ProgressReporterReader::new(buffered_reader, sender));
+/// Unpacks one tar stream into every destination, each in its own thread.
+fn unpack_to_all<R: Read>(mut reader: R, destination_dirs: &[&Path]) -> Result<()> {
+    thread::scope(|scope| {
+        let (mut senders, unpackers): (Vec<_>, Vec<_>) = destination_dirs
+            .iter()
+            .map(|destination_dir| {
+                let (sender, receiver) = sync::mpsc::sync_channel::<Arc<[u8]>>(16);
+                let unpacker = scope.spawn(move || {
+                    Archive::new(ChannelReader::new(receiver)).unpack(destination_dir)
+                });
 
-    archive.unpack(destination_dir)?;
+                (Some(sender), unpacker)
+            })
+            .unzip();
 
-    Ok(())
+        let mut buffer = vec![0; 256 * 1024];
+
+        let read_result = loop {
+            let chunk: Arc<[u8]> = match reader.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(bytes_read) => Arc::from(&buffer[..bytes_read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            };
+
+            // An unpacker stops early at the end of the archive or on an error, which joining it
+            // reports.
+            for sender in senders.iter_mut() {
+                if sender
+                    .as_ref()
+                    .is_some_and(|sender| sender.send(chunk.clone()).is_err())
+                {
+                    *sender = None;
+                }
+            }
+        };
+
+        drop(senders);
+
+        let results = unpackers
+            .into_iter()
+            .map(|unpacker| unpacker.join())
+            .collect::<Vec<_>>();
+
+        read_result?;
+
+        for result in results {
+            result.map_err(|_| anyhow::anyhow!("Unpacking panicked"))??;
+        }
+
+        Ok(())
+    })
 }
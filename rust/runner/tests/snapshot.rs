@@ -3,7 +3,10 @@ use std::{fs, path::Path};
 use anyhow::Result;
 use rand::{Rng, SeedableRng, distr::Alphanumeric, rngs::StdRng};
 use tempfile::tempdir;
-use vsnap_runner::library::snapshot::{restore, snapshot};
+use vsnap::library::metadata::ChecksumState;
+use vsnap_runner::library::snapshot::{
+    SnapshotOptions, clone_tree, copy, free_space, inspect, restore, restore_many, snapshot,
+};
 use walkdir::WalkDir;
 
 fn create_random_files(
@@ -57,7 +60,7 @@ fn compare_directories(dir1: &Path, dir2: &Path) -> Result<()> {
         "Directory entry count mismatch"
     );
 
-    for (entry1, entry2) in entries1.into_iter().zip(entries2.into_iter()) {
+    for (entry1, entry2) in entries1.into_iter().zip(entries2) {
         let entry1 = entry1?;
         let entry2 = entry2?;
 
@@ -112,8 +115,15 @@ fn test_snapshot_restore_uncompressed() -> Result<()> {
 
     create_random_files(source_dir.path(), 5, 3, &mut rng)?;
 
-    snapshot(source_dir.path(), snapshot_dir.path(), false)?;
-    restore(snapshot_dir.path(), target_dir.path())?;
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: false,
+            ..Default::default()
+        },
+    )?;
+    restore(snapshot_dir.path(), target_dir.path(), false)?;
 
     compare_directories(source_dir.path(), target_dir.path())?;
 
@@ -130,8 +140,15 @@ fn test_snapshot_restore_compressed() -> Result<()> {
 
     create_random_files(source_dir.path(), 5, 3, &mut rng)?;
 
-    snapshot(source_dir.path(), snapshot_dir.path(), true)?;
-    restore(snapshot_dir.path(), target_dir.path())?;
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: true,
+            ..Default::default()
+        },
+    )?;
+    restore(snapshot_dir.path(), target_dir.path(), false)?;
 
     compare_directories(source_dir.path(), target_dir.path())?;
 
@@ -146,8 +163,15 @@ fn test_snapshot_restore_empty_dir_uncompressed() -> Result<()> {
 
     fs::create_dir_all(source_dir.path().join("empty_dir"))?;
 
-    snapshot(source_dir.path(), snapshot_dir.path(), false)?;
-    restore(snapshot_dir.path(), target_dir.path())?;
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: false,
+            ..Default::default()
+        },
+    )?;
+    restore(snapshot_dir.path(), target_dir.path(), false)?;
 
     compare_directories(source_dir.path(), target_dir.path())?;
 
@@ -162,8 +186,15 @@ fn test_snapshot_restore_empty_dir_compressed() -> Result<()> {
 
     fs::create_dir_all(source_dir.path().join("empty_dir"))?;
 
-    snapshot(source_dir.path(), snapshot_dir.path(), true)?;
-    restore(snapshot_dir.path(), target_dir.path())?;
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: true,
+            ..Default::default()
+        },
+    )?;
+    restore(snapshot_dir.path(), target_dir.path(), false)?;
 
     compare_directories(source_dir.path(), target_dir.path())?;
 
@@ -176,8 +207,15 @@ fn test_snapshot_restore_no_files_uncompressed() -> Result<()> {
     let snapshot_dir = tempdir()?;
     let target_dir = tempdir()?;
 
-    snapshot(source_dir.path(), snapshot_dir.path(), false)?;
-    restore(snapshot_dir.path(), target_dir.path())?;
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: false,
+            ..Default::default()
+        },
+    )?;
+    restore(snapshot_dir.path(), target_dir.path(), false)?;
 
     compare_directories(source_dir.path(), target_dir.path())?;
 
@@ -190,10 +228,154 @@ fn test_snapshot_restore_no_files_compressed() -> Result<()> {
     let snapshot_dir = tempdir()?;
     let target_dir = tempdir()?;
 
-    snapshot(source_dir.path(), snapshot_dir.path(), true)?;
-    restore(snapshot_dir.path(), target_dir.path())?;
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: true,
+            ..Default::default()
+        },
+    )?;
+    restore(snapshot_dir.path(), target_dir.path(), false)?;
 
     compare_directories(source_dir.path(), target_dir.path())?;
 
     Ok(())
 }
+
+#[test]
+fn test_snapshot_copy_restore() -> Result<()> {
+    let source_dir = tempdir()?;
+    let snapshot_dir = tempdir()?;
+    let copy_dir = tempdir()?;
+    let target_dir = tempdir()?;
+
+    let mut rng = StdRng::seed_from_u64(2);
+
+    create_random_files(source_dir.path(), 5, 3, &mut rng)?;
+
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: true,
+            ..Default::default()
+        },
+    )?;
+    copy(snapshot_dir.path(), copy_dir.path())?;
+    restore(copy_dir.path(), target_dir.path(), false)?;
+
+    compare_directories(source_dir.path(), target_dir.path())?;
+    compare_directories(snapshot_dir.path(), copy_dir.path())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_restore_many() -> Result<()> {
+    let source_dir = tempdir()?;
+    let snapshot_dir = tempdir()?;
+    let target_dirs = [tempdir()?, tempdir()?, tempdir()?];
+
+    let mut rng = StdRng::seed_from_u64(4);
+
+    // Larger than one chunk, so that the unpackers share it.
+    create_random_files(source_dir.path(), 50, 80, &mut rng)?;
+
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: true,
+            ..Default::default()
+        },
+    )?;
+    restore_many(
+        snapshot_dir.path(),
+        &target_dirs.each_ref().map(|dir| dir.path()),
+        false,
+    )?;
+
+    for target_dir in &target_dirs {
+        compare_directories(source_dir.path(), target_dir.path())?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_clone_tree() -> Result<()> {
+    let source_dir = tempdir()?;
+    let target_dir = tempdir()?;
+
+    let mut rng = StdRng::seed_from_u64(5);
+
+    create_random_files(source_dir.path(), 5, 3, &mut rng)?;
+    std::os::unix::fs::symlink("missing", source_dir.path().join("link"))?;
+
+    clone_tree(source_dir.path(), target_dir.path(), false)?;
+
+    compare_directories(source_dir.path(), target_dir.path())?;
+    assert_eq!(
+        fs::read_link(target_dir.path().join("link"))?,
+        Path::new("missing")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_inspect() -> Result<()> {
+    let source_dir = tempdir()?;
+    let snapshot_dir = tempdir()?;
+
+    let mut rng = StdRng::seed_from_u64(3);
+
+    create_random_files(source_dir.path(), 5, 3, &mut rng)?;
+
+    snapshot(
+        source_dir.path(),
+        snapshot_dir.path(),
+        &SnapshotOptions {
+            compress: true,
+            source_name: Some("source-volume".to_string()),
+            ..Default::default()
+        },
+    )?;
+
+    let inspection = inspect(snapshot_dir.path(), true)?;
+    let metadata = inspection.metadata.expect("metadata should be present");
+
+    assert_eq!(metadata.source_volume.as_deref(), Some("source-volume"));
+    assert_eq!(metadata.compression.as_deref(), Some("zstd"));
+    assert_eq!(metadata.file_count, Some(15));
+    assert_eq!(metadata.dir_count, Some(3));
+    assert_eq!(metadata.total_size, 1500);
+    assert_eq!(metadata.archive_size, inspection.archive_size);
+    assert_eq!(inspection.checksum, ChecksumState::Valid);
+
+    fs::write(snapshot_dir.path().join("snapshot.tar.zst"), "corrupted")?;
+
+    assert_eq!(
+        inspect(snapshot_dir.path(), true)?.checksum,
+        ChecksumState::Invalid
+    );
+    assert_eq!(
+        inspect(snapshot_dir.path(), false)?.checksum,
+        ChecksumState::Unverified
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_free_space() -> Result<()> {
+    let dir = tempdir()?;
+    let space = free_space(dir.path())?;
+
+    assert!(space.available <= space.total);
+    assert!(space.check_fits(0).is_ok());
+    assert!(space.check_fits(u64::MAX).is_err());
+
+    Ok(())
+}